@@ -42,7 +42,11 @@ pub struct App<'w> {
 
 impl App<'_> {
     pub fn new(options: AppOptions) -> Self {
-        let store = Store::new(options.data_dir.clone());
+        let store = Store::new(
+            options.data_dir.clone(),
+            Duration::seconds(options.autosave_interval as i64),
+            options.keep_snapshots,
+        );
 
         store
             .fs
@@ -58,6 +62,19 @@ impl App<'_> {
     }
 
     pub fn run(&mut self) -> Result<(), EventLoopError> {
+        if let Some(name) = &self.options.snapshot {
+            match self
+                .store
+                .fs
+                .snapshot_save(name, self.options.keep_snapshots)
+            {
+                Ok(snapshot) => tracing::info!("Created snapshot {}", snapshot.path.display()),
+                Err(e) => tracing::error!("Failed to snapshot save '{name}': {e}"),
+            }
+
+            return Ok(());
+        }
+
         EventLoop::new()?.run_app(self)
     }
 }
@@ -183,14 +200,18 @@ pub struct Store {
     pub(crate) input: Input,
     pub(crate) fs: Fs,
     pub(crate) delta_time: DeltaTime,
+    pub(crate) autosave_interval: Duration,
+    pub(crate) keep_snapshots: usize,
 }
 
 impl Store {
-    pub fn new(root_dir: PathBuf) -> Self {
+    pub fn new(root_dir: PathBuf, autosave_interval: Duration, keep_snapshots: usize) -> Self {
         Self {
             input: Input::default(),
-            fs: Fs::new(root_dir),
+            fs: Fs::new(root_dir, server::chunk::migration::migrations()),
             delta_time: DeltaTime::new(),
+            autosave_interval,
+            keep_snapshots,
         }
     }
 }
@@ -280,11 +301,15 @@ impl State {
             Command::OpenMenu(config) => {
                 *self = State::Browsing(Menu::new(config, &ctx.video.painter));
             }
-            Command::StartGame { save } => {
-                let session = Session::create(save, &mut ctx.video, &ctx.store.fs.path().join("assets"));
+            Command::StartGame { save, world } => {
+                let session = Session::create(save, world, ctx.store.autosave_interval, ctx.video, &ctx.store.fs.path().join("assets"));
                 *self = Self::Playing(session);
             }
             Command::Exit => {
+                if let State::Playing(session) = self {
+                    session.exit();
+                }
+
                 ctx.event_loop.exit();
             }
             Command::PauseGame => {
@@ -308,7 +333,7 @@ impl State {
 #[derive(Debug, Clone)]
 pub enum Command {
     OpenMenu(MenuConfig),
-    StartGame { save: Save },
+    StartGame { save: Save, world: Option<String> },
     PauseGame,
     Exit,
 }
@@ -398,6 +423,22 @@ pub struct AppOptions {
     pub sample_count: SampleCount,
     #[arg(default_value = "false", help = "Limit frame rate to display refresh rate", long = "vsync", short = 'v')]
     pub vsync: bool,
+    #[arg(
+        default_value_t = server::autosave::DEFAULT_AUTOSAVE_INTERVAL.whole_seconds() as u64,
+        help = "Seconds between autosaves while playing (0 disables autosaving)",
+        long = "autosave-interval",
+        value_name = "SECONDS",
+    )]
+    pub autosave_interval: u64,
+    #[arg(help = "Snapshot the named save and exit without opening a window", long = "snapshot", value_name = "SAVE")]
+    pub snapshot: Option<String>,
+    #[arg(
+        default_value_t = 10,
+        help = "Number of snapshots kept per save; older snapshots are pruned",
+        long = "keep-snapshots",
+        value_name = "COUNT"
+    )]
+    pub keep_snapshots: usize,
 }
 
 #[derive(Debug)]
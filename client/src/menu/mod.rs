@@ -27,7 +27,7 @@ impl Menu {
         match config {
             MenuConfig::Title => Menu::Title(TitleMenu::new(painter)),
             MenuConfig::Options => Menu::Options(OptionsMenu::new()),
-            MenuConfig::Play => Menu::Play(PlayMenu::new()),
+            MenuConfig::Play => Menu::Play(PlayMenu::new(painter)),
         }
     }
 
@@ -1,16 +1,206 @@
-use crate::app::{Command, Render, Update};
+use std::random::random;
+
+use lib::color::{Color, ColorConsts, Rgba};
+use lib::save::{ChunkCompression, GeneratorConfig, Save, SaveAttributes, SaveError, TerrainMode, WorldAttributes, WorldDescriptor, WorldType, WORLD_FORMAT_VERSION};
+use lib::size::Size2;
+use tracing::{error, info, warn};
+
+use crate::app::{Command, Render, Store, Update};
+use crate::menu::MenuConfig;
+use crate::ui::{Button, ButtonId, LayoutDirection, Ui, UiEvent};
+use crate::video::ui::brush::Text;
+use crate::video::ui::font::FontId;
+use crate::video::ui::Painter;
+
+const DEFAULT_SAVE: &str = "default";
 
 #[derive(Debug)]
-pub struct PlayMenu {}
+pub struct PlayMenu {
+    ui: Ui,
+    start_button_id: ButtonId,
+    world_type_button_ids: [(ButtonId, &'static str); 3],
+    snapshot_button_id: ButtonId,
+    restore_button_id: ButtonId,
+    back_button_id: ButtonId,
+}
 
 impl PlayMenu {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(painter: &Painter) -> Self {
+        let font_id = painter.default_font_id();
+
+        let mut start_button_id = None;
+        let mut flat_button_id = None;
+        let mut void_button_id = None;
+        let mut debug_button_id = None;
+        let mut snapshot_button_id = None;
+        let mut restore_button_id = None;
+        let mut back_button_id = None;
+        Self {
+            ui: Ui::build(painter)
+                .with_background_color(Rgba::from_rgb(1.0, 0.0, 0.0))
+                .with_padding(Size2::new(128., 128.))
+                .with_gap(16.)
+                .with_layout_direction(LayoutDirection::Column)
+                .with_button(button(font_id, "Start"), &mut start_button_id)
+                .with_button(button(font_id, "Flat world"), &mut flat_button_id)
+                .with_button(button(font_id, "Void world"), &mut void_button_id)
+                .with_button(button(font_id, "Debug world"), &mut debug_button_id)
+                .with_button(button(font_id, "Create snapshot"), &mut snapshot_button_id)
+                .with_button(button(font_id, "Restore latest snapshot"), &mut restore_button_id)
+                .with_button(button(font_id, "Back"), &mut back_button_id)
+                .finish(),
+            start_button_id: start_button_id.unwrap(),
+            world_type_button_ids: [
+                (flat_button_id.unwrap(), "flat"),
+                (void_button_id.unwrap(), "void"),
+                (debug_button_id.unwrap(), "debug"),
+            ],
+            snapshot_button_id: snapshot_button_id.unwrap(),
+            restore_button_id: restore_button_id.unwrap(),
+            back_button_id: back_button_id.unwrap(),
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut Update) -> Option<Command> {
+        let mut command = None;
+        for event in self.ui.events(&ctx.input) {
+            command = match *event {
+                UiEvent::Clicked(id) if id == self.start_button_id => start_button_pressed(ctx.store),
+                UiEvent::Clicked(id) if let Some(&(_, name)) = self.world_type_button_ids.iter().find(|(x, _)| *x == id) => {
+                    world_type_button_pressed(ctx.store, name)
+                }
+                UiEvent::Clicked(id) if id == self.snapshot_button_id => {
+                    snapshot_button_pressed(ctx.store);
+                    None
+                }
+                UiEvent::Clicked(id) if id == self.restore_button_id => {
+                    restore_button_pressed(ctx.store);
+                    None
+                }
+                UiEvent::Clicked(id) if id == self.back_button_id => Some(Command::OpenMenu(MenuConfig::Title)),
+                _ => continue,
+            }
+        }
+
+        command
+    }
+
+    pub fn render(&mut self, ctx: &mut Render) {
+        self.ui.render(ctx);
+    }
+}
+
+fn button(font_id: FontId, content: &str) -> Button {
+    Button {
+        padding: Size2::splat(64.),
+        color: Rgba::BLACK,
+        text: Text {
+            font_id,
+            content: content.to_owned(),
+            font_size: 54.0,
+            color: Rgba::WHITE,
+        },
+    }
+}
+
+fn open_default_save(store: &Store) -> Result<Save, SaveError> {
+    store.fs.create_or_open_save(
+        DEFAULT_SAVE,
+        SaveAttributes {
+            title: "Default".to_string(),
+            default_world: WorldAttributes {
+                name: "world".to_string(),
+                descriptor: world_descriptor("Overworld", WorldType::Standard),
+            },
+        },
+    )
+}
+
+fn world_descriptor(title: &str, world_type: WorldType) -> WorldDescriptor {
+    WorldDescriptor {
+        format_version: WORLD_FORMAT_VERSION,
+        title: title.to_string(),
+        seed: random(..),
+        chunk_compression: ChunkCompression::default(),
+        terrain: TerrainMode::Density,
+        generator: GeneratorConfig::default(),
+        world_type,
+        spawn: None,
+    }
+}
+
+fn start_button_pressed(store: &Store) -> Option<Command> {
+    match open_default_save(store) {
+        Ok(save) => Some(Command::StartGame { save, world: None }),
+        Err(e) => {
+            error!("Failed to open save '{DEFAULT_SAVE}': {e}");
+            None
+        }
+    }
+}
+
+fn world_type_button_pressed(store: &Store, name: &str) -> Option<Command> {
+    let world_type = match name {
+        "flat" => WorldType::flat(),
+        "void" => WorldType::Void,
+        _ => WorldType::DebugGrid,
+    };
+
+    let result = open_default_save(store).and_then(|save| {
+        match save.world(name) {
+            Err(SaveError::WorldNotFound(_)) => {
+                save.create_world(WorldAttributes {
+                    name: name.to_string(),
+                    descriptor: world_descriptor(name, world_type),
+                })?;
+                info!("Created {name} world in save '{DEFAULT_SAVE}'");
+            }
+            result => {
+                result?;
+            }
+        }
+
+        Ok(save)
+    });
+
+    match result {
+        Ok(save) => Some(Command::StartGame { save, world: Some(name.to_string()) }),
+        Err(e) => {
+            error!("Failed to open {name} world in save '{DEFAULT_SAVE}': {e}");
+            None
+        }
     }
+}
 
-    pub fn update(&mut self, _: &mut Update) -> Option<Command> {
-        None
+fn snapshot_button_pressed(store: &Store) {
+    match store
+        .fs
+        .snapshot_save(DEFAULT_SAVE, store.keep_snapshots)
+    {
+        Ok(snapshot) => info!("Created snapshot {}", snapshot.path.display()),
+        Err(e) => error!("Failed to snapshot save '{DEFAULT_SAVE}': {e}"),
     }
+}
+
+fn restore_button_pressed(store: &Store) {
+    let result = store
+        .fs
+        .open_save(DEFAULT_SAVE)
+        .and_then(|save| {
+            let Some(snapshot) = save
+                .snapshots(store.fs.snapshots_path())?
+                .pop()
+            else {
+                warn!("Save '{DEFAULT_SAVE}' has no snapshots to restore");
+                return Ok(());
+            };
 
-    pub fn render(&mut self, _: &mut Render) {}
+            save.restore_snapshot(&snapshot, store.fs.migrations())?;
+            info!("Restored save '{DEFAULT_SAVE}' from snapshot {}", snapshot.path.display());
+            Ok(())
+        });
+
+    if let Err(e) = result {
+        error!("Failed to restore save '{DEFAULT_SAVE}': {e}");
+    }
 }
@@ -1,7 +1,4 @@
-use std::random::random;
-
 use lib::color::{Color, ColorConsts, Rgba};
-use lib::save::{SaveAttributes, WorldAttributes, WorldDescriptor};
 use lib::size::Size2;
 
 use crate::app::{Command, Render, Update};
@@ -87,7 +84,7 @@ impl TitleMenu {
         let mut command = None;
         for event in self.ui.events(&ctx.input) {
             command = match event {
-                &UiEvent::Clicked(id) if id == self.play_button_id => play_button_pressed(ctx),
+                &UiEvent::Clicked(id) if id == self.play_button_id => Some(Command::OpenMenu(MenuConfig::Play)),
                 &UiEvent::Clicked(id) if id == self.options_button_id => Some(Command::OpenMenu(MenuConfig::Options)),
                 &UiEvent::Clicked(id) if id == self.quit_button_id => Some(Command::Exit),
                 _ => continue,
@@ -101,25 +98,3 @@ impl TitleMenu {
         self.ui.render(ctx);
     }
 }
-
-fn play_button_pressed(ctx: &Update) -> Option<Command> {
-    let save = ctx
-        .store
-        .fs
-        .create_or_open_save(
-            "default",
-            SaveAttributes {
-                title: "Default".to_string(),
-                default_world: WorldAttributes {
-                    name: "world".to_string(),
-                    descriptor: WorldDescriptor {
-                        title: "Overworld".to_string(),
-                        seed: random(),
-                    },
-                },
-            },
-        )
-        .unwrap();
-
-    Some(Command::StartGame { save })
-}
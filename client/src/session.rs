@@ -8,11 +8,13 @@ use server::handle::GameHandle;
 use server::{Game, Options};
 use std::path::Path;
 use time::Duration;
+use tracing::error;
 use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 use winit::window::CursorGrabMode;
 
 use crate::app::{Command, Render, Update};
+use crate::menu::MenuConfig;
 use crate::video::resource::{Mesh, MeshId, Meshes};
 use crate::video::ui::brush::{Brush, Text};
 use crate::video::world::Vertex3d;
@@ -33,8 +35,13 @@ pub struct Session {
 }
 
 impl Session {
-    pub fn create(save: Save, video: &mut Video, assets_path: &Path) -> Self {
-        let handle = Game::spawn(Options { save });
+    pub fn create(save: Save, world: Option<String>, autosave_interval: Duration, video: &mut Video, assets_path: &Path) -> Self {
+        let handle = Game::spawn(Options {
+            save,
+            autosave_interval,
+            assets_path: assets_path.to_path_buf(),
+            world,
+        });
 
         Self {
             world: World::new(video),
@@ -48,6 +55,15 @@ impl Session {
     }
 
     pub fn update(&mut self, ctx: &mut Update) -> Option<Command> {
+        if let Some(e) = self.handle.failure() {
+            error!("Failed to start the game: {e}");
+            ctx.window
+                .set_cursor_grab(CursorGrabMode::None)
+                .unwrap();
+            ctx.window.set_cursor_visible(true);
+            return Some(Command::OpenMenu(MenuConfig::Play));
+        }
+
         self.fps.update(ctx.dt);
 
         if ctx.input.key_events.contains(&KeyCode::Escape) {
@@ -96,6 +112,12 @@ impl Session {
             chisel.render_each_by_id(self.world.player.targeted_cube_wireframe_id);
         }
 
+        {
+            let mut chisel = ctx.frame.draw_3d(world::RenderType::Translucent);
+            self.world
+                .render_translucent(&self.mesh_ids, &mut chisel);
+        }
+
         {
             let mut brush = ctx.frame.draw_2d();
 
@@ -153,8 +175,13 @@ impl Session {
 
     pub fn set_resolution(&mut self, _: size2u) {}
 
+    pub fn switch_world(&self, name: impl Into<String>) {
+        self.handle.request_world(name);
+    }
+
     pub fn exit(&mut self) {
         self.handle.request_exit();
+        self.handle.wait_for_exit();
     }
 
     pub fn pause(&mut self) {
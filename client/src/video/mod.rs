@@ -41,7 +41,7 @@ impl<'w> Video<'w> {
         let (handle, surface) = gpu::create(target, options.resolution, options.sample_count, options.vsync);
         let mut painter = Painter::create(&handle, options.sample_count, &options.asset_path);
         painter.set_resolution(&handle, options.resolution);
-        let sculptor = Sculptor::create(&handle, options.sample_count);
+        let sculptor = Sculptor::create(&handle, options.sample_count, &options.asset_path);
 
         Self {
             handle,
@@ -12,10 +12,14 @@ struct World {
     light_dir: vec3f,
     fog_color: vec3f,
     fog: vec2f,
+    daylight: f32,
 }
 
 @group(1) @binding(0) var<uniform> world: World;
 
+@group(2) @binding(0) var albedo_sampler: sampler;
+@group(2) @binding(1) var albedo_texture: texture_2d<f32>;
+
 // Vertex shader
 
 struct Vertex {
@@ -31,8 +35,10 @@ struct Instance {
     @location(5) model_2: vec4f,
     @location(6) model_3: vec4f,
     @location(7) color: vec4f,
-    @location(8) light: u32,
-    @location(9) ao: vec4f,
+    @location(8) uv_t: vec2f,
+    @location(9) uv_s: vec2f,
+    @location(10) light: u32,
+    @location(11) ao: vec4f,
 }
 
 @vertex
@@ -50,6 +56,9 @@ fn vs(vert: Vertex, inst: Instance) -> Fragment {
     frag.world_position = world_position.xyz;
     frag.normal = (model * vec4(vert.normal, 0.0)).xyz;
     frag.color = inst.color;
+    // The quad's uv runs along its y axis first, so it is turned to keep images upright on the sides of cubes.
+    frag.uv = inst.uv_t + vec2(vert.uv.y, 1.0 - vert.uv.x) * inst.uv_s;
+    frag.textured = select(0.0, 1.0, any(inst.uv_s != vec2(0.0)));
     frag.light = inst.light;
     frag.ao = inst.ao[vert.index];
 
@@ -58,6 +67,11 @@ fn vs(vert: Vertex, inst: Instance) -> Fragment {
 
 // Fragment shader
 
+const UNLIT: u32 = 0xFFFFFFFFu;
+const MAX_LIGHT: f32 = 15.0;
+const MIN_LIGHT: f32 = 0.04;
+const BLOCK_LIGHT_COLOR: vec3f = vec3(1.0, 0.9, 0.75);
+
 struct Fragment {
     @builtin(position) clip_position: vec4f,
     @location(0) world_position: vec3f,
@@ -65,17 +79,31 @@ struct Fragment {
     @location(2) color: vec4f,
     @location(3) light: u32,
     @location(4) ao: f32,
+    @location(5) uv: vec2f,
+    @location(6) textured: f32,
 }
 
 @fragment
 fn fs(frag: Fragment) -> @location(0) vec4f {
-    if (frag.light != 0) {
+    let texture_color = textureSample(albedo_texture, albedo_sampler, frag.uv);
+
+    if (frag.light == UNLIT) {
         return frag.color;
     }
 
-    let albedo_color = frag.color;
+    // Fully transparent texels are the gaps of cutout materials.
+    if (frag.textured != 0.0 && texture_color.a == 0.0) {
+        discard;
+    }
+
+    let albedo_color = frag.color * mix(vec4(1.0), texture_color, frag.textured);
     let diffuse = max(dot(frag.normal, world.light_dir), 0.0);
-    let lit_color = (diffuse + world.ambient_light) * albedo_color.xyz * frag.ao;
+    // The light is packed with the block light in the lowest byte and the sky light in the next one. Faces are lit
+    // by whichever is brighter, and never fall entirely into darkness.
+    let block_light = BLOCK_LIGHT_COLOR * f32(frag.light & 0xFFu) / MAX_LIGHT;
+    let sky_light = (diffuse + world.ambient_light) * f32((frag.light >> 8u) & 0xFFu) / MAX_LIGHT * world.daylight;
+    let lighting = max(max(sky_light, block_light), vec3(MIN_LIGHT));
+    let lit_color = lighting * albedo_color.xyz * frag.ao;
 
     let fog_amount = smoothstep(world.fog.x - world.fog.y, world.fog.x, length(frag.world_position - camera.position));
     let color_with_fog = mix(lit_color.xyz, world.fog_color, fog_amount);
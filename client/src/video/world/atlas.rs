@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::video::gpu;
+use crate::video::resource::{AtlasTextureCoord, Texture};
+
+const MISSING_IMAGE_SIZE: u32 = 2;
+
+/// The images of every block texture, packed into a single texture.
+#[derive(Debug)]
+pub struct BlockAtlas {
+    pub(crate) texture: Texture,
+    coords: Arc<BlockAtlasCoords>,
+}
+
+/// Where each image of the block atlas is, by the name of the image.
+#[derive(Debug)]
+pub struct BlockAtlasCoords {
+    map: HashMap<String, AtlasTextureCoord>,
+    missing: AtlasTextureCoord,
+}
+
+impl BlockAtlas {
+    /// Packs every PNG image of a directory, named by its file stem. A directory that cannot be read has no images.
+    pub fn create(gpu: &gpu::Handle, dir_path: &Path) -> Self {
+        let mut names = vec![];
+        let mut images = vec![missing_image()];
+
+        match read_dir(dir_path) {
+            Ok(entries) => {
+                for path in entries.flatten().map(|x| x.path()) {
+                    if path.extension().is_none_or(|x| x != "png") {
+                        continue;
+                    }
+
+                    match image::open(&path) {
+                        Ok(image) => {
+                            names.push(path.file_stem().unwrap().to_string_lossy().into_owned());
+                            images.push(image);
+                        }
+                        Err(e) => tracing::error!("Failed to read block texture {}: {e}", path.display()),
+                    }
+                }
+            }
+            Err(e) => tracing::error!("Failed to read block textures from {}: {e}", dir_path.display()),
+        }
+
+        let (texture, coords) = Texture::atlas(gpu, images).expect("Failed to pack block textures");
+        let mut coords = coords.into_iter();
+        let missing = coords.next().unwrap();
+
+        Self {
+            texture,
+            coords: Arc::new(BlockAtlasCoords {
+                map: names.into_iter().zip(coords).collect(),
+                missing,
+            }),
+        }
+    }
+
+    pub fn coords(&self) -> &Arc<BlockAtlasCoords> {
+        &self.coords
+    }
+}
+
+impl BlockAtlasCoords {
+    /// The coordinates of an image, or of a placeholder if the atlas does not have it.
+    pub fn get(&self, name: &str) -> AtlasTextureCoord {
+        self.map
+            .get(name)
+            .copied()
+            .unwrap_or(self.missing)
+    }
+}
+
+/// A checkerboard of magenta and black, shown in place of images that are not in the atlas.
+fn missing_image() -> DynamicImage {
+    RgbaImage::from_fn(MISSING_IMAGE_SIZE, MISSING_IMAGE_SIZE, |x, y| {
+        if (x + y) % 2 == 0 { Rgba([255, 0, 255, 255]) } else { Rgba([0, 0, 0, 255]) }
+    })
+    .into()
+}
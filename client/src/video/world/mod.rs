@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::slice;
 
 pub use vertex::{Instance3d, Vertex3d};
@@ -6,8 +7,12 @@ pub use world::{World, WorldPayload};
 
 use crate::video::camera::VideoCamera;
 use crate::video::gpu;
-use crate::video::resource::{BindGroup, Buffer, CompiledShaders, Meshes, PipelineMap, PipelineOptions, PipelineType, SampleCount, Sets, ShaderSources};
+use crate::video::resource::{
+    BindGroup, Buffer, CompiledShaders, Filter, Meshes, PipelineMap, PipelineOptions, PipelineType, SampleCount, SamplerOptions, Sets, ShaderSources, Texture,
+};
+use crate::video::world::atlas::BlockAtlas;
 
+pub mod atlas;
 pub mod chisel;
 pub mod vertex;
 pub mod world;
@@ -21,16 +26,18 @@ pub struct Sculptor {
     shaders: CompiledShaders,
     pub(crate) meshes: Meshes<Vertex3d>,
     pub(crate) sets: Sets<Instance3d>,
+    atlas: BlockAtlas,
 }
 
 impl Sculptor {
-    pub fn create(gpu: &gpu::Handle, sample_count: SampleCount) -> Self {
+    pub fn create(gpu: &gpu::Handle, sample_count: SampleCount, asset_path: &Path) -> Self {
         let camera_buffer = Buffer::create(gpu, 1, BufferUsages::UNIFORM | BufferUsages::COPY_DST);
         let world_buffer = Buffer::create(gpu, 1, BufferUsages::UNIFORM | BufferUsages::COPY_DST);
         let shaders = ShaderSources::default()
             .with("world", include_str!("../shaders/world.wgsl"))
             .compile(gpu)
             .expect("Failed to compile shaders");
+        let atlas = BlockAtlas::create(gpu, &asset_path.join("texture"));
 
         Self {
             gpu: gpu.clone(),
@@ -40,6 +47,7 @@ impl Sculptor {
                     camera_buffer: &camera_buffer,
                     world_buffer: &world_buffer,
                     shader_module: shaders.get_module("world").unwrap(),
+                    texture: &atlas.texture,
                 },
                 sample_count,
             ),
@@ -48,6 +56,7 @@ impl Sculptor {
             shaders,
             meshes: Meshes::new(gpu),
             sets: Sets::new(gpu),
+            atlas,
         }
     }
 
@@ -59,6 +68,7 @@ impl Sculptor {
                 camera_buffer: &self.camera_buffer,
                 world_buffer: &self.world_buffer,
                 shader_module: self.shaders.get_module("world").unwrap(),
+                texture: &self.atlas.texture,
             },
         );
     }
@@ -82,11 +92,17 @@ impl Sculptor {
     pub fn sets(&mut self) -> &mut Sets<Instance3d> {
         &mut self.sets
     }
+
+    pub fn atlas(&self) -> &BlockAtlas {
+        &self.atlas
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum RenderType {
     Terrain,
+    /// Faces that are blended with what is behind them. They test against depth without writing it.
+    Translucent,
     Sky,
 }
 
@@ -95,11 +111,12 @@ pub struct RenderType3dOptions<'a> {
     camera_buffer: &'a Buffer<VideoCamera>,
     world_buffer: &'a Buffer<WorldPayload>,
     shader_module: &'a ShaderModule,
+    texture: &'a Texture,
 }
 
 impl PipelineType for RenderType {
     type Options<'a> = RenderType3dOptions<'a>;
-    const ENTRIES: &'static [Self] = &[Self::Terrain, Self::Sky];
+    const ENTRIES: &'static [Self] = &[Self::Terrain, Self::Translucent, Self::Sky];
 
     fn create_bind_groups(gpu: &gpu::Handle, options: &Self::Options<'_>) -> Vec<BindGroup> {
         let camera_bind_group = BindGroup::build()
@@ -110,7 +127,13 @@ impl PipelineType for RenderType {
             .with_buffer(options.world_buffer, ShaderStages::VERTEX_FRAGMENT)
             .finish(gpu);
 
-        vec![camera_bind_group, world_bind_group]
+        let sampler = gpu.create_sampler(SamplerOptions { filter: Filter::Pixelated });
+        let texture_bind_group = BindGroup::build()
+            .with_sampler(&sampler)
+            .with_texture(options.texture, true)
+            .finish(gpu);
+
+        vec![camera_bind_group, world_bind_group, texture_bind_group]
     }
 
     fn pipeline_options<'a>(&self, _: &gpu::Handle, options: &Self::Options<'a>) -> PipelineOptions<'a> {
@@ -118,7 +141,7 @@ impl PipelineType for RenderType {
             shader_module: options.shader_module,
             vertex_buffer_layouts: &[Vertex3d::LAYOUT, Instance3d::LAYOUT],
             cull_mode: Some(match self {
-                RenderType::Terrain => Face::Back,
+                RenderType::Terrain | RenderType::Translucent => Face::Back,
                 RenderType::Sky => Face::Front,
             }),
             depth_write_enabled: matches!(self, RenderType::Terrain),
@@ -6,7 +6,7 @@ use lib::vector::{Vec3, Vec4, vec2f, vec3f, vec4f};
 use serde::{Deserialize, Serialize};
 use wgpu::{VertexBufferLayout, VertexStepMode, vertex_attr_array};
 
-use crate::video::resource::Vertex;
+use crate::video::resource::{AtlasTextureCoord, Vertex};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable, Deserialize, Serialize)]
@@ -46,12 +46,17 @@ pub struct Instance3d {
     model_2: vec4f,
     model_3: vec4f,
     color: Rgba<f32>,
+    uv_t: vec2f,
+    uv_s: vec2f,
     light: u32,
     ao: vec4f,
 }
 
 impl Instance3d {
-    pub fn new(position: vec3f, rotation: Quat, scale: vec3f, color: Rgba<f32>, light: u32, ao: vec4f) -> Self {
+    /// The light of instances that are drawn in their own color, without any shading.
+    pub const UNLIT: u32 = u32::MAX;
+
+    pub fn new(position: vec3f, rotation: Quat, scale: vec3f, color: Rgba<f32>, texture_coord: AtlasTextureCoord, light: u32, ao: vec4f) -> Self {
         let rotation_matrix = rotation.to_axes();
         let model_matrix = rotation_matrix * Mat3::from(scale);
 
@@ -61,6 +66,8 @@ impl Instance3d {
             model_2: model_matrix.z.extend(0.0),
             model_3: position.extend(1.0),
             color,
+            uv_t: texture_coord.translation,
+            uv_s: texture_coord.scale,
             light,
             ao,
         }
@@ -77,14 +84,16 @@ impl Instance3d {
             5 => Float32x4,
             6 => Float32x4,
             7 => Float32x4,
-            8 => Uint32,
-            9 => Float32x4,
+            8 => Float32x2,
+            9 => Float32x2,
+            10 => Uint32,
+            11 => Float32x4,
         ],
     };
 }
 
 impl Default for Instance3d {
     fn default() -> Self {
-        Instance3d::new(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE, Rgba::TRANSPARENT, 1, Vec4::ZERO)
+        Instance3d::new(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE, Rgba::TRANSPARENT, AtlasTextureCoord::NONE, Self::UNLIT, Vec4::ZERO)
     }
 }
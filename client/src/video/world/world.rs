@@ -9,6 +9,8 @@ pub struct World {
     pub fog_color: Rgb<f32>,
     pub fog_distance: f32,
     pub fog_density: f32,
+    /// How bright the sky light is at the time of day, from 0 to 1.
+    pub daylight: f32,
 }
 
 #[repr(C)]
@@ -17,7 +19,7 @@ pub struct WorldPayload {
     ambient_light: vec4f,
     light_dir: vec4f,
     fog_color: Rgba<f32>,
-    fog_distance_density: vec4f,
+    fog_distance_density_daylight: vec4f,
 }
 
 impl World {
@@ -26,7 +28,7 @@ impl World {
             ambient_light: self.ambient_light.extend(0.0),
             light_dir: self.light_dir.extend(0.0),
             fog_color: self.fog_color.to_rgba(),
-            fog_distance_density: Vec4::new(self.fog_distance, self.fog_density, 0.0, 0.0),
+            fog_distance_density_daylight: Vec4::new(self.fog_distance, self.fog_density, self.daylight, 0.0),
         }
     }
 }
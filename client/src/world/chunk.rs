@@ -7,19 +7,22 @@ use std::sync::Arc;
 use fastrand::Rng;
 use lib::aabb::Aabb3;
 use lib::collections::Mailbox;
+use lib::color::{ColorConsts, Rgba};
 use lib::point::ChunkPt;
 use lib::spatial::{CubeFace, PerFace};
 use lib::task::THREAD_POOL;
-use lib::vector::{vec3i, vec3u5, vec4f, Vec3, Vec4};
+use lib::vector::{vec3f, vec3i, vec3u5, vec4f, Vec3, Vec4};
 use lib::world::{CHUNK_LENGTH, CHUNK_VOLUME};
 use parking_lot::{RwLock, RwLockReadGuard};
 use server::chunk::cube::Cube;
 use server::chunk::handle::{ChunkCube, GameChunkHandle};
-use server::chunk::material::{Palette, PaletteCube};
+use server::chunk::light::LightChannel;
+use server::chunk::material::{Palette, PaletteCube, PaletteMaterialOptionExt, Transparency};
 use wgpu::BufferUsages;
 
 use crate::video::gpu;
-use crate::video::resource::GrowBuffer;
+use crate::video::resource::{AtlasTextureCoord, GrowBuffer};
+use crate::video::world::atlas::BlockAtlasCoords;
 use crate::video::world::chisel::Chisel;
 use crate::video::world::Instance3d;
 use crate::world::frustum::Frustum;
@@ -31,26 +34,32 @@ type ChunkShellGuard<'a> = [Option<RwLockReadGuard<'a, ChunkData>>; 27];
 #[derive(Debug)]
 pub struct ChunkMap {
     pub(crate) map: HashMap<ChunkPt, Chunk>,
-    remesh_queue: Vec<(ChunkPt, Vec<Instance3d>)>,
-    mesh_return: Mailbox<(ChunkPt, Vec<Instance3d>)>,
+    remesh_queue: Vec<(ChunkPt, Vec<Instance3d>, Vec<TranslucentFace>)>,
+    mesh_return: Mailbox<(ChunkPt, Vec<Instance3d>, Vec<TranslucentFace>)>,
+    atlas_coords: Arc<BlockAtlasCoords>,
 }
 
 impl ChunkMap {
-    pub fn new() -> Self {
+    pub fn new(atlas_coords: Arc<BlockAtlasCoords>) -> Self {
         Self {
             map: HashMap::new(),
             remesh_queue: vec![],
             mesh_return: Mailbox::default(),
+            atlas_coords,
         }
     }
 
-    pub fn update(&mut self, handle: &gpu::Handle) {
-        for (position, instances) in &self.mesh_return {
+    pub fn update(&mut self, handle: &gpu::Handle, camera_position: vec3f) {
+        for (position, instances, translucent_faces) in &self.mesh_return {
             if let Some(chunk) = self.map.get_mut(&position) {
-                chunk.submit_mesh(handle, instances);
+                chunk.submit_mesh(handle, instances, translucent_faces);
             }
         }
 
+        for chunk in self.map.values_mut() {
+            chunk.sort_translucent_faces(handle, camera_position);
+        }
+
         self.remesh_queue.clear();
         for (position, chunk) in &mut self.map {
             let updated = chunk.apply_updates_from_server();
@@ -59,18 +68,22 @@ impl ChunkMap {
             }
 
             chunk.cached_quad_instances.clear();
+            chunk.translucent_faces.clear();
+            chunk.is_meshing = true;
             let instances = take(&mut chunk.cached_quad_instances);
-            self.remesh_queue.push((*position, instances));
+            let translucent_faces = take(&mut chunk.translucent_faces);
+            self.remesh_queue.push((*position, instances, translucent_faces));
         }
 
-        for (chunk_position, mut instances) in self.remesh_queue.drain(..) {
+        for (chunk_position, mut instances, mut translucent_faces) in self.remesh_queue.drain(..) {
             let return_tx = self.mesh_return.sender();
             let chunk_shell = create_chunk_shell(&self.map, chunk_position);
+            let atlas_coords = self.atlas_coords.clone();
 
             THREAD_POOL.spawn(move || {
-                generate_mesh(&chunk_shell, &mut instances);
+                generate_mesh(&chunk_shell, &atlas_coords, &mut instances, &mut translucent_faces);
 
-                let _ = return_tx.send((chunk_position, instances));
+                let _ = return_tx.send((chunk_position, instances, translucent_faces));
             })
         }
     }
@@ -80,6 +93,21 @@ impl ChunkMap {
             chunk.render(frustum, chisel);
         }
     }
+
+    /// Renders the translucent faces of every chunk, from the farthest chunk to the nearest.
+    pub fn render_translucent(&self, frustum: &Frustum, camera_position: vec3f, chisel: &mut Chisel) {
+        let mut chunks = self
+            .map
+            .values()
+            .filter(|chunk| chunk.translucent_mesh.len() > 0)
+            .map(|chunk| ((chunk.center() - camera_position).length_squared(), chunk))
+            .collect::<Vec<_>>();
+        chunks.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        for (_, chunk) in chunks {
+            chunk.render_translucent(frustum, chisel);
+        }
+    }
 }
 
 // TODO: cache neighboring cube solidity for ao calculations instead of querying 26 neighbors every time
@@ -90,9 +118,22 @@ pub struct Chunk {
     handle: GameChunkHandle,
     cached_quad_instances: Vec<Instance3d>,
     mesh: GrowBuffer<Instance3d>,
+    translucent_faces: Vec<TranslucentFace>,
+    translucent_mesh: GrowBuffer<Instance3d>,
+    /// The cube of the camera when the translucent faces were last sorted.
+    sorted_from: Option<vec3i>,
+    /// Whether the faces were handed to a job to be remeshed, in which case the buffers still hold the old mesh.
+    is_meshing: bool,
     data: Arc<RwLock<ChunkData>>,
 }
 
+/// A face that is blended with what is behind it, so it must be drawn after the faces behind it.
+#[derive(Debug, Copy, Clone)]
+struct TranslucentFace {
+    center: vec3f,
+    instance: Instance3d,
+}
+
 #[derive(Debug)]
 struct ChunkData {
     position: ChunkPt,
@@ -103,11 +144,16 @@ struct ChunkData {
 impl Chunk {
     pub fn create(gpu: &gpu::Handle, position: ChunkPt, handle: GameChunkHandle) -> Self {
         let mesh = GrowBuffer::empty(gpu, BufferUsages::VERTEX | BufferUsages::COPY_DST);
+        let translucent_mesh = GrowBuffer::empty(gpu, BufferUsages::VERTEX | BufferUsages::COPY_DST);
 
         Self {
             position,
             handle,
             cached_quad_instances: vec![],
+            translucent_faces: vec![],
+            translucent_mesh,
+            sorted_from: None,
+            is_meshing: false,
             data: Arc::new(RwLock::new(ChunkData {
                 position,
                 cubes: Box::new([Cube::new(None); CHUNK_VOLUME]),
@@ -129,6 +175,22 @@ impl Chunk {
         chisel.render_each(&self.mesh);
     }
 
+    fn render_translucent(&self, frustum: &Frustum, chisel: &mut Chisel) {
+        if !frustum.contains_cube(self.position.0.cast(), CHUNK_LENGTH as f32) {
+            return;
+        }
+
+        if !self.handle.is_rendered() {
+            return;
+        }
+
+        chisel.render_each(&self.translucent_mesh);
+    }
+
+    fn center(&self) -> vec3f {
+        ((self.position.0 * CHUNK_LENGTH as i32).cast::<f32>()) + CHUNK_LENGTH as f32 / 2.0
+    }
+
     fn apply_updates_from_server(&mut self) -> bool {
         if self.handle.cube_update.is_empty() {
             return false;
@@ -148,10 +210,44 @@ impl Chunk {
         true
     }
 
-    fn submit_mesh(&mut self, handle: &gpu::Handle, instances: Vec<Instance3d>) {
+    fn submit_mesh(&mut self, handle: &gpu::Handle, instances: Vec<Instance3d>, translucent_faces: Vec<TranslucentFace>) {
         self.cached_quad_instances = instances;
         self.mesh
             .write(handle, &self.cached_quad_instances);
+
+        self.translucent_faces = translucent_faces;
+        self.sorted_from = None;
+        self.is_meshing = false;
+
+        // Chunks without translucent faces are never sorted, so the faces of their old mesh are cleared here.
+        if self.translucent_faces.is_empty() && self.translucent_mesh.len() > 0 {
+            self.translucent_mesh.write(handle, &[]);
+        }
+    }
+
+    /// Orders the translucent faces from back to front, which only changes once the camera moves to another cube.
+    fn sort_translucent_faces(&mut self, handle: &gpu::Handle, camera_position: vec3f) {
+        if self.is_meshing || self.translucent_faces.is_empty() {
+            return;
+        }
+
+        let camera_cube = camera_position.floor().cast::<i32>();
+        if self.sorted_from == Some(camera_cube) {
+            return;
+        }
+        self.sorted_from = Some(camera_cube);
+
+        self.translucent_faces.sort_by(|a, b| {
+            (b.center - camera_position)
+                .length_squared()
+                .total_cmp(&(a.center - camera_position).length_squared())
+        });
+        let instances = self
+            .translucent_faces
+            .iter()
+            .map(|face| face.instance)
+            .collect::<Vec<_>>();
+        self.translucent_mesh.write(handle, &instances);
     }
 }
 
@@ -173,7 +269,7 @@ fn create_chunk_shell(map: &HashMap<ChunkPt, Chunk>, position: ChunkPt) -> Chunk
     shell
 }
 
-fn generate_mesh(shell: &ChunkShell, instances: &mut Vec<Instance3d>) {
+fn generate_mesh(shell: &ChunkShell, atlas_coords: &BlockAtlasCoords, instances: &mut Vec<Instance3d>, translucent_faces: &mut Vec<TranslucentFace>) {
     let shell_guard = shell
         .each_ref()
         .map(|chunk| chunk.as_ref().map(|x| x.read()));
@@ -204,17 +300,34 @@ fn generate_mesh(shell: &ChunkShell, instances: &mut Vec<Instance3d>) {
                     };
 
                     for face in cube.flags.faces() {
-                        let color = material.get_color(perms[face]);
+                        // Faces with an image show it as is, while the others show one of the material's colors.
+                        let (color, texture_coord) = match material.texture.image(face) {
+                            Some(image) => (Rgba::WHITE, atlas_coords.get(image)),
+                            None => (material.get_color(perms[face]), AtlasTextureCoord::NONE),
+                        };
                         let ao = facial_ao(&shell_guard, face, position.cast());
-
-                        instances.push(Instance3d::new(
-                            (chunk_position + position.cast::<i32>()).cast::<f32>(),
-                            face.rotation(),
-                            Vec3::ONE,
-                            color,
-                            0,
-                            ao,
-                        ));
+                        let cube_position = (chunk_position + position.cast::<i32>()).cast::<f32>();
+                        // Emissive materials are at least as bright as the light they give off.
+                        let block_light = cube
+                            .flags
+                            .light_levels(LightChannel::Block)
+                            .get(face)
+                            .max(material.light);
+                        let sky_light = cube
+                            .flags
+                            .light_levels(LightChannel::Sky)
+                            .get(face);
+                        let light = block_light as u32 | (sky_light as u32) << 8;
+
+                        let instance = Instance3d::new(cube_position, face.rotation(), Vec3::ONE, color, texture_coord, light, ao);
+                        if material.transparency == Transparency::Translucent {
+                            translucent_faces.push(TranslucentFace {
+                                center: cube_position + 0.5 + face.normal().cast::<f32>() * 0.5,
+                                instance,
+                            });
+                        } else {
+                            instances.push(instance);
+                        }
                     }
                 }
             }
@@ -222,7 +335,7 @@ fn generate_mesh(shell: &ChunkShell, instances: &mut Vec<Instance3d>) {
     }
 }
 
-fn is_cube_present(shell: &ChunkShellGuard, position: vec3i) -> bool {
+fn is_cube_opaque(shell: &ChunkShellGuard, position: vec3i) -> bool {
     let chunk_offset = position.div_euclid_each(CHUNK_LENGTH as i32);
 
     if !Aabb3::new(-Vec3::ONE, Vec3::ONE).contains(chunk_offset) {
@@ -243,9 +356,10 @@ fn is_cube_present(shell: &ChunkShellGuard, position: vec3i) -> bool {
         return false;
     };
 
+    // Only opaque cubes occlude the light reaching their neighbours.
     target_chunk.cubes[local_position.linearize()]
         .material
-        .is_some()
+        .is_opaque(&target_chunk.palette)
 }
 
 fn vertex_ao(s1: bool, s2: bool, c: bool) -> u8 {
@@ -255,14 +369,14 @@ fn vertex_ao(s1: bool, s2: bool, c: bool) -> u8 {
 fn facial_ao(shell: &ChunkShellGuard, face: CubeFace, position: vec3i) -> vec4f {
     let (u, v, n) = face.orthonormal_basis();
 
-    let tl = is_cube_present(shell, position + n - v - u);
-    let tc = is_cube_present(shell, position + n - v);
-    let tr = is_cube_present(shell, position + n - v + u);
-    let ml = is_cube_present(shell, position + n - u);
-    let mr = is_cube_present(shell, position + n + u);
-    let bl = is_cube_present(shell, position + n + v - u);
-    let bc = is_cube_present(shell, position + n + v);
-    let br = is_cube_present(shell, position + n + v + u);
+    let tl = is_cube_opaque(shell, position + n - v - u);
+    let tc = is_cube_opaque(shell, position + n - v);
+    let tr = is_cube_opaque(shell, position + n - v + u);
+    let ml = is_cube_opaque(shell, position + n - u);
+    let mr = is_cube_opaque(shell, position + n + u);
+    let bl = is_cube_opaque(shell, position + n + v - u);
+    let bc = is_cube_opaque(shell, position + n + v);
+    let br = is_cube_opaque(shell, position + n + v + u);
 
     let occlusion_bl = vertex_ao(ml, tc, tl);
     let occlusion_tl = vertex_ao(ml, bc, bl);
@@ -3,7 +3,7 @@ use lib::color::{Color, Rgb};
 use lib::ptr::DetectMut;
 use lib::vector::Vec3;
 use server::chunk::handle::ChunkLoad;
-use server::handle::GameHandle;
+use server::handle::{ChunkEvent, GameHandle};
 
 use crate::app::Update;
 use crate::session::MeshIds;
@@ -12,6 +12,7 @@ use crate::video::{Video, world};
 use crate::world::chunk::ChunkMap;
 use crate::world::particle::Particles;
 use crate::world::player::Player;
+use crate::world::sky::DayCycle;
 
 pub mod chunk;
 pub mod frustum;
@@ -26,6 +27,7 @@ pub struct World {
     pub(crate) render_settings: DetectMut<world::World>,
     pub(crate) player: Player,
     particles: Particles,
+    day_cycle: DayCycle,
 }
 
 impl World {
@@ -36,13 +38,16 @@ impl World {
             fog_color: Rgb::<u8>::from_rgb(177, 242, 255).into(),
             fog_distance: 300.0,
             fog_density: 20.0,
+            daylight: 1.0,
         };
 
         Self {
-            chunk_map: ChunkMap::new(),
+            chunk_map: ChunkMap::new(video.sculptor.atlas().coords().clone()),
             render_settings: DetectMut::new(render_settings),
             player: Player::create(render_settings.fog_color.to_rgba(), video),
             particles: Particles::create(&video.handle),
+            // Sessions start in the morning.
+            day_cycle: DayCycle::new(0.1),
         }
     }
 
@@ -55,6 +60,14 @@ impl World {
         self.particles.render(chisel);
     }
 
+    /// Renders what is blended with the rest of the world, which must come after everything else in the world.
+    pub fn render_translucent(&mut self, mesh_ids: &MeshIds, chisel: &mut Chisel) {
+        chisel.load_mesh(mesh_ids.solid_quad);
+
+        self.chunk_map
+            .render_translucent(&self.player.frustum, self.player.eye_position(), chisel);
+    }
+
     pub fn update(&mut self, is_focused: bool, handle: &GameHandle, ctx: &mut Update) {
         if let Some(handle) = handle.next_player_handle() {
             self.player.handle = Some(handle);
@@ -66,22 +79,32 @@ impl World {
             self.player.update_input(ctx);
         }
 
+        self.day_cycle.update(ctx.dt);
+        let daylight = self.day_cycle.daylight();
+        if self.render_settings.daylight != daylight {
+            self.render_settings.daylight = daylight;
+        }
+
         if DetectMut::check(&mut self.render_settings) {
             ctx.video
                 .sculptor
                 .update_world(&self.render_settings);
         }
 
-        while let Some(ChunkLoad { position, handle }) = handle.chunks.next_load() {
-            let chunk = Chunk::create(&ctx.video.handle, position, handle);
-            self.chunk_map.map.insert(position, chunk);
+        while let Some(event) = handle.chunks.next_event() {
+            match event {
+                ChunkEvent::Load(ChunkLoad { position, handle }) => {
+                    let chunk = Chunk::create(&ctx.video.handle, position, handle);
+                    self.chunk_map.map.insert(position, chunk);
+                }
+                ChunkEvent::Unload(position) => {
+                    self.chunk_map.map.remove(&position);
+                }
+            }
         }
 
-        while let Some(position) = handle.chunks.next_unload() {
-            self.chunk_map.map.remove(&position);
-        }
-
-        self.chunk_map.update(&ctx.video.handle);
+        self.chunk_map
+            .update(&ctx.video.handle, self.player.eye_position());
 
         self.particles
             .update(handle, ctx, self.player.state.position);
@@ -7,7 +7,7 @@ use wgpu::BufferUsages;
 
 use crate::app::Update;
 use crate::video::gpu;
-use crate::video::resource::GrowBuffer;
+use crate::video::resource::{AtlasTextureCoord, GrowBuffer};
 use crate::video::world::chisel::Chisel;
 use crate::video::world::Instance3d;
 
@@ -65,7 +65,8 @@ pub fn update_particle(particle: &mut Particle, ctx: &mut Update, mut camera_pos
         }),
         Vec3::splat(0.1),
         particle.color,
-        1,
+        AtlasTextureCoord::NONE,
+        Instance3d::UNLIT,
         Vec4::ZERO,
     )
 }
@@ -12,7 +12,7 @@ use winit::keyboard::KeyCode;
 
 use crate::app::Update;
 use crate::video::camera::{VideoCamera, View};
-use crate::video::resource::{SetId, Sets};
+use crate::video::resource::{AtlasTextureCoord, SetId, Sets};
 use crate::video::world::Instance3d;
 use crate::video::Video;
 use crate::world::frustum::Frustum;
@@ -161,7 +161,7 @@ impl Player {
         }
     }
 
-    fn eye_position(&self) -> vec3f {
+    pub(crate) fn eye_position(&self) -> vec3f {
         self.state.position.cast::<f32>() + self.state.eye_offset
     }
 
@@ -173,5 +173,5 @@ impl Player {
 fn cube(position: vec3f, color: Rgba<f32>) -> impl IntoIterator<Item = Instance3d> {
     CubeFace::values()
         .map(CubeFace::rotation)
-        .map(move |rotation| Instance3d::new(position, rotation, Vec3::splat(1.0), color, 1, Vec4::ZERO))
+        .map(move |rotation| Instance3d::new(position, rotation, Vec3::splat(1.0), color, AtlasTextureCoord::NONE, Instance3d::UNLIT, Vec4::ZERO))
 }
@@ -1,5 +1,14 @@
+use std::f32::consts::TAU;
+
+use time::Duration;
+
 use crate::app::{Render, Update};
 
+/// The length of a full day and night.
+const DAY_LENGTH: Duration = Duration::minutes(20);
+/// The daylight at night, which keeps open terrain from becoming entirely dark.
+const NIGHT_DAYLIGHT: f32 = 0.2;
+
 pub struct Clouds {}
 
 impl Clouds {
@@ -7,3 +16,27 @@ impl Clouds {
 
     pub fn render(&self, _: &mut Render) {}
 }
+
+/// The time of day, which the brightness of the sky follows.
+#[derive(Debug)]
+pub struct DayCycle {
+    /// How far through the day it is, from 0 at sunrise to 1 at the next sunrise.
+    time: f32,
+}
+
+impl DayCycle {
+    pub fn new(time: f32) -> Self {
+        Self { time: time.rem_euclid(1.0) }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.time = (self.time + dt.as_seconds_f32() / DAY_LENGTH.as_seconds_f32()).fract();
+    }
+
+    /// How bright the sky light is, from [`NIGHT_DAYLIGHT`] at night to 1 for most of the day, with a short dusk and
+    /// dawn between them.
+    pub fn daylight(&self) -> f32 {
+        let sun_height = (self.time * TAU).sin();
+        NIGHT_DAYLIGHT + (1.0 - NIGHT_DAYLIGHT) * (sun_height * 2.0 + 0.5).clamp(0.0, 1.0)
+    }
+}
@@ -4,8 +4,7 @@ use std::ops::{Add, AddAssign};
 use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
 
-#[const_trait]
-pub trait Color {
+pub const trait Color {
     type Comp: ColorComp;
 
     fn from_rgb(r: Self::Comp, g: Self::Comp, b: Self::Comp) -> Self;
@@ -149,8 +148,7 @@ impl ColorComp for f64 {
     }
 }
 
-#[const_trait]
-pub trait ColorConsts {
+pub const trait ColorConsts {
     const WHITE: Self;
     const BLACK: Self;
 }
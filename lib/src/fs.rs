@@ -1,21 +1,29 @@
-use std::fs::{create_dir, write};
+use std::ffi::OsString;
+use std::fs::{File, copy, create_dir, create_dir_all, read_dir, remove_file, rename};
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use include_dir::{Dir, include_dir};
+use tracing::warn;
 
-use crate::save::{Save, SaveAttributes, SaveError, Saves};
+use crate::save::{ImportOptions, Migrations, Save, SaveAttributes, SaveError, Saves, Snapshot, archive, snapshot};
 
 #[derive(Debug)]
 pub struct Fs {
     saves: PathBuf,
+    snapshots: PathBuf,
     root: PathBuf,
+    migrations: Migrations,
 }
 
 impl Fs {
-    pub fn new(root: PathBuf) -> Self {
+    /// Creates the file system rooted at `root`, which opens saves with `migrations`.
+    pub fn new(root: PathBuf, migrations: Migrations) -> Self {
         Self {
             saves: root.join("saves"),
+            snapshots: root.join("snapshots"),
             root,
+            migrations,
         }
     }
 
@@ -28,21 +36,47 @@ impl Fs {
             create_dir(&self.saves)?;
         }
 
+        if !self.snapshots.exists() {
+            create_dir(&self.snapshots)?;
+        }
+
+        snapshot::recover_restores(&self.saves)?;
+
         copy_assets(&self.root.join("assets"))?;
 
         Ok(())
     }
 
     pub fn saves(&self) -> std::io::Result<Saves> {
-        Saves::new(&self.saves)
+        Saves::new(&self.saves, self.migrations.clone())
     }
 
     pub fn open_save(&self, name: impl AsRef<str>) -> Result<Save, SaveError> {
-        Save::open(self.saves.join(name.as_ref()))
+        Save::open(self.saves.join(name.as_ref()), &self.migrations)
     }
 
     pub fn create_or_open_save(&self, name: impl AsRef<str>, attributes: SaveAttributes) -> Result<Save, SaveError> {
-        Save::create_or_open(self.saves.join(name.as_ref()), attributes)
+        Save::create_or_open(self.saves.join(name.as_ref()), attributes, &self.migrations)
+    }
+
+    pub fn import_save(&self, archive_path: impl AsRef<Path>, options: ImportOptions) -> Result<Save, SaveError> {
+        archive::import(&self.saves, archive_path.as_ref(), options, &self.migrations)
+    }
+
+    pub fn snapshot_save(&self, name: impl AsRef<str>, keep: usize) -> Result<Snapshot, SaveError> {
+        let save = self.open_save(name)?;
+        let snapshot = save.create_snapshot(&self.snapshots)?;
+        save.prune_snapshots(&self.snapshots, keep)?;
+
+        Ok(snapshot)
+    }
+
+    pub fn snapshots_path(&self) -> &Path {
+        &self.snapshots
+    }
+
+    pub fn migrations(&self) -> &Migrations {
+        &self.migrations
     }
 
     pub fn path(&self) -> &Path {
@@ -50,6 +84,114 @@ impl Fs {
     }
 }
 
+pub fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    create_dir_all(to)?;
+
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let path = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &path)?;
+        } else {
+            copy(entry.path(), path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Distinct from the suffixes of other programs, so that only files left behind by this module are ever removed.
+const TEMP_SUFFIX: &str = ".herbolution-tmp";
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`, so a crash leaves either the old
+/// or the new contents in place but never a partial file.
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_atomic_with(path, |writer| writer.write_all(contents.as_ref()))
+}
+
+/// Like [`write_atomic`], but streams the contents through `f`.
+pub fn write_atomic_with(path: impl AsRef<Path>, f: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp_path = temp_path(path);
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    if let Err(e) = f(&mut writer) {
+        drop(writer);
+        let _ = remove_file(&temp_path);
+        return Err(e);
+    }
+
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+
+    rename(&temp_path, path)?;
+    sync_parent(path)
+}
+
+/// Removes temporary files left behind by interrupted [`write_atomic`] calls anywhere under `dir_path`, returning how
+/// many were removed. Files that cannot be removed are logged and left in place.
+pub fn remove_temp_files(dir_path: &Path) -> usize {
+    let entries = match read_dir(dir_path) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to look for temporary files in {}: {e}", dir_path.display());
+            return 0;
+        }
+    };
+
+    let mut count = 0;
+    for entry in entries {
+        let (path, file_type) = match entry.and_then(|x| Ok((x.path(), x.file_type()?))) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to look for temporary files in {}: {e}", dir_path.display());
+                continue;
+            }
+        };
+
+        if file_type.is_dir() {
+            count += remove_temp_files(&path);
+        } else if file_type.is_file() && is_temp_path(&path) {
+            match remove_file(&path) {
+                Ok(()) => count += 1,
+                Err(e) => warn!("Failed to remove temporary file {}: {e}", path.display()),
+            }
+        }
+    }
+
+    count
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .map_or_else(OsString::new, ToOwned::to_owned);
+    file_name.push(TEMP_SUFFIX);
+
+    path.with_file_name(file_name)
+}
+
+fn is_temp_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.ends_with(TEMP_SUFFIX))
+}
+
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
 fn copy_assets(base_path: &Path) -> std::io::Result<()> {
     const DIR: Dir<'_> = include_dir!("assets");
 
@@ -63,7 +205,7 @@ fn copy_assets(base_path: &Path) -> std::io::Result<()> {
 
         if let Some(file) = entry.as_file() {
             if !path.exists() {
-                write(&path, file.contents())?;
+                write_atomic(&path, file.contents())?;
             }
         } else if let Some(dir) = entry.as_dir() {
             if !path.exists() {
@@ -74,7 +216,7 @@ fn copy_assets(base_path: &Path) -> std::io::Result<()> {
         }
     }
 
-    write(
+    write_atomic(
         base_path.join("README"),
         "Please do not edit the contents of this directory manually; it is overwritten frequently without warning.",
     )?;
@@ -17,6 +17,22 @@ impl From<ChunkCubePt> for CubePt {
     }
 }
 
+impl Add<vec3i> for CubePt {
+    type Output = Self;
+
+    fn add(self, rhs: vec3i) -> Self::Output {
+        Self(self.0 + rhs)
+    }
+}
+
+impl Sub<vec3i> for CubePt {
+    type Output = Self;
+
+    fn sub(self, rhs: vec3i) -> Self::Output {
+        Self(self.0 - rhs)
+    }
+}
+
 pub struct ChunkCubePt {
     pub chunk: ChunkPt,
     pub local: vec3u5,
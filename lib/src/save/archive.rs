@@ -0,0 +1,368 @@
+use std::fs::{create_dir_all, read, read_dir};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::fs::{write_atomic, write_atomic_with};
+use crate::save::snapshot::{install, staging_path};
+use crate::save::{Migrations, SAVE_FORMAT_VERSION, Save, SaveError, is_valid_world_name};
+use crate::util::Crc32;
+
+// Save archive layout (all integers little-endian):
+//
+// | magic "HBSA" | version: u16 | manifest size: u32 | manifest | entry count: u32 | entries... | crc32: u32 |
+//
+// The manifest is UTF-8 TOML. Each entry is a (path size: u16, path, data size: u64, data) record, where the path is
+// relative to the save directory and uses `/` separators. Entries are `Save.toml`, the files of each world under
+// `worlds/` and the material overrides under `materials/`. The checksum covers every byte before it.
+
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"HBSA";
+pub const ARCHIVE_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub title: String,
+    pub format_version: u32,
+    pub default_world: String,
+    pub worlds: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Archive {
+    pub manifest: ArchiveManifest,
+    pub entries: Vec<ArchiveEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// The save name to import into. When `None`, the name is derived from the archive file name and made unique.
+    pub name: Option<String>,
+    /// Whether an existing save named `name` may be replaced.
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("Archive data ended unexpectedly")]
+    Truncated,
+    #[error("File does not start with the save archive magic number")]
+    BadMagic,
+    #[error("Unsupported save archive version {0} (expected {ARCHIVE_VERSION})")]
+    BadVersion(u16),
+    #[error("Archive checksum mismatch (expected {expected:#010x}, found {found:#010x})")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Archive manifest is invalid: {0}")]
+    InvalidManifest(String),
+    #[error("Archive was written with save format version {0}, which is newer than this build supports")]
+    UnsupportedFormat(u32),
+    #[error("Archive entry has an unsafe path: {0}")]
+    UnsafePath(String),
+    #[error("Archive is missing {0}")]
+    MissingEntry(String),
+    #[error("Archive entry is not in a world listed by the manifest: {0}")]
+    UnlistedWorld(String),
+    #[error("Archive entry path is longer than 65535 bytes: {0}")]
+    PathTooLong(String),
+}
+
+impl Save {
+    pub fn export(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let mut worlds = vec![];
+        let mut entries = vec![PathBuf::from("Save.toml")];
+        for entry in read_dir(self.path.join("worlds"))? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            worlds.push(entry.file_name().to_string_lossy().into_owned());
+            collect_files(&self.path, &entry.path(), &mut entries)?;
+        }
+        worlds.sort();
+
+        let materials_path = self.path.join("materials");
+        if materials_path.is_dir() {
+            collect_files(&self.path, &materials_path, &mut entries)?;
+        }
+
+        let manifest = ArchiveManifest {
+            title: self.descriptor.title.clone(),
+            format_version: self.descriptor.format_version,
+            default_world: self.descriptor.default_world.clone(),
+            worlds,
+        };
+        let manifest = toml::to_string(&manifest)?;
+
+        let names = entries
+            .iter()
+            .map(|entry| {
+                let name = entry
+                    .components()
+                    .map(|x| x.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if name.len() > u16::MAX as usize {
+                    return Err(ArchiveError::PathTooLong(name));
+                }
+                Ok(name)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        write_atomic_with(path, |writer| {
+            let mut writer = ChecksumWriter::new(writer);
+
+            writer.write_all(&ARCHIVE_MAGIC)?;
+            writer.write_all(&ARCHIVE_VERSION.to_le_bytes())?;
+            writer.write_all(&(manifest.len() as u32).to_le_bytes())?;
+            writer.write_all(manifest.as_bytes())?;
+            writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+
+            for (entry, name) in entries.iter().zip(&names) {
+                let data = read(self.path.join(entry))?;
+
+                writer.write_all(&(name.len() as u16).to_le_bytes())?;
+                writer.write_all(name.as_bytes())?;
+                writer.write_all(&(data.len() as u64).to_le_bytes())?;
+                writer.write_all(&data)?;
+            }
+
+            let checksum = writer.checksum.finish();
+            writer.inner.write_all(&checksum.to_le_bytes())
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Reads and validates a save archive without extracting it.
+pub fn read_archive(path: impl AsRef<Path>) -> Result<Archive, SaveError> {
+    let bytes = read(path)?;
+    let mut reader = Reader { bytes: &bytes, position: 0 };
+
+    if reader.take(ARCHIVE_MAGIC.len())? != ARCHIVE_MAGIC {
+        return Err(ArchiveError::BadMagic.into());
+    }
+
+    let version = reader.u16()?;
+    if version != ARCHIVE_VERSION {
+        return Err(ArchiveError::BadVersion(version).into());
+    }
+
+    let checked_len = bytes
+        .len()
+        .checked_sub(4)
+        .ok_or(ArchiveError::Truncated)?;
+    let expected = u32::from_le_bytes(bytes[checked_len..].try_into().unwrap());
+    let mut checksum = Crc32::new();
+    checksum.update(&bytes[..checked_len]);
+    let found = checksum.finish();
+    if expected != found {
+        return Err(ArchiveError::ChecksumMismatch { expected, found }.into());
+    }
+    reader.bytes = &bytes[..checked_len];
+
+    let manifest_len = reader.u32()? as usize;
+    let manifest = std::str::from_utf8(reader.take(manifest_len)?).map_err(|e| ArchiveError::InvalidManifest(e.to_string()))?;
+    let manifest: ArchiveManifest = toml::from_str(manifest).map_err(|e| ArchiveError::InvalidManifest(e.to_string()))?;
+
+    if manifest.format_version > SAVE_FORMAT_VERSION {
+        return Err(ArchiveError::UnsupportedFormat(manifest.format_version).into());
+    }
+    if let Some(name) = manifest
+        .worlds
+        .iter()
+        .find(|x| !is_valid_world_name(x))
+    {
+        return Err(ArchiveError::InvalidManifest(format!("world name '{name}' is invalid")).into());
+    }
+    if !manifest.worlds.contains(&manifest.default_world) {
+        return Err(ArchiveError::InvalidManifest(format!("default world '{}' is not in the world list", manifest.default_world)).into());
+    }
+
+    let count = reader.u32()?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let name_len = reader.u16()? as usize;
+        let path = std::str::from_utf8(reader.take(name_len)?)
+            .map_err(|_| ArchiveError::UnsafePath("<non-UTF-8 path>".to_string()))?
+            .to_owned();
+        if !is_safe_entry(&path) {
+            return Err(ArchiveError::UnsafePath(path).into());
+        }
+        if let Some(world) = path.strip_prefix("worlds/").and_then(|x| x.split('/').next())
+            && !manifest.worlds.iter().any(|x| x == world)
+        {
+            return Err(ArchiveError::UnlistedWorld(path).into());
+        }
+
+        let data_len = usize::try_from(reader.u64()?).map_err(|_| ArchiveError::Truncated)?;
+        entries.push(ArchiveEntry {
+            path,
+            data: reader.take(data_len)?.to_vec(),
+        });
+    }
+
+    if reader.position != reader.bytes.len() {
+        return Err(ArchiveError::InvalidManifest("archive has trailing data".to_string()).into());
+    }
+
+    let required = ["Save.toml".to_string()]
+        .into_iter()
+        .chain(manifest.worlds.iter().map(|x| format!("worlds/{x}/World.toml")));
+    for name in required {
+        if !entries.iter().any(|x| x.path == name) {
+            return Err(ArchiveError::MissingEntry(name).into());
+        }
+    }
+
+    Ok(Archive { manifest, entries })
+}
+
+/// Extracts an archive into `saves_path` and opens the imported save.
+pub(crate) fn import(saves_path: &Path, archive_path: &Path, options: ImportOptions, migrations: &Migrations) -> Result<Save, SaveError> {
+    let Archive { manifest, entries } = read_archive(archive_path)?;
+
+    let save_path = match options.name {
+        Some(name) => {
+            let path = saves_path.join(sanitize_name(&name));
+            if path.exists() && !options.overwrite {
+                return Err(SaveError::AlreadyExists);
+            }
+            path
+        }
+        None => {
+            let stem = archive_path
+                .file_stem()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_else(|| manifest.title.clone());
+            unique_path(saves_path, &sanitize_name(&stem))
+        }
+    };
+
+    let staging_path = staging_path(&save_path)?;
+    for entry in &entries {
+        let path = staging_path.join(&entry.path);
+        create_dir_all(path.parent().unwrap())?;
+        write_atomic(path, &entry.data)?;
+    }
+
+    install(&staging_path, &save_path)?;
+
+    Save::open(save_path, migrations)
+}
+
+fn collect_files(base_path: &Path, dir_path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in read_dir(dir_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_files(base_path, &entry.path(), files)?;
+        } else {
+            files.push(
+                entry
+                    .path()
+                    .strip_prefix(base_path)
+                    .unwrap()
+                    .to_path_buf(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn is_safe_entry(name: &str) -> bool {
+    let path = Path::new(name);
+
+    // Empty and `.` segments are checked separately, since they are skipped by `Path::components`.
+    (name == "Save.toml" || name.starts_with("worlds/") || name.starts_with("materials/"))
+        && name
+            .split('/')
+            .all(|x| !x.is_empty() && x != "." && x != "..")
+        && path
+            .components()
+            .all(|x| matches!(x, Component::Normal(_)))
+}
+
+fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|x| if x.is_alphanumeric() || x == '-' || x == '_' { x } else { '_' })
+        .collect();
+
+    if name.is_empty() { "imported".to_string() } else { name }
+}
+
+fn unique_path(saves_path: &Path, name: &str) -> PathBuf {
+    let mut path = saves_path.join(name);
+    let mut n = 2;
+    while path.exists() {
+        path = saves_path.join(format!("{name}-{n}"));
+        n += 1;
+    }
+
+    path
+}
+
+struct ChecksumWriter<'a, W> {
+    inner: &'a mut W,
+    checksum: Crc32,
+}
+
+impl<'a, W: Write> ChecksumWriter<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        Self { inner, checksum: Crc32::new() }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.checksum.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ArchiveError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .ok_or(ArchiveError::Truncated)?;
+        let slice = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(ArchiveError::Truncated)?;
+
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, ArchiveError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ArchiveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ArchiveError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
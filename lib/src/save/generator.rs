@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings that shape the terrain of a world, stored in its `World.toml`.
+///
+/// Every field has a default, so a world only needs to list the settings it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorConfig {
+    /// Noise layers that are summed to produce the terrain height before it is scaled by the biome.
+    pub noise_layers: Vec<NoiseLayer>,
+    /// Multiplies the height scale of every biome.
+    pub height_scale: f32,
+    /// The height around which terrain is generated; biome height offsets are relative to it.
+    pub sea_level: f32,
+    /// How many cubes of the biome surface material cover a column.
+    pub surface_depth: u32,
+    /// The material below the biome subsurface, unless a band replaces it.
+    pub base_material: String,
+    /// Ranges of heights in which another material replaces the base material.
+    pub bands: Vec<MaterialBand>,
+}
+
+impl GeneratorConfig {
+    /// Returns the material keys that the config refers to.
+    pub fn materials(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.base_material.as_str()).chain(self.bands.iter().map(|x| x.material.as_str()))
+    }
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            noise_layers: vec![NoiseLayer::default()],
+            height_scale: 1.0,
+            sea_level: 0.0,
+            surface_depth: 1,
+            base_material: "herbolution:stone".to_string(),
+            bands: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseLayer {
+    pub frequency: f32,
+    pub octaves: u8,
+    pub lacunarity: f32,
+    pub gain: f32,
+    pub amplitude: f32,
+}
+
+impl Default for NoiseLayer {
+    fn default() -> Self {
+        Self {
+            frequency: 0.001,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.6,
+            amplitude: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialBand {
+    pub material: String,
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+/// Chooses how the chunks of a world are generated.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorldType {
+    /// Noise terrain shaped by the generator config and the terrain mode.
+    #[default]
+    Standard,
+    /// Horizontal layers of materials, with the top layer ending just below y = 0.
+    Flat {
+        #[serde(default = "WorldType::default_flat_layers")]
+        layers: Vec<MaterialBand>,
+    },
+    /// Empty except for a platform to spawn on.
+    Void,
+    /// Every material laid out in a grid on a platform.
+    DebugGrid,
+}
+
+impl WorldType {
+    pub fn flat() -> Self {
+        Self::Flat {
+            layers: Self::default_flat_layers(),
+        }
+    }
+
+    pub fn default_flat_layers() -> Vec<MaterialBand> {
+        vec![
+            MaterialBand {
+                material: "herbolution:stone".to_string(),
+                min_y: -64,
+                max_y: -5,
+            },
+            MaterialBand {
+                material: "herbolution:dirt".to_string(),
+                min_y: -4,
+                max_y: -2,
+            },
+            MaterialBand {
+                material: "herbolution:grass".to_string(),
+                min_y: -1,
+                max_y: -1,
+            },
+        ]
+    }
+
+    /// Returns the material keys that the world type refers to.
+    pub fn materials(&self) -> impl Iterator<Item = &str> {
+        let layers = match self {
+            Self::Flat { layers } => layers.as_slice(),
+            _ => &[],
+        };
+
+        layers.iter().map(|x| x.material.as_str())
+    }
+}
@@ -0,0 +1,248 @@
+use std::fs::{copy, create_dir_all, read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use toml::{Table, Value};
+
+use crate::fs::{copy_dir, write_atomic};
+use crate::save::SaveError;
+
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+pub const WORLD_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MigrationKind {
+    Save,
+    World,
+}
+
+impl MigrationKind {
+    pub fn current_version(self) -> u32 {
+        match self {
+            MigrationKind::Save => SAVE_FORMAT_VERSION,
+            MigrationKind::World => WORLD_FORMAT_VERSION,
+        }
+    }
+
+    fn descriptor_name(self) -> &'static str {
+        match self {
+            MigrationKind::Save => "Save.toml",
+            MigrationKind::World => "World.toml",
+        }
+    }
+}
+
+/// A single step that upgrades a save or world from `from_version` to `from_version + 1`.
+///
+/// `apply` receives the directory that holds the descriptor and the descriptor itself, and may rewrite any file in
+/// that directory. The descriptor's `format_version` is bumped after `apply` succeeds.
+#[derive(Debug, Copy, Clone)]
+pub struct Migration {
+    pub kind: MigrationKind,
+    pub from_version: u32,
+    pub description: &'static str,
+    pub apply: fn(&Path, &mut Table) -> Result<(), SaveError>,
+}
+
+/// The steps that upgrade older saves and worlds, which are passed to everything that opens a save.
+///
+/// Only the steps that need nothing beyond this crate are included by default, so callers that open saves with
+/// world data add the steps that re-encode it.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    migrations: Vec<Migration>,
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        let mut migrations = Self { migrations: vec![] };
+
+        migrations.insert(Migration {
+            kind: MigrationKind::Save,
+            from_version: 0,
+            description: "Add a format version to Save.toml",
+            apply: |_, _| Ok(()),
+        });
+        migrations.insert(Migration {
+            kind: MigrationKind::World,
+            from_version: 0,
+            description: "Add a format version to World.toml",
+            apply: |_, _| Ok(()),
+        });
+
+        migrations
+    }
+
+    /// Adds a step, replacing any step of the same kind from the same version.
+    pub fn insert(&mut self, migration: Migration) {
+        self.migrations
+            .retain(|x| x.kind != migration.kind || x.from_version != migration.from_version);
+        self.migrations.push(migration);
+    }
+
+    pub fn get(&self, kind: MigrationKind, from_version: u32) -> Option<Migration> {
+        self.migrations
+            .iter()
+            .find(|x| x.kind == kind && x.from_version == from_version)
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub backup_path: Option<PathBuf>,
+    pub applied: Vec<AppliedMigration>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub kind: MigrationKind,
+    /// The name of the migrated world, or `None` for the save descriptor.
+    pub world: Option<String>,
+    pub from_version: u32,
+    pub description: &'static str,
+}
+
+pub(crate) fn migrate(save_path: &Path, migrations: &Migrations) -> Result<MigrationReport, SaveError> {
+    let mut pending = vec![];
+
+    let save_descriptor = Descriptor::read(save_path, MigrationKind::Save, None)?;
+    if save_descriptor.is_outdated() {
+        pending.push(save_descriptor);
+    }
+
+    let worlds_path = save_path.join("worlds");
+    if worlds_path.is_dir() {
+        for entry in read_dir(&worlds_path)? {
+            let entry = entry?;
+            if !entry.path().join("World.toml").is_file() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let world_descriptor = Descriptor::read(&entry.path(), MigrationKind::World, Some(name))?;
+            if world_descriptor.is_outdated() {
+                pending.push(world_descriptor);
+            }
+        }
+    }
+
+    let mut report = MigrationReport::default();
+    if pending.is_empty() {
+        return Ok(report);
+    }
+
+    // Every step is looked up before anything is written, so a missing step leaves the save untouched.
+    let mut steps = vec![];
+    for descriptor in &pending {
+        let mut descriptor_steps = vec![];
+        for from_version in descriptor.version..descriptor.kind.current_version() {
+            let migration = migrations
+                .get(descriptor.kind, from_version)
+                .ok_or(SaveError::MissingMigration {
+                    kind: descriptor.kind,
+                    version: from_version,
+                })?;
+            descriptor_steps.push(migration);
+        }
+        steps.push(descriptor_steps);
+    }
+
+    let backup_path = create_backup(save_path)?;
+    report.backup_path = Some(backup_path);
+
+    for (mut descriptor, steps) in pending.into_iter().zip(steps) {
+        for migration in steps {
+            let from_version = descriptor.version;
+
+            (migration.apply)(&descriptor.dir_path, &mut descriptor.table)?;
+
+            descriptor.version += 1;
+            descriptor.write()?;
+
+            report.applied.push(AppliedMigration {
+                kind: descriptor.kind,
+                world: descriptor.world.clone(),
+                from_version,
+                description: migration.description,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+struct Descriptor {
+    kind: MigrationKind,
+    world: Option<String>,
+    dir_path: PathBuf,
+    table: Table,
+    version: u32,
+}
+
+impl Descriptor {
+    fn read(dir_path: &Path, kind: MigrationKind, world: Option<String>) -> Result<Self, SaveError> {
+        let table: Table = toml::from_str(&read_to_string(dir_path.join(kind.descriptor_name()))?)?;
+        let version = match table.get("format_version") {
+            None => 0,
+            Some(Value::Integer(x)) => u32::try_from(*x).map_err(|_| SaveError::UnsupportedVersion { kind, version: u32::MAX })?,
+            Some(_) => return Err(SaveError::UnsupportedVersion { kind, version: u32::MAX }),
+        };
+
+        if version > kind.current_version() {
+            return Err(SaveError::UnsupportedVersion { kind, version });
+        }
+
+        Ok(Self {
+            kind,
+            world,
+            dir_path: dir_path.to_path_buf(),
+            table,
+            version,
+        })
+    }
+
+    fn is_outdated(&self) -> bool {
+        self.version < self.kind.current_version()
+    }
+
+    fn write(&mut self) -> Result<(), SaveError> {
+        self.table
+            .insert("format_version".to_string(), Value::Integer(self.version as i64));
+        write_atomic(self.dir_path.join(self.kind.descriptor_name()), toml::to_string(&self.table)?)?;
+
+        Ok(())
+    }
+}
+
+fn create_backup(save_path: &Path) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    let backup_path = save_path
+        .join("backups")
+        .join(format!("pre-migration-{timestamp}"));
+
+    create_dir_all(&backup_path)?;
+    copy(save_path.join("Save.toml"), backup_path.join("Save.toml"))?;
+
+    let worlds_path = save_path.join("worlds");
+    if worlds_path.is_dir() {
+        copy_dir(&worlds_path, &backup_path.join("worlds"))?;
+    }
+
+    Ok(backup_path)
+}
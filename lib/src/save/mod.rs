@@ -0,0 +1,340 @@
+use std::fs::{ReadDir, create_dir, create_dir_all, read_dir, read_to_string, remove_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::fs::{remove_temp_files, write_atomic};
+use crate::rotation::Euler;
+use crate::vector::vec3d;
+use crate::world::Health;
+
+pub use archive::{Archive, ArchiveEntry, ArchiveError, ArchiveManifest, ImportOptions};
+pub use generator::{GeneratorConfig, MaterialBand, NoiseLayer, WorldType};
+pub use snapshot::{Snapshot, SnapshotManifest};
+pub use migration::{AppliedMigration, Migration, MigrationKind, MigrationReport, Migrations, SAVE_FORMAT_VERSION, WORLD_FORMAT_VERSION};
+
+pub mod archive;
+pub mod generator;
+pub mod migration;
+pub mod snapshot;
+
+#[derive(Debug, Clone)]
+pub struct Save {
+    pub path: PathBuf,
+    pub descriptor: SaveDescriptor,
+    pub migrations: MigrationReport,
+}
+
+impl Save {
+    /// Opens a save, upgrading it and its worlds with `migrations` if they are older than the current format.
+    pub fn open(path: PathBuf, migrations: &Migrations) -> Result<Self, SaveError> {
+        remove_temp_files(&path);
+
+        let migrations = migration::migrate(&path, migrations)?;
+        let descriptor = toml::from_str(&read_to_string(path.join("Save.toml"))?)?;
+
+        Ok(Self { path, descriptor, migrations })
+    }
+
+    pub fn worlds(&self) -> io::Result<Worlds> {
+        Worlds::new(self.path.join("worlds"))
+    }
+
+    pub fn world(&self, name: &str) -> Result<SaveWorld, SaveError> {
+        let world_path = self.world_path(name)?;
+        if !world_path.exists() {
+            return Err(SaveError::WorldNotFound(name.to_string()));
+        }
+
+        SaveWorld::open(world_path, name.to_string())
+    }
+
+    pub fn default_world(&self) -> Result<SaveWorld, SaveError> {
+        self.world(&self.descriptor.default_world)
+    }
+
+    pub fn create_world(&self, attributes: WorldAttributes) -> Result<SaveWorld, SaveError> {
+        let world_path = self.world_path(&attributes.name)?;
+        if world_path.exists() {
+            return Err(SaveError::WorldAlreadyExists(attributes.name));
+        }
+
+        create_world_dir(&world_path, attributes)
+    }
+
+    pub fn delete_world(&self, name: &str) -> Result<(), SaveError> {
+        if name == self.descriptor.default_world {
+            return Err(SaveError::DefaultWorld(name.to_string()));
+        }
+
+        let world_path = self.world_path(name)?;
+        if !world_path.exists() {
+            return Err(SaveError::WorldNotFound(name.to_string()));
+        }
+
+        remove_dir_all(world_path)?;
+
+        Ok(())
+    }
+
+    fn world_path(&self, name: &str) -> Result<PathBuf, SaveError> {
+        if !is_valid_world_name(name) {
+            return Err(SaveError::InvalidWorldName(name.to_string()));
+        }
+
+        Ok(self.path.join("worlds").join(name))
+    }
+
+    pub fn create(path: &Path, attributes: SaveAttributes) -> Result<Self, SaveError> {
+        if path.exists() {
+            return Err(SaveError::AlreadyExists);
+        }
+
+        if !is_valid_world_name(&attributes.default_world.name) {
+            return Err(SaveError::InvalidWorldName(attributes.default_world.name));
+        }
+
+        create_dir(path)?;
+
+        let worlds_path = path.join("worlds");
+        if !worlds_path.exists() {
+            create_dir(&worlds_path)?;
+        }
+
+        let descriptor = SaveDescriptor {
+            format_version: SAVE_FORMAT_VERSION,
+            title: attributes.title,
+            default_world: attributes.default_world.name.clone(),
+        };
+
+        let world_path = worlds_path.join(&attributes.default_world.name);
+        if !world_path.exists() {
+            create_world_dir(&world_path, attributes.default_world)?;
+        }
+
+        let descriptor_path = path.join("Save.toml");
+        write_atomic(&descriptor_path, toml::to_string(&descriptor)?)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            descriptor,
+            migrations: MigrationReport::default(),
+        })
+    }
+
+    pub fn create_or_open(path: PathBuf, attributes: SaveAttributes, migrations: &Migrations) -> Result<Self, SaveError> {
+        match Save::create(&path, attributes) {
+            Ok(x) => Ok(x),
+            Err(SaveError::AlreadyExists) => Save::open(path, migrations),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub struct SaveAttributes {
+    pub title: String,
+    pub default_world: WorldAttributes,
+}
+
+fn create_world_dir(path: &Path, mut attributes: WorldAttributes) -> Result<SaveWorld, SaveError> {
+    create_dir_all(path)?;
+
+    attributes.descriptor.format_version = WORLD_FORMAT_VERSION;
+    write_atomic(path.join("World.toml"), toml::to_string(&attributes.descriptor)?)?;
+
+    Ok(SaveWorld {
+        path: path.to_path_buf(),
+        name: attributes.name,
+        descriptor: attributes.descriptor,
+    })
+}
+
+pub(crate) fn is_valid_world_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+}
+
+pub struct WorldAttributes {
+    pub name: String,
+    pub descriptor: WorldDescriptor,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorldDescriptor {
+    #[serde(default)]
+    pub format_version: u32,
+    pub title: String,
+    pub seed: i64,
+    #[serde(default)]
+    pub chunk_compression: ChunkCompression,
+    #[serde(default)]
+    pub terrain: TerrainMode,
+    #[serde(default)]
+    pub generator: GeneratorConfig,
+    #[serde(default)]
+    pub world_type: WorldType,
+    /// Where players spawn, which is searched for when the world is first loaded.
+    #[serde(default)]
+    pub spawn: Option<vec3d>,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerrainMode {
+    #[default]
+    Heightmap,
+    Density,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkCompression {
+    None,
+    Rle,
+    #[default]
+    Lz,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveDescriptor {
+    #[serde(default)]
+    pub format_version: u32,
+    pub title: String,
+    pub default_world: String,
+}
+
+#[derive(Debug)]
+pub struct Saves {
+    read_dir: ReadDir,
+    migrations: Migrations,
+}
+
+impl Saves {
+    pub fn new(path: impl AsRef<Path>, migrations: Migrations) -> io::Result<Saves> {
+        Ok(Self {
+            read_dir: read_dir(path)?,
+            migrations,
+        })
+    }
+}
+
+impl Iterator for Saves {
+    type Item = Result<Save, SaveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = self.read_dir.next()?.ok()?;
+
+        while !entry.file_type().ok()?.is_dir() {
+            entry = self.read_dir.next()?.ok()?;
+        }
+
+        Some(Save::open(entry.path(), &self.migrations))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("Failed to read save directory: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to deserialize save descriptor: {0}")]
+    TomlDe(#[from] toml::de::Error),
+    #[error("Failed to serialize save descriptor: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+    #[error("Save already exists")]
+    AlreadyExists,
+    #[error("World '{0}' already exists")]
+    WorldAlreadyExists(String),
+    #[error("World '{0}' does not exist")]
+    WorldNotFound(String),
+    #[error("Invalid world name '{0}'")]
+    InvalidWorldName(String),
+    #[error("World '{0}' is the default world of the save and cannot be deleted")]
+    DefaultWorld(String),
+    #[error("Unsupported {kind:?} format version {version} (expected at most {})", kind.current_version())]
+    UnsupportedVersion { kind: MigrationKind, version: u32 },
+    #[error("No migration is registered to upgrade {kind:?} format version {version}")]
+    MissingMigration { kind: MigrationKind, version: u32 },
+    #[error("Migration failed: {0}")]
+    Migration(String),
+    #[error("Invalid save archive: {0}")]
+    Archive(#[from] ArchiveError),
+}
+
+pub struct Worlds {
+    read_dir: ReadDir,
+}
+
+impl Worlds {
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self { read_dir: read_dir(path)? })
+    }
+}
+
+impl Iterator for Worlds {
+    type Item = Result<SaveWorld, SaveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = self.read_dir.next()?.ok()?;
+
+        while !entry.file_type().ok()?.is_dir() {
+            entry = self.read_dir.next()?.ok()?;
+        }
+
+        Some(SaveWorld::open(entry.path(), entry.file_name().to_string_lossy().into_owned()))
+    }
+}
+
+#[derive(Debug)]
+pub struct SaveWorld {
+    pub path: PathBuf,
+    pub name: String,
+    pub descriptor: WorldDescriptor,
+}
+
+impl SaveWorld {
+    pub fn open(path: PathBuf, name: String) -> Result<Self, SaveError> {
+        let descriptor = toml::from_str(&read_to_string(path.join("World.toml"))?)?;
+        Ok(Self { path, name, descriptor })
+    }
+
+    pub fn write_descriptor(&self) -> Result<(), SaveError> {
+        write_atomic(self.path.join("World.toml"), toml::to_string(&self.descriptor)?)?;
+
+        Ok(())
+    }
+
+    pub fn read_player(&self, name: &str) -> Result<Option<PlayerData>, SaveError> {
+        let path = self.player_path(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(toml::from_str(&read_to_string(path)?)?))
+    }
+
+    pub fn write_player(&self, name: &str, data: &PlayerData) -> Result<(), SaveError> {
+        create_dir_all(self.path.join("players"))?;
+        write_atomic(self.player_path(name), toml::to_string(data)?)?;
+
+        Ok(())
+    }
+
+    fn player_path(&self, name: &str) -> PathBuf {
+        self.path
+            .join("players")
+            .join(format!("{name}.toml"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub position: vec3d,
+    pub rotation: Euler<f32>,
+    pub health: Health,
+    pub has_gravity: bool,
+    pub acceleration_rate: f64,
+}
@@ -0,0 +1,194 @@
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, remove_file, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::fs::{copy_dir, write_atomic};
+use crate::save::{Migrations, Save, SaveError};
+
+// Snapshots live under `<snapshots>/<save name>/<created>[-n]/` and hold a copy of `Save.toml`, `worlds/` and the
+// material overrides in `materials/` next to a `Snapshot.toml` manifest. The manifest is written last, so a directory without one is an interrupted snapshot and is
+// ignored.
+
+const MANIFEST_NAME: &str = "Snapshot.toml";
+const RESTORING_SUFFIX: &str = ".restoring";
+const REPLACED_SUFFIX: &str = ".replaced";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub manifest: SnapshotManifest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub save_title: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+}
+
+impl Save {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    pub fn create_snapshot(&self, snapshots_path: &Path) -> Result<Snapshot, SaveError> {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+
+        let dir_path = snapshots_path.join(self.name());
+        let mut path = dir_path.join(created.to_string());
+        let mut n = 1;
+        while path.exists() {
+            path = dir_path.join(format!("{created}-{n}"));
+            n += 1;
+        }
+
+        create_dir_all(&path)?;
+        copy(self.path.join("Save.toml"), path.join("Save.toml"))?;
+        copy_dir(&self.path.join("worlds"), &path.join("worlds"))?;
+        let materials_path = self.path.join("materials");
+        if materials_path.is_dir() {
+            copy_dir(&materials_path, &path.join("materials"))?;
+        }
+
+        let manifest = SnapshotManifest {
+            save_title: self.descriptor.title.clone(),
+            created,
+        };
+        write_atomic(path.join(MANIFEST_NAME), toml::to_string(&manifest)?)?;
+
+        Ok(Snapshot { path, manifest })
+    }
+
+    /// Returns the snapshots of this save, oldest first.
+    pub fn snapshots(&self, snapshots_path: &Path) -> Result<Vec<Snapshot>, SaveError> {
+        let dir_path = snapshots_path.join(self.name());
+        if !dir_path.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut snapshots = vec![];
+        for entry in read_dir(dir_path)? {
+            let path = entry?.path();
+            let manifest_path = path.join(MANIFEST_NAME);
+            if !manifest_path.is_file() {
+                continue;
+            }
+
+            let manifest = toml::from_str(&read_to_string(manifest_path)?)?;
+            snapshots.push(Snapshot { path, manifest });
+        }
+
+        snapshots.sort_by(|a, b| {
+            a.manifest
+                .created
+                .cmp(&b.manifest.created)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        Ok(snapshots)
+    }
+
+    /// Removes all but the newest `keep` snapshots and returns the removed ones.
+    pub fn prune_snapshots(&self, snapshots_path: &Path, keep: usize) -> Result<Vec<Snapshot>, SaveError> {
+        let mut snapshots = self.snapshots(snapshots_path)?;
+        let count = snapshots.len().saturating_sub(keep);
+
+        let removed: Vec<_> = snapshots.drain(..count).collect();
+        for snapshot in &removed {
+            remove_dir_all(&snapshot.path)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Replaces the live save with the contents of `snapshot` and reopens it. The backups taken before migrations are
+    /// kept, since snapshots do not hold them, and so are the material overrides if the snapshot was taken before
+    /// snapshots held them.
+    pub fn restore_snapshot(self, snapshot: &Snapshot, migrations: &Migrations) -> Result<Save, SaveError> {
+        let staging_path = staging_path(&self.path)?;
+        copy_dir(&snapshot.path, &staging_path)?;
+        remove_file(staging_path.join(MANIFEST_NAME))?;
+
+        let backups_path = self.path.join("backups");
+        if backups_path.is_dir() {
+            copy_dir(&backups_path, &staging_path.join("backups"))?;
+        }
+
+        let materials_path = self.path.join("materials");
+        let staged_materials_path = staging_path.join("materials");
+        if materials_path.is_dir() && !staged_materials_path.exists() {
+            copy_dir(&materials_path, &staged_materials_path)?;
+        }
+
+        install(&staging_path, &self.path)?;
+
+        Save::open(self.path, migrations)
+    }
+}
+
+/// Returns an empty staging directory path for a save that will be moved into place with [`install`].
+pub(crate) fn staging_path(save_path: &Path) -> io::Result<PathBuf> {
+    let path = with_suffix(save_path, RESTORING_SUFFIX);
+    if path.exists() {
+        remove_dir_all(&path)?;
+    }
+
+    Ok(path)
+}
+
+/// Moves a staged save into place, replacing the save at `save_path` if there is one.
+pub(crate) fn install(staging_path: &Path, save_path: &Path) -> io::Result<()> {
+    if !save_path.exists() {
+        return rename(staging_path, save_path);
+    }
+
+    let replaced_path = with_suffix(save_path, REPLACED_SUFFIX);
+    if replaced_path.exists() {
+        remove_dir_all(&replaced_path)?;
+    }
+
+    rename(save_path, &replaced_path)?;
+    rename(staging_path, save_path)?;
+    remove_dir_all(&replaced_path)
+}
+
+/// Finishes or rolls back restores that were interrupted between their renames.
+pub(crate) fn recover_restores(saves_path: &Path) -> io::Result<()> {
+    for entry in read_dir(saves_path)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|x| x.to_str()) else {
+            continue;
+        };
+
+        if name.ends_with(RESTORING_SUFFIX) {
+            remove_dir_all(&path)?;
+        } else if let Some(live_name) = name.strip_suffix(REPLACED_SUFFIX) {
+            let live_path = path.with_file_name(live_name);
+            if live_path.exists() {
+                remove_dir_all(&path)?;
+            } else {
+                rename(&path, &live_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_owned();
+    file_name.push(suffix);
+
+    path.with_file_name(file_name)
+}
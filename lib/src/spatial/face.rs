@@ -162,14 +162,14 @@ impl CubeFaces {
 
     pub fn set(&mut self, face: CubeFace, active: bool) {
         if active {
-            self.0 |= face as u8;
+            *self += face;
         } else {
-            self.0 &= !(face as u8);
+            *self -= face;
         }
     }
 
     pub fn contains(self, face: CubeFace) -> bool {
-        self.0 & face as u8 != 0
+        self.0 & CubeFaces::from(face).0 != 0
     }
 }
 
@@ -272,13 +272,13 @@ impl Add<CubeFace> for CubeFaces {
     type Output = Self;
 
     fn add(self, rhs: CubeFace) -> Self::Output {
-        Self(self.0 | rhs as u8)
+        Self(self.0 | CubeFaces::from(rhs).0)
     }
 }
 
 impl AddAssign<CubeFace> for CubeFaces {
     fn add_assign(&mut self, rhs: CubeFace) {
-        self.0 |= rhs as u8;
+        self.0 |= CubeFaces::from(rhs).0;
     }
 }
 
@@ -300,13 +300,13 @@ impl Sub<CubeFace> for CubeFaces {
     type Output = Self;
 
     fn sub(self, rhs: CubeFace) -> Self::Output {
-        Self(self.0 & !(rhs as u8))
+        Self(self.0 & !CubeFaces::from(rhs).0)
     }
 }
 
 impl SubAssign<CubeFace> for CubeFaces {
     fn sub_assign(&mut self, rhs: CubeFace) {
-        self.0 &= !(rhs as u8);
+        self.0 &= !CubeFaces::from(rhs).0;
     }
 }
 
@@ -556,4 +556,26 @@ impl PerFaceU5 {
     pub fn set_south(&mut self, value: u8) {
         self.dns.set_z(value);
     }
+
+    pub fn get(&self, face: CubeFace) -> u8 {
+        match face {
+            CubeFace::East => self.east(),
+            CubeFace::West => self.west(),
+            CubeFace::Up => self.up(),
+            CubeFace::Down => self.down(),
+            CubeFace::North => self.north(),
+            CubeFace::South => self.south(),
+        }
+    }
+
+    pub fn set(&mut self, face: CubeFace, value: u8) {
+        match face {
+            CubeFace::East => self.set_east(value),
+            CubeFace::West => self.set_west(value),
+            CubeFace::Up => self.set_up(value),
+            CubeFace::Down => self.set_down(value),
+            CubeFace::North => self.set_north(value),
+            CubeFace::South => self.set_south(value),
+        }
+    }
 }
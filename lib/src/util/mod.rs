@@ -1,6 +1,7 @@
 use std::fmt::{Display, Formatter};
 
 crate::reexport! {
+    mod checksum;
     mod group_key;
     mod time;
 }
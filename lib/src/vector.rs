@@ -1274,6 +1274,11 @@ impl vec3u5 {
     pub fn linearize(&self) -> usize {
         self.x() as usize * 32usize.pow(2) + self.z() as usize * 32 + self.y() as usize
     }
+
+    #[inline]
+    pub fn delinearize(index: usize) -> Self {
+        Self::new((index / 32usize.pow(2)) as u8, (index % 32) as u8, (index / 32 % 32) as u8)
+    }
 }
 
 assert_eq_size!(Option<vec3u5>, vec3u5);
@@ -1,4 +1,5 @@
 use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
 pub const CHUNK_EXP: u32 = 5;
@@ -7,7 +8,7 @@ pub const CHUNK_AREA: usize = CHUNK_LENGTH.pow(2);
 pub const CHUNK_VOLUME: usize = CHUNK_LENGTH.pow(3);
 
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Pod, Zeroable, Deserialize, Serialize)]
 pub struct Health {
     current: f32,
     max: f32,
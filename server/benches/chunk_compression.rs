@@ -0,0 +1,75 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use lib::point::ChunkPt;
+use lib::save::{ChunkCompression, GeneratorConfig, TerrainMode};
+use lib::vector::Vec3;
+use server::chunk::codec::CubeGrid;
+use server::chunk::registry::MaterialRegistry;
+use server::chunk::mesh::CubeMesh;
+use server::generator::biome::BiomeTable;
+use server::generator::GenerationParams;
+
+const ITERATIONS: u32 = 8;
+
+fn generate_grids() -> Vec<CubeGrid> {
+    let materials = MaterialRegistry::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/material")).unwrap();
+    let params = GenerationParams::new(0x4865_7262, TerrainMode::Heightmap, GeneratorConfig::default(), Arc::new(materials), Arc::new(BiomeTable::default()));
+
+    let mut grids = vec![];
+    for x in -4..4 {
+        for y in -2..2 {
+            for z in -4..4 {
+                let mut mesh = CubeMesh::new(ChunkPt(Vec3::new(x, y, z)));
+                params.generate(&mut mesh);
+                grids.push(CubeGrid::from_mesh(&mesh));
+            }
+        }
+    }
+
+    grids
+}
+
+fn main() {
+    let grids = generate_grids();
+    println!("{} generated chunks, {} iterations", grids.len(), ITERATIONS);
+    println!("{:<6} {:>12} {:>14} {:>14}", "codec", "bytes", "encode", "decode");
+
+    for compression in [ChunkCompression::None, ChunkCompression::Rle, ChunkCompression::Lz] {
+        let mut encoded = vec![vec![]; grids.len()];
+        let mut encode_time = Duration::ZERO;
+        let mut decode_time = Duration::ZERO;
+
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            for (grid, buf) in grids.iter().zip(&mut encoded) {
+                buf.clear();
+                grid.encode(compression, buf);
+                black_box(&buf);
+            }
+            encode_time += start.elapsed();
+
+            let start = Instant::now();
+            for buf in &encoded {
+                black_box(CubeGrid::decode(black_box(buf)).unwrap());
+            }
+            decode_time += start.elapsed();
+        }
+
+        let total_bytes: usize = encoded.iter().map(Vec::len).sum();
+        let per_chunk = |time: Duration| time / (ITERATIONS * grids.len() as u32);
+
+        println!(
+            "{:<6} {:>12} {:>14?} {:>14?}",
+            format!("{compression:?}"),
+            total_bytes,
+            per_chunk(encode_time),
+            per_chunk(decode_time)
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use time::Duration;
+
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::seconds(60);
+
+#[derive(Debug)]
+pub struct AutosaveScheduler {
+    interval: Duration,
+    elapsed: Duration,
+}
+
+impl AutosaveScheduler {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Advances the scheduler by `dt` and returns whether an autosave is due. A non-positive interval disables
+    /// autosaving.
+    pub fn tick(&mut self, dt: Duration) -> bool {
+        if !self.interval.is_positive() {
+            return false;
+        }
+
+        self.elapsed += dt;
+        if self.elapsed < self.interval {
+            return false;
+        }
+
+        self.elapsed = Duration::ZERO;
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+}
@@ -1,10 +1,28 @@
+use std::sync::Arc;
+
 use lib::point::ChunkPt;
+use lib::save::ChunkCompression;
+use lib::util::crc32;
+use lib::vector::vec3u5;
 use lib::world::CHUNK_VOLUME;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use thiserror::Error;
 
-use crate::chunk::material::{Palette, PaletteMaterialId};
+use crate::chunk::compression;
+use crate::chunk::material::{Material, Palette, PaletteMaterialId};
 use crate::chunk::mesh::CubeMesh;
 
+// Chunk file layout (all integers little-endian):
+//
+// | magic "HBCK" | version: u16 | codec: u8 | palette size: u32 | palette | body size: u32 | body | crc32: u32 |
+//
+// The palette is a u16 material count followed by the encoded materials. The body holds the cube material ids
+// (0 is air and n > 0 is palette entry n - 1) compressed by the codec identified by the codec tag. The checksum
+// covers every byte before it. Version 1 files have no codec tag and always use run-length encoding.
+
+pub const MAGIC: [u8; 4] = *b"HBCK";
+pub const FORMAT_VERSION: u16 = 2;
+
 pub struct CubeGrid {
     data: Box<[Option<PaletteMaterialId>]>,
     palette: Palette,
@@ -33,74 +51,189 @@ impl CubeGrid {
 
     pub fn to_mesh(&self, position: ChunkPt) -> CubeMesh {
         let mut mesh = CubeMesh::new(position);
+        mesh.palette = self.palette.clone();
 
-        mesh.data
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(index, dest)| {
-                dest.material = self.data[index];
-            });
+        for (index, &material) in self.data.iter().enumerate() {
+            if material.is_some() {
+                mesh.set(vec3u5::delinearize(index), material);
+            }
+        }
 
         mesh
     }
 
-    pub fn encode(&self, buf: &mut Vec<u8>) {
-        encode_palette(&self.palette, buf);
-        encode_cubes(self.data.iter().copied(), buf);
+    pub fn get(&self, position: vec3u5) -> Option<PaletteMaterialId> {
+        self.data[position.linearize()]
+    }
+
+    pub fn set(&mut self, position: vec3u5, material: Option<PaletteMaterialId>) {
+        self.data[position.linearize()] = material;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
     }
 
-    pub fn decode(bytes: &[u8]) -> Self {
-        let mut i = 0;
-        let palette = Palette::new();
+    pub fn encode(&self, compression: ChunkCompression, buf: &mut Vec<u8>) {
+        let start = buf.len();
 
-        let mut mesh = Self::new(palette);
+        buf.extend(MAGIC);
+        buf.extend(FORMAT_VERSION.to_le_bytes());
+        buf.push(compression::tag(compression));
 
-        for &[count, m0, m1] in bytes[i..].array_chunks::<3>() {
-            let material = PaletteMaterialId::new(u16::from_le_bytes([m0, m1]));
+        encode_section(buf, |buf| encode_palette(&self.palette, buf));
+        encode_section(buf, |buf| compression::encode(compression, &self.raw_ids(), buf));
 
-            for _ in 0..count {
-                mesh.data[i] = material;
-                i += 1;
+        let checksum = crc32(&buf[start..]);
+        buf.extend(checksum.to_le_bytes());
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        let compression = match reader.u16()? {
+            1 => ChunkCompression::Rle,
+            FORMAT_VERSION => {
+                let tag = reader.u8()?;
+                compression::from_tag(tag).ok_or(DecodeError::UnknownCodec(tag))?
             }
+            version => return Err(DecodeError::BadVersion(version)),
+        };
+
+        let palette_len = reader.u32()? as usize;
+        let palette_bytes = reader.take(palette_len)?;
+        let body_len = reader.u32()? as usize;
+        let body_bytes = reader.take(body_len)?;
+
+        let checked_len = reader.position;
+        let expected = reader.u32()?;
+        let found = crc32(&bytes[..checked_len]);
+        if expected != found {
+            return Err(DecodeError::ChecksumMismatch { expected, found });
         }
 
-        mesh
+        let mut grid = Self::new(decode_palette(palette_bytes)?);
+
+        let mut raw_ids = vec![0; CHUNK_VOLUME];
+        compression::decode(compression, body_bytes, &mut raw_ids)?;
+        grid.set_raw_ids(&raw_ids)?;
+
+        Ok(grid)
+    }
+
+    fn raw_ids(&self) -> Vec<u16> {
+        self.data
+            .iter()
+            .map(|material| material.map_or(0, |id| id.to_u16() + 1))
+            .collect()
+    }
+
+    fn set_raw_ids(&mut self, raw_ids: &[u16]) -> Result<(), DecodeError> {
+        for (dest, &raw_id) in self.data.iter_mut().zip(raw_ids) {
+            *dest = match raw_id.checked_sub(1) {
+                None => None,
+                Some(id) => match PaletteMaterialId::new(id) {
+                    Some(id) if self.palette.get_by_id(id).is_some() => Some(id),
+                    _ => return Err(DecodeError::UnknownMaterial(raw_id)),
+                },
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("Chunk data ended unexpectedly")]
+    Truncated,
+    #[error("Chunk data does not start with the chunk magic number")]
+    BadMagic,
+    #[error("Unsupported chunk format version {0} (expected {FORMAT_VERSION})")]
+    BadVersion(u16),
+    #[error("Chunk is compressed with unknown codec {0}")]
+    UnknownCodec(u8),
+    #[error("Chunk checksum mismatch (expected {expected:#010x}, found {found:#010x})")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Chunk palette contains a malformed material")]
+    InvalidMaterial,
+    #[error("Chunk references material {0}, which is not in its palette")]
+    UnknownMaterial(u16),
+    #[error("Chunk body describes {0} cubes (expected {CHUNK_VOLUME})")]
+    CubeCount(usize),
+    #[error("Chunk body is not valid {0:?} data")]
+    InvalidBody(ChunkCompression),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .ok_or(DecodeError::Truncated)?;
+        let slice = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(DecodeError::Truncated)?;
+
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
     }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn encode_section(buf: &mut Vec<u8>, f: impl FnOnce(&mut Vec<u8>)) {
+    let len_index = buf.len();
+    buf.extend(0u32.to_le_bytes());
+
+    f(buf);
+
+    let len = (buf.len() - len_index - 4) as u32;
+    buf[len_index..len_index + 4].copy_from_slice(&len.to_le_bytes());
 }
 
 fn encode_palette(palette: &Palette, buf: &mut Vec<u8>) {
-    for (i, material) in palette.materials().enumerate() {
-        buf.extend((i as u16).to_le_bytes());
+    buf.extend((palette.materials().len() as u16).to_le_bytes());
+
+    for material in palette.materials() {
         material.encode(buf);
     }
 }
 
-fn encode_cubes(materials: impl Iterator<Item = Option<PaletteMaterialId>>, buf: &mut Vec<u8>) {
-    let mut count = 0;
-    let mut current = None;
-
-    for material in materials {
-        if current != material || count == u8::MAX {
-            buf.push(count);
-            buf.extend(
-                current
-                    .map(|id| id.to_u16())
-                    .unwrap_or(0)
-                    .to_le_bytes(),
-            );
-
-            current = material;
-            count = 1;
-        } else {
-            count += 1;
-        }
+fn decode_palette(bytes: &[u8]) -> Result<Palette, DecodeError> {
+    let mut reader = Reader::new(bytes);
+    let len = reader.u16()?;
+
+    let mut bytes = bytes[reader.position..].iter().copied();
+    let mut palette = Palette::new();
+    for _ in 0..len {
+        let material = Material::decode(&mut bytes).ok_or(DecodeError::InvalidMaterial)?;
+        palette.insert(Arc::new(material));
     }
 
-    buf.push(count);
-    buf.extend(
-        current
-            .map(|id| id.to_u16())
-            .unwrap_or(0)
-            .to_le_bytes(),
-    );
+    Ok(palette)
 }
@@ -0,0 +1,297 @@
+use lib::save::ChunkCompression;
+
+use crate::chunk::codec::DecodeError;
+
+// Cubes are passed to codecs as raw material ids, where 0 is air and n > 0 is palette entry n - 1.
+
+pub trait CubeCodec {
+    const TAG: u8;
+
+    fn encode(&self, cubes: &[u16], buf: &mut Vec<u8>);
+
+    fn decode(&self, bytes: &[u8], cubes: &mut [u16]) -> Result<(), DecodeError>;
+}
+
+pub fn tag(compression: ChunkCompression) -> u8 {
+    match compression {
+        ChunkCompression::None => Uncompressed::TAG,
+        ChunkCompression::Rle => Rle::TAG,
+        ChunkCompression::Lz => Lz::TAG,
+    }
+}
+
+pub fn from_tag(tag: u8) -> Option<ChunkCompression> {
+    match tag {
+        Uncompressed::TAG => Some(ChunkCompression::None),
+        Rle::TAG => Some(ChunkCompression::Rle),
+        Lz::TAG => Some(ChunkCompression::Lz),
+        _ => None,
+    }
+}
+
+pub fn encode(compression: ChunkCompression, cubes: &[u16], buf: &mut Vec<u8>) {
+    match compression {
+        ChunkCompression::None => Uncompressed.encode(cubes, buf),
+        ChunkCompression::Rle => Rle.encode(cubes, buf),
+        ChunkCompression::Lz => Lz.encode(cubes, buf),
+    }
+}
+
+pub fn decode(compression: ChunkCompression, bytes: &[u8], cubes: &mut [u16]) -> Result<(), DecodeError> {
+    match compression {
+        ChunkCompression::None => Uncompressed.decode(bytes, cubes),
+        ChunkCompression::Rle => Rle.decode(bytes, cubes),
+        ChunkCompression::Lz => Lz.decode(bytes, cubes),
+    }
+}
+
+// Uncompressed
+
+#[derive(Debug, Copy, Clone)]
+pub struct Uncompressed;
+
+impl CubeCodec for Uncompressed {
+    const TAG: u8 = 0;
+
+    fn encode(&self, cubes: &[u16], buf: &mut Vec<u8>) {
+        buf.reserve(cubes.len() * 2);
+        for cube in cubes {
+            buf.extend(cube.to_le_bytes());
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], cubes: &mut [u16]) -> Result<(), DecodeError> {
+        if bytes.len() != cubes.len() * 2 {
+            return Err(DecodeError::CubeCount(bytes.len() / 2));
+        }
+
+        for (cube, &[b0, b1]) in cubes.iter_mut().zip(bytes.as_chunks::<2>().0) {
+            *cube = u16::from_le_bytes([b0, b1]);
+        }
+
+        Ok(())
+    }
+}
+
+// Run-length encoding with (count: u8, material: u16) runs
+
+#[derive(Debug, Copy, Clone)]
+pub struct Rle;
+
+impl CubeCodec for Rle {
+    const TAG: u8 = 1;
+
+    fn encode(&self, cubes: &[u16], buf: &mut Vec<u8>) {
+        let mut count = 0;
+        let mut current = 0;
+
+        for &cube in cubes {
+            if current != cube || count == u8::MAX {
+                push_run(count, current, buf);
+
+                current = cube;
+                count = 1;
+            } else {
+                count += 1;
+            }
+        }
+
+        push_run(count, current, buf);
+    }
+
+    fn decode(&self, bytes: &[u8], cubes: &mut [u16]) -> Result<(), DecodeError> {
+        let (runs, remainder) = bytes.as_chunks::<3>();
+        if !remainder.is_empty() {
+            return Err(DecodeError::Truncated);
+        }
+
+        let mut i = 0;
+        for &[count, m0, m1] in runs {
+            let end = i + count as usize;
+            let Some(run) = cubes.get_mut(i..end) else {
+                return Err(DecodeError::CubeCount(end));
+            };
+
+            run.fill(u16::from_le_bytes([m0, m1]));
+            i = end;
+        }
+
+        if i != cubes.len() {
+            return Err(DecodeError::CubeCount(i));
+        }
+
+        Ok(())
+    }
+}
+
+fn push_run(count: u8, material: u16, buf: &mut Vec<u8>) {
+    if count == 0 {
+        return;
+    }
+
+    buf.push(count);
+    buf.extend(material.to_le_bytes());
+}
+
+// LZ77 over the little-endian cube bytes, using an LZ4-style sequence layout:
+//
+// | token: u8 | literal length ext... | literals | offset: u16 | match length ext... |
+//
+// The high nibble of the token is the literal length and the low nibble is the match length minus LZ_MIN_MATCH; a
+// nibble of 15 is followed by extension bytes that are summed until one is below 255. The final sequence carries only
+// literals.
+
+const LZ_MIN_MATCH: usize = 4;
+const LZ_HASH_BITS: u32 = 13;
+const LZ_MAX_OFFSET: usize = u16::MAX as usize;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Lz;
+
+impl CubeCodec for Lz {
+    const TAG: u8 = 2;
+
+    fn encode(&self, cubes: &[u16], buf: &mut Vec<u8>) {
+        let mut input = Vec::with_capacity(cubes.len() * 2);
+        Uncompressed.encode(cubes, &mut input);
+
+        lz_compress(&input, buf);
+    }
+
+    fn decode(&self, bytes: &[u8], cubes: &mut [u16]) -> Result<(), DecodeError> {
+        let mut output = Vec::with_capacity(cubes.len() * 2);
+        lz_decompress(bytes, &mut output, cubes.len() * 2)?;
+
+        Uncompressed.decode(&output, cubes)
+    }
+}
+
+fn lz_hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - LZ_HASH_BITS)) as usize
+}
+
+fn lz_compress(input: &[u8], buf: &mut Vec<u8>) {
+    let mut table = vec![usize::MAX; 1 << LZ_HASH_BITS];
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + LZ_MIN_MATCH <= input.len() {
+        let hash = lz_hash(&input[i..]);
+        let candidate = table[hash];
+        table[hash] = i;
+
+        let is_match = candidate != usize::MAX && i - candidate <= LZ_MAX_OFFSET && input[candidate..candidate + LZ_MIN_MATCH] == input[i..i + LZ_MIN_MATCH];
+        if !is_match {
+            i += 1;
+            continue;
+        }
+
+        let mut match_len = LZ_MIN_MATCH;
+        while i + match_len < input.len() && input[candidate + match_len] == input[i + match_len] {
+            match_len += 1;
+        }
+
+        push_sequence(&input[literal_start..i], Some(((i - candidate) as u16, match_len)), buf);
+
+        i += match_len;
+        literal_start = i;
+    }
+
+    push_sequence(&input[literal_start..], None, buf);
+}
+
+fn push_sequence(literals: &[u8], matched: Option<(u16, usize)>, buf: &mut Vec<u8>) {
+    let match_len = matched.map_or(0, |(_, len)| len - LZ_MIN_MATCH);
+    buf.push((literals.len().min(15) as u8) << 4 | match_len.min(15) as u8);
+
+    if literals.len() >= 15 {
+        push_length_ext(literals.len() - 15, buf);
+    }
+    buf.extend_from_slice(literals);
+
+    if let Some((offset, _)) = matched {
+        buf.extend(offset.to_le_bytes());
+
+        if match_len >= 15 {
+            push_length_ext(match_len - 15, buf);
+        }
+    }
+}
+
+fn push_length_ext(mut len: usize, buf: &mut Vec<u8>) {
+    while len >= 255 {
+        buf.push(255);
+        len -= 255;
+    }
+    buf.push(len as u8);
+}
+
+fn lz_decompress(bytes: &[u8], output: &mut Vec<u8>, expected_len: usize) -> Result<(), DecodeError> {
+    let invalid = || DecodeError::InvalidBody(ChunkCompression::Lz);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let token = bytes[i];
+        i += 1;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += read_length_ext(bytes, &mut i).ok_or_else(invalid)?;
+        }
+
+        let literals = bytes
+            .get(i..i + literal_len)
+            .ok_or_else(invalid)?;
+        if output.len() + literal_len > expected_len {
+            return Err(invalid());
+        }
+        output.extend_from_slice(literals);
+        i += literal_len;
+
+        if i == bytes.len() {
+            break;
+        }
+
+        let offset = match bytes.get(i..i + 2) {
+            Some(&[o0, o1]) => u16::from_le_bytes([o0, o1]) as usize,
+            _ => return Err(invalid()),
+        };
+        i += 2;
+
+        let mut match_len = (token & 15) as usize;
+        if match_len == 15 {
+            match_len += read_length_ext(bytes, &mut i).ok_or_else(invalid)?;
+        }
+        match_len += LZ_MIN_MATCH;
+
+        if offset == 0 || offset > output.len() || output.len() + match_len > expected_len {
+            return Err(invalid());
+        }
+
+        let start = output.len() - offset;
+        for j in 0..match_len {
+            output.push(output[start + j]);
+        }
+    }
+
+    if output.len() != expected_len {
+        return Err(DecodeError::CubeCount(output.len() / 2));
+    }
+
+    Ok(())
+}
+
+fn read_length_ext(bytes: &[u8], i: &mut usize) -> Option<usize> {
+    let mut len = 0;
+
+    loop {
+        let byte = *bytes.get(*i)?;
+        *i += 1;
+        len += byte as usize;
+
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
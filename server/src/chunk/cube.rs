@@ -1,6 +1,8 @@
 use std::fmt::{Debug, Formatter};
 
-use lib::spatial::{CubeFaces, PerFaceU5};
+use lib::spatial::{CubeFace, CubeFaces, PerFaceU5};
+
+use crate::chunk::light::{LightChannel, MAX_LIGHT};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -24,75 +26,73 @@ impl<M: Default> Default for Cube<M> {
     }
 }
 
+/// The faces of a cube that are shown, and the light that reaches each of them.
+///
+/// The lowest six bits hold the faces, followed by four bits of block light for each face and then four bits of sky
+/// light for each face.
 #[repr(transparent)]
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct CubeFlags {
-    value: u32,
+    value: u64,
 }
 
+const FACE_BITS: u64 = 0b111111;
+
 impl CubeFlags {
     pub const fn new() -> Self {
         Self { value: 0 }
     }
 
     pub fn faces(&self) -> CubeFaces {
-        if self.value & 1 == 0 {
-            CubeFaces::none()
-        } else {
-            CubeFaces::from((self.value >> 1) as u8)
-        }
+        CubeFaces::from((self.value & FACE_BITS) as u8)
+    }
+
+    #[inline]
+    pub fn set_faces(&mut self, faces: CubeFaces) {
+        self.value = self.value & !FACE_BITS | faces.bits() as u64;
     }
 
     pub fn insert_faces(&mut self, faces: impl Into<CubeFaces>) {
-        self.set_opaque(self.faces() + faces.into())
+        self.set_faces(self.faces() + faces.into())
     }
 
     pub fn remove_faces(&mut self, faces: impl Into<CubeFaces>) {
-        self.set_opaque(self.faces() - faces.into())
+        self.set_faces(self.faces() - faces.into())
     }
 
-    pub fn light_levels(&self) -> PerFaceU5 {
-        if self.value & 1 == 0 {
-            let east = ((self.value >> 1) & 31) as u8;
-            let west = ((self.value >> 6) & 31) as u8;
-            let top = ((self.value >> 11) & 31) as u8;
-            let bottom = ((self.value >> 16) & 31) as u8;
-            let north = ((self.value >> 21) & 31) as u8;
-            let south = ((self.value >> 26) & 31) as u8;
-
-            PerFaceU5::new(east, west, top, bottom, north, south)
-        } else {
-            PerFaceU5::ZERO
-        }
+    pub fn light_levels(&self, channel: LightChannel) -> PerFaceU5 {
+        let level = |i: u32| ((self.value >> light_shift(channel, i)) & 0xF) as u8;
+        PerFaceU5::new(level(0), level(1), level(2), level(3), level(4), level(5))
     }
 
-    #[inline]
-    pub fn set_opaque(&mut self, faces: CubeFaces) {
-        self.value = (faces.bits() as u32) << 1 | 1;
+    pub fn set_light_levels(&mut self, channel: LightChannel, light_levels: PerFaceU5) {
+        for (i, face) in CubeFace::values().enumerate() {
+            let shift = light_shift(channel, i as u32);
+            self.value = self.value & !(0xF << shift) | (light_levels.get(face).min(MAX_LIGHT) as u64) << shift;
+        }
     }
 
-    pub fn set_translucent(&mut self, light_levels: PerFaceU5) {
-        let east = light_levels.east() as u32;
-        let west = light_levels.west() as u32;
-        let top = light_levels.up() as u32;
-        let bottom = light_levels.down() as u32;
-        let north = light_levels.north() as u32;
-        let south = light_levels.south() as u32;
-
-        self.value = (east & 31) << 1 | (west & 31) << 6 | (top & 31) << 11 | (bottom & 31) << 16 | (north & 31) << 21 | (south & 31) << 26;
+    pub fn set_light(&mut self, channel: LightChannel, face: CubeFace, level: u8) {
+        let mut light_levels = self.light_levels(channel);
+        light_levels.set(face, level);
+        self.set_light_levels(channel, light_levels);
     }
+}
 
-    #[inline]
-    pub fn is_opaque(&self) -> bool {
-        self.value & 1 == 1
-    }
+fn light_shift(channel: LightChannel, face_index: u32) -> u32 {
+    let offset = match channel {
+        LightChannel::Block => 6,
+        LightChannel::Sky => 30,
+    };
+    offset + face_index * 4
 }
 
 impl Debug for CubeFlags {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CubeDependentData")
             .field("faces", &self.faces())
-            .field("light_levels", &self.light_levels())
+            .field("block_light", &self.light_levels(LightChannel::Block))
+            .field("sky_light", &self.light_levels(LightChannel::Sky))
             .finish()
     }
 }
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+
+use lib::point::{ChunkCubePt, CubePt};
+use lib::spatial::CubeFace;
+use lib::vector::vec3u5;
+use lib::world::{CHUNK_LENGTH, CHUNK_VOLUME};
+
+use crate::chunk::material::PaletteMaterialOptionExt;
+use crate::chunk::mesh::{neighbour, CubeMesh};
+
+/// The brightest level of light, given off by the most emissive materials and by the open sky.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The kinds of light, which spread separately from each other.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LightChannel {
+    /// Light given off by emissive materials.
+    Block,
+    /// Light that falls from the sky, which goes straight down without being lost.
+    Sky,
+}
+
+impl LightChannel {
+    pub fn values() -> impl Iterator<Item = Self> {
+        [Self::Block, Self::Sky].into_iter()
+    }
+}
+
+/// The cubes that light spreads through.
+///
+/// Light is stored per cube and is lost by one level for each cube it passes through. Positions that are not loaded
+/// are `None` and stop the light from spreading.
+pub trait LightVolume {
+    fn light(&self, position: CubePt, channel: LightChannel) -> Option<u8>;
+
+    fn set_light(&mut self, position: CubePt, channel: LightChannel, level: u8);
+
+    fn light_properties(&self, position: CubePt, channel: LightChannel) -> Option<LightProperties>;
+}
+
+/// How the cube at a position affects light.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct LightProperties {
+    /// Whether light is stopped by the cube.
+    pub is_opaque: bool,
+    /// The level of light the cube gives off.
+    pub emission: u8,
+}
+
+/// The level that light at a level has after spreading across a face.
+fn spread(channel: LightChannel, face: CubeFace, level: u8) -> u8 {
+    if channel == LightChannel::Sky && face == CubeFace::Down && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Spreads the light of each queued position to its neighbours, until it runs out or is stopped by opaque cubes.
+pub fn propagate(volume: &mut impl LightVolume, channel: LightChannel, mut queue: VecDeque<CubePt>) {
+    while let Some(position) = queue.pop_front() {
+        let Some(level) = volume.light(position, channel) else { continue };
+        if level <= 1 {
+            continue;
+        }
+
+        for face in CubeFace::values() {
+            let adj_position = position + face.normal();
+            let Some(properties) = volume.light_properties(adj_position, channel) else { continue };
+            if properties.is_opaque {
+                continue;
+            }
+
+            let adj_level = spread(channel, face, level);
+            if volume
+                .light(adj_position, channel)
+                .is_some_and(|x| x < adj_level)
+            {
+                volume.set_light(adj_position, channel, adj_level);
+                queue.push_back(adj_position);
+            }
+        }
+    }
+}
+
+/// Darkens the light that spread from a position which was at the given level, returning the positions that
+/// should spread their light again to fill the darkened cubes.
+pub fn remove(volume: &mut impl LightVolume, channel: LightChannel, position: CubePt, level: u8) -> VecDeque<CubePt> {
+    let mut queue = VecDeque::from([(position, level)]);
+    let mut refill = VecDeque::new();
+
+    volume.set_light(position, channel, 0);
+    while let Some((position, level)) = queue.pop_front() {
+        for face in CubeFace::values() {
+            let adj_position = position + face.normal();
+            let Some(adj_level) = volume.light(adj_position, channel) else { continue };
+
+            if adj_level != 0 && (adj_level < level || adj_level == spread(channel, face, level)) {
+                volume.set_light(adj_position, channel, 0);
+                queue.push_back((adj_position, adj_level));
+            } else if adj_level >= level {
+                refill.push_back(adj_position);
+            }
+        }
+
+        // Emitters that were darkened give off their light again.
+        let emission = volume
+            .light_properties(position, channel)
+            .map_or(0, |x| x.emission);
+        if emission > 0 {
+            volume.set_light(position, channel, emission);
+            refill.push_back(position);
+        }
+    }
+
+    refill
+}
+
+/// Updates the light around a position after the cube at it has changed.
+pub fn update(volume: &mut impl LightVolume, position: CubePt) {
+    for channel in LightChannel::values() {
+        let Some(level) = volume.light(position, channel) else { return };
+
+        let refill = remove(volume, channel, position, level);
+        propagate(volume, channel, refill);
+    }
+}
+
+/// Lights a chunk by itself, from its emitters and from the sky that falls into the columns open at its top, given
+/// by their position in the chunk. Light from the neighbouring chunks spreads in once the chunk is loaded next to them.
+pub fn light_chunk(mesh: &mut CubeMesh, is_open_to_sky: impl Fn(u8, u8) -> bool) {
+    let origin = mesh.position.0 * CHUNK_LENGTH as i32;
+    let to_position = |local: vec3u5| CubePt(origin + local.try_cast().unwrap());
+
+    let mut block_queue = VecDeque::new();
+    for i in 0..CHUNK_VOLUME {
+        let light = mesh.data[i]
+            .material
+            .using(&mesh.palette, |material| material.light)
+            .unwrap_or(0);
+        if light > 0 {
+            let local = vec3u5::delinearize(i);
+            mesh.set_light(local, LightChannel::Block, light);
+            block_queue.push_back(to_position(local));
+        }
+    }
+
+    // Sky light goes down each open column until it reaches an opaque cube.
+    let mut lit = vec![];
+    for x in 0..CHUNK_LENGTH as u8 {
+        for z in 0..CHUNK_LENGTH as u8 {
+            if !is_open_to_sky(x, z) {
+                continue;
+            }
+
+            for y in (0..CHUNK_LENGTH as u8).rev() {
+                let local = vec3u5::new(x, y, z);
+                if mesh.get(local).is_opaque(&mesh.palette) {
+                    break;
+                }
+
+                mesh.set_light(local, LightChannel::Sky, MAX_LIGHT);
+                lit.push(local);
+            }
+        }
+    }
+
+    // Only the cubes at the edges of the lit columns have darker neighbours to spread to.
+    let is_edge = |local: vec3u5| {
+        [CubeFace::East, CubeFace::West, CubeFace::North, CubeFace::South, CubeFace::Down]
+            .into_iter()
+            .any(|face| neighbour(local, face).is_some_and(|x| mesh.light(x, LightChannel::Sky) != MAX_LIGHT))
+    };
+    let sky_queue = lit
+        .into_iter()
+        .filter(|&local| is_edge(local))
+        .map(to_position)
+        .collect();
+
+    let mut volume = ChunkLight { mesh: &mut *mesh, is_open_to_sky };
+    propagate(&mut volume, LightChannel::Block, block_queue);
+    propagate(&mut volume, LightChannel::Sky, sky_queue);
+
+    mesh.refresh_face_light(|_, _| 0);
+}
+
+/// A single chunk, with everything outside of it unloaded.
+struct ChunkLight<'a, F> {
+    mesh: &'a mut CubeMesh,
+    is_open_to_sky: F,
+}
+
+impl<F> ChunkLight<'_, F> {
+    fn local(&self, position: CubePt) -> Option<vec3u5> {
+        let ChunkCubePt { chunk, local } = position.into();
+        (chunk == self.mesh.position).then_some(local)
+    }
+}
+
+impl<F: Fn(u8, u8) -> bool> LightVolume for ChunkLight<'_, F> {
+    fn light(&self, position: CubePt, channel: LightChannel) -> Option<u8> {
+        Some(self.mesh.light(self.local(position)?, channel))
+    }
+
+    fn set_light(&mut self, position: CubePt, channel: LightChannel, level: u8) {
+        if let Some(local) = self.local(position) {
+            self.mesh.set_light(local, channel, level);
+        }
+    }
+
+    fn light_properties(&self, position: CubePt, channel: LightChannel) -> Option<LightProperties> {
+        let local = self.local(position)?;
+        let material = self.mesh.get(local);
+
+        let is_opaque = material.is_opaque(&self.mesh.palette);
+        let emission = match channel {
+            LightChannel::Block => material
+                .using(&self.mesh.palette, |material| material.light)
+                .unwrap_or(0),
+            LightChannel::Sky if !is_opaque && local.y() == CHUNK_LENGTH as u8 - 1 && (self.is_open_to_sky)(local.x(), local.z()) => MAX_LIGHT,
+            LightChannel::Sky => 0,
+        };
+
+        Some(LightProperties { is_opaque, emission })
+    }
+}
@@ -1,36 +1,46 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::Add;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use crossbeam_channel::Receiver;
 use lib::aabb::Aabb3;
 use lib::collections::mailbox::Mailbox;
 use lib::point::{ChunkCubePt, ChunkPt, CubePt};
-use lib::spatial::{CubeFace, CubeFaces};
+use lib::save::WorldDescriptor;
+use lib::spatial::{CubeFace, PerFace};
 use lib::task::THREAD_POOL;
 use lib::util::{GroupKey, GroupKeyBuf};
 use lib::vector::{vec3d, vec3f, vec3i, vec3u5, Vec3};
 use lib::world::CHUNK_LENGTH;
 use line_drawing::{VoxelOrigin, WalkVoxels};
+use tracing::error;
 
 use crate::chunk::handle::ChunkLoad;
-use crate::chunk::material::{Material, PaletteMaterialId};
+use crate::chunk::light::{LightChannel, LightProperties, LightVolume, MAX_LIGHT};
+use crate::chunk::material::{Material, PaletteMaterialId, PaletteMaterialOptionExt};
+use crate::chunk::mesh::boundary;
 use crate::chunk::provider::ChunkProvider;
-use crate::chunk::{handle, Chunk};
+use crate::chunk::registry::MaterialRegistry;
+use crate::chunk::{handle, light, pending, Chunk};
+use crate::generator::biome::{Biome, BiomeId, BiomeTable};
+use crate::generator::column::ColumnCacheStats;
 use crate::handle::ClientHandle;
 
 #[derive(Debug)]
 pub struct ChunkMap {
     map: HashMap<ChunkPt, Chunk>,
     provider: ChunkProvider,
+    requested: HashSet<ChunkPt>,
     unloader: Mailbox<ChunkPt>,
 }
 
 impl ChunkMap {
-    pub fn new(seed: i64, dir_path: PathBuf) -> Self {
+    pub fn new(dir_path: PathBuf, descriptor: &WorldDescriptor, materials: Arc<MaterialRegistry>, biomes: Arc<BiomeTable>) -> Self {
         Self {
             map: HashMap::new(),
-            provider: ChunkProvider::new(dir_path, seed),
+            provider: ChunkProvider::new(dir_path, descriptor, materials, biomes),
+            requested: HashSet::new(),
             unloader: Mailbox::default(),
         }
     }
@@ -66,8 +76,8 @@ impl ChunkMap {
         let _ = self.unloader.push(position);
     }
 
-    pub fn queue_load(&self, position: ChunkPt) {
-        if self.map.contains_key(&position) {
+    pub fn queue_load(&mut self, position: ChunkPt) {
+        if self.map.contains_key(&position) || !self.requested.insert(position) {
             return;
         }
 
@@ -79,18 +89,22 @@ impl ChunkMap {
     }
 
     pub fn set_cube<'a>(&mut self, position: impl Into<CubePt>, material_ref: impl MaterialRef) {
-        let ChunkCubePt { chunk, local } = position.into().into();
+        let position = position.into();
+        let ChunkCubePt { chunk, local } = position.into();
         let material_key = material_ref.as_key_ref();
+        let material = material_key.and_then(|x| self.provider.materials.get(x)).cloned();
 
         let edges = [
-            (Vec3::new(-1, 0, 0), CubeFaces::EAST, local.x() == 0),
-            (Vec3::new(1, 0, 0), CubeFaces::WEST, local.x() == CHUNK_LENGTH as u8 - 1),
-            (Vec3::new(0, -1, 0), CubeFaces::UP, local.y() == 0),
-            (Vec3::new(0, 1, 0), CubeFaces::DOWN, local.y() == CHUNK_LENGTH as u8 - 1),
-            (Vec3::new(0, 0, -1), CubeFaces::NORTH, local.z() == 0),
-            (Vec3::new(0, 0, 1), CubeFaces::SOUTH, local.z() == CHUNK_LENGTH as u8 - 1),
+            (Vec3::new(-1, 0, 0), CubeFace::East, local.x() == 0),
+            (Vec3::new(1, 0, 0), CubeFace::West, local.x() == CHUNK_LENGTH as u8 - 1),
+            (Vec3::new(0, -1, 0), CubeFace::Up, local.y() == 0),
+            (Vec3::new(0, 1, 0), CubeFace::Down, local.y() == CHUNK_LENGTH as u8 - 1),
+            (Vec3::new(0, 0, -1), CubeFace::North, local.z() == 0),
+            (Vec3::new(0, 0, 1), CubeFace::South, local.z() == CHUNK_LENGTH as u8 - 1),
         ];
 
+        // The faces between the cube and the neighbouring chunks, as whether the cube's face is hidden.
+        let mut boundary_faces = vec![];
         for (offset, face, condition) in edges {
             if !condition {
                 continue;
@@ -110,7 +124,26 @@ impl ChunkMap {
             let index = neighbor_local_pos.linearize();
 
             let mut mesh = chunk.mesh.write();
-            mesh.data[index].flags.insert_faces(face);
+            let adj_material = mesh.data[index]
+                .material
+                .and_then(|id| mesh.palette.get_by_id(id))
+                .cloned();
+            if let Some(adj_material) = &adj_material {
+                let hidden = material
+                    .as_ref()
+                    .is_some_and(|x| adj_material.is_face_hidden_by(face, x));
+                if hidden {
+                    mesh.data[index].flags.remove_faces(face);
+                } else {
+                    mesh.data[index].flags.insert_faces(face);
+                }
+            }
+
+            let hidden = material
+                .as_ref()
+                .zip(adj_material.as_ref())
+                .is_some_and(|(material, adj_material)| material.is_face_hidden_by(face.inverse(), adj_material));
+            boundary_faces.push((face.inverse(), hidden));
 
             let Vec3 { x, y, z } = local.try_cast::<i32>().unwrap().add(offset);
             if x == 0 || x == 15 && material_key.is_none() {
@@ -144,10 +177,31 @@ impl ChunkMap {
         let Some(chunk) = self.get_chunk(chunk) else { return };
         let mut mesh = chunk.mesh.write();
 
-        let material = material_key
-            .map(|x| mesh.palette.get_id_by_key(x))
-            .flatten();
+        // The material is looked up in the registry, since the chunk's palette only has the materials it already contains.
+        let material = material.map(|x| mesh.palette.insert(x));
         mesh.set(local, material);
+        if material.is_some() {
+            for (face, hidden) in boundary_faces {
+                if hidden {
+                    mesh.data[local.linearize()].flags.remove_faces(face);
+                }
+            }
+        }
+        mesh.is_dirty = true;
+        drop(mesh);
+
+        let mut light = self.light();
+        light.refresh_faces(position);
+        light::update(&mut light, position);
+    }
+
+    /// The level of light at a cube, or `None` if it is not loaded.
+    pub fn get_light(&self, position: impl Into<CubePt>, channel: LightChannel) -> Option<u8> {
+        self.light().light(position.into(), channel)
+    }
+
+    fn light(&self) -> MapLight<'_> {
+        MapLight { map: &self.map }
     }
 
     pub fn get_material(&self, position: impl Into<CubePt>) -> Option<Arc<Material>> {
@@ -164,6 +218,11 @@ impl ChunkMap {
         self.get_chunk(chunk)?.mesh.read().get(local)
     }
 
+    pub fn biome_at(&self, position: impl Into<CubePt>) -> (BiomeId, &Biome) {
+        let CubePt(Vec3 { x, z, .. }) = position.into();
+        self.provider.generator.params().biome_at(x, z)
+    }
+
     pub fn has_collider(&self, position: impl Into<CubePt>) -> bool {
         let ChunkCubePt { chunk, local } = position.into().into();
         let Some(chunk) = self.get_chunk(chunk) else {
@@ -201,8 +260,23 @@ impl ChunkMap {
     }
 
     fn load_provided(&mut self, handle: &ClientHandle) {
-        for mesh in self.provider.dequeue() {
+        let mut is_written = false;
+        for mut mesh in self.provider.dequeue() {
             let position = mesh.position;
+            if !self.requested.remove(&position) {
+                if mesh.is_dirty {
+                    is_written |= self.provider.write(&mesh);
+                }
+                continue;
+            }
+
+            let writes = self.provider.pending.take(position);
+            let written = writes
+                .iter()
+                .map(|x| CubePt::from(ChunkCubePt { chunk: position, local: x.local }))
+                .collect::<Vec<_>>();
+            pending::apply(&mut mesh, writes, &self.provider.materials);
+
             let (game_handle, client_handle) = handle::create(position);
             let chunk = Chunk::new(mesh, client_handle);
 
@@ -224,23 +298,357 @@ impl ChunkMap {
             }
 
             self.map.insert(chunk.position, chunk);
+            let mut light = self.light();
+            light.load(position);
+            // The chunk was lit before the pending writes were applied to it.
+            for position in written {
+                light.refresh_faces(position);
+                light::update(&mut light, position);
+            }
+        }
+
+        if is_written {
+            self.provider.sync();
         }
     }
 
-    fn unload_requested(&mut self, handle: &ClientHandle) {
+    pub(crate) fn unload_requested(&mut self, handle: &ClientHandle) {
+        let mut columns = HashSet::new();
+        let mut uncovered = vec![];
         for chunk_position in &self.unloader {
-            self.map.remove(&chunk_position);
+            self.requested.remove(&chunk_position);
+
+            if let Some(chunk) = self.map.remove(&chunk_position) {
+                chunk.save(&self.provider);
+                uncovered.push(chunk_position - CubeFace::Up.normal());
+            }
+
             handle.chunks.unload(chunk_position);
+            columns.insert(chunk_position.0.xz());
+        }
+
+        if columns.is_empty() {
+            return;
+        }
+        self.provider.sync();
+
+        // The chunks below the unloaded ones are open to the sky again.
+        let mut light = self.light();
+        for position in uncovered {
+            light.uncover(position);
+            light.refresh_chunk_faces(position);
+        }
+
+        // A column is only evicted once none of its chunks are loaded or waiting to be generated.
+        for position in self.map.keys().chain(&self.requested) {
+            columns.remove(&position.0.xz());
+        }
+        for column in columns {
+            self.provider
+                .generator
+                .column_cache()
+                .evict(column);
+        }
+    }
+
+    pub fn materials(&self) -> &MaterialRegistry {
+        &self.provider.materials
+    }
+
+    /// Searches for a spawn point on the thread pool, see [`crate::generator::ChunkGenerator::find_spawn`].
+    pub fn request_spawn(&self) -> Receiver<Option<vec3d>> {
+        self.provider.generator.request_spawn()
+    }
+
+    pub fn column_cache_stats(&self) -> ColumnCacheStats {
+        self.provider
+            .generator
+            .column_cache()
+            .stats()
+    }
+
+    fn apply_pending(&mut self) {
+        let writes = self
+            .provider
+            .pending
+            .take_where(|position| self.map.contains_key(&position));
+
+        for (chunk_position, writes) in writes {
+            let positions = writes
+                .iter()
+                .map(|x| CubePt::from(ChunkCubePt { chunk: chunk_position, local: x.local }))
+                .collect::<Vec<_>>();
+
+            let chunk = &self.map[&chunk_position];
+            pending::apply(&mut chunk.mesh.write(), writes, &self.provider.materials);
+
+            let mut light = self.light();
+            for position in positions {
+                light.refresh_faces(position);
+                light::update(&mut light, position);
+            }
         }
     }
 
     pub fn update(&mut self, handle: &ClientHandle) {
         self.load_provided(handle);
         self.unload_requested(handle);
+        self.apply_pending();
+
+        for chunk in self.map.values() {
+            chunk.update();
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            let stats = self.column_cache_stats();
+            tracing_tracy::client::plot!("column_cache_hit_rate", stats.hit_rate());
+            tracing_tracy::client::plot!("column_cache_len", stats.len as f64);
+        }
+    }
 
+    pub fn save(&self) {
         for chunk in self.map.values() {
-            chunk.update(self.provider.dir_path.clone());
+            chunk.save(&self.provider);
         }
+        self.provider.sync();
+
+        if let Err(e) = self.provider.pending.save() {
+            error!("Failed to save pending writes: {}", e);
+        }
+    }
+}
+
+/// The light of the loaded chunks. Each access locks only the chunk it is in, so chunks are never locked while
+/// another is.
+///
+/// Chunks above the top of the loaded world are taken to be open sky, so the sky light of a column starts at the
+/// topmost loaded chunk. It is darkened again if a chunk that covers it is loaded later, and opened again once that
+/// chunk is unloaded.
+struct MapLight<'a> {
+    map: &'a HashMap<ChunkPt, Chunk>,
+}
+
+impl MapLight<'_> {
+    /// Lights a chunk that was just loaded, which was already lit by itself when it was generated or read. Only the
+    /// light that crosses its borders spreads here, so the work on the game thread grows with the area of the chunk
+    /// rather than its volume.
+    #[tracing::instrument(name = "chunk_light", skip_all)]
+    fn load(&mut self, position: ChunkPt) {
+        let Some(chunk) = self.map.get(&position) else { return };
+        let origin = position.0 * CHUNK_LENGTH as i32;
+
+        // Light spreads both ways across each border with a loaded chunk, from the cubes on either side of it.
+        let mut block_queue = VecDeque::new();
+        let mut sky_queue = VecDeque::new();
+        for face in CubeFace::values() {
+            let adj_position = position + face.normal();
+            let Some(adj_chunk) = self.map.get(&adj_position) else { continue };
+
+            for (chunk, chunk_origin, side) in [(chunk, origin, face), (adj_chunk, adj_position.0 * CHUNK_LENGTH as i32, face.inverse())] {
+                let mesh = chunk.mesh.read();
+                let boundary = boundary(side);
+                for x in boundary.x {
+                    for y in boundary.y.clone() {
+                        for z in boundary.z.clone() {
+                            let local = vec3u5::new(x, y, z);
+                            let position = CubePt(chunk_origin + local.try_cast().unwrap());
+                            if mesh.light(local, LightChannel::Block) > 1 {
+                                block_queue.push_back(position);
+                            }
+                            if mesh.light(local, LightChannel::Sky) > 1 {
+                                sky_queue.push_back(position);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        light::propagate(self, LightChannel::Block, block_queue);
+        light::propagate(self, LightChannel::Sky, sky_queue);
+
+        // The chunk was lit without knowing what is above it, so its top is opened to the sky if nothing is, and
+        // darkened where the chunk above covers it.
+        self.uncover(position);
+        self.cover(position + CubeFace::Up.normal());
+        self.cover(position);
+        self.refresh_chunk_faces(position);
+    }
+
+    /// Lights the top of a chunk as open sky, once nothing is loaded above it.
+    fn uncover(&mut self, position: ChunkPt) {
+        if self.map.contains_key(&(position + CubeFace::Up.normal())) {
+            return;
+        }
+        let Some(chunk) = self.map.get(&position) else { return };
+        let top = CHUNK_LENGTH as u8 - 1;
+
+        let uncovered = {
+            let mesh = chunk.mesh.read();
+            let mut uncovered = VecDeque::new();
+            for x in 0..CHUNK_LENGTH as u8 {
+                for z in 0..CHUNK_LENGTH as u8 {
+                    let local = vec3u5::new(x, top, z);
+                    if !mesh.get(local).is_opaque(&mesh.palette) && mesh.light(local, LightChannel::Sky) != MAX_LIGHT {
+                        uncovered.push_back(CubePt(position.0 * CHUNK_LENGTH as i32 + local.try_cast().unwrap()));
+                    }
+                }
+            }
+            uncovered
+        };
+
+        for &position in &uncovered {
+            self.set_light(position, LightChannel::Sky, MAX_LIGHT);
+        }
+        light::propagate(self, LightChannel::Sky, uncovered);
+    }
+
+    /// Darkens the columns below a chunk that were lit as if it were open sky, where the chunk does not let the full
+    /// sky light through.
+    fn cover(&mut self, position: ChunkPt) {
+        let below_position = position - CubeFace::Up.normal();
+        let (Some(chunk), Some(below)) = (self.map.get(&position), self.map.get(&below_position)) else {
+            return;
+        };
+        let top = CHUNK_LENGTH as u8 - 1;
+
+        // The bottom layer of the chunk is copied out so that the chunk below is not locked while it is.
+        let mut bottom = [[0; CHUNK_LENGTH]; CHUNK_LENGTH];
+        {
+            let mesh = chunk.mesh.read();
+            for x in 0..CHUNK_LENGTH as u8 {
+                for z in 0..CHUNK_LENGTH as u8 {
+                    bottom[x as usize][z as usize] = mesh.light(vec3u5::new(x, 0, z), LightChannel::Sky);
+                }
+            }
+        }
+
+        let covered = {
+            let below_mesh = below.mesh.read();
+            let mut covered = vec![];
+            for x in 0..CHUNK_LENGTH as u8 {
+                for z in 0..CHUNK_LENGTH as u8 {
+                    let local = vec3u5::new(x, top, z);
+                    if below_mesh.light(local, LightChannel::Sky) == MAX_LIGHT && bottom[x as usize][z as usize] != MAX_LIGHT {
+                        covered.push(CubePt(below_position.0 * CHUNK_LENGTH as i32 + local.try_cast().unwrap()));
+                    }
+                }
+            }
+            covered
+        };
+
+        for position in covered {
+            let refill = light::remove(self, LightChannel::Sky, position, MAX_LIGHT);
+            light::propagate(self, LightChannel::Sky, refill);
+        }
+    }
+
+    /// Sets the light of every face in a chunk, along with the faces of the neighbouring chunks that face it.
+    fn refresh_chunk_faces(&self, position: ChunkPt) {
+        let Some(chunk) = self.map.get(&position) else { return };
+
+        let outside = PerFace::mapped(|face| {
+            self.map
+                .get(&(position + face.normal()))
+                .map(|x| x.mesh.read().light.clone())
+        });
+        let light = chunk.mesh.read().light.clone();
+
+        chunk
+            .mesh
+            .write()
+            .refresh_face_light(|face, local| outside[face].as_ref().map_or(0, |x| x[local.linearize()]));
+
+        for face in CubeFace::values() {
+            let Some(adj_chunk) = self.map.get(&(position + face.normal())) else { continue };
+            let mut mesh = adj_chunk.mesh.write();
+            let boundary = boundary(face.inverse());
+            for x in boundary.x {
+                for y in boundary.y.clone() {
+                    for z in boundary.z.clone() {
+                        let local = vec3u5::new(x, y, z);
+                        let adj_local = vec3u5::from((local.try_cast::<i32>().unwrap() - face.normal()) & (CHUNK_LENGTH as i32 - 1));
+                        mesh.set_face_light(local, face.inverse(), light[adj_local.linearize()]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets the light that reaches each face of a cube from the cube in front of it.
+    fn refresh_faces(&self, position: CubePt) {
+        let levels = CubeFace::values()
+            .map(|face| {
+                let adj_position = position + face.normal();
+                let level = |channel| self.light(adj_position, channel).unwrap_or(0);
+                (face, level(LightChannel::Block), level(LightChannel::Sky))
+            })
+            .collect::<Vec<_>>();
+
+        let ChunkCubePt { chunk, local } = position.into();
+        let Some(chunk) = self.map.get(&chunk) else { return };
+        let mut mesh = chunk.mesh.write();
+        for (face, block, sky) in levels {
+            mesh.set_face_light(local, face, block | sky << 4);
+        }
+    }
+}
+
+impl LightVolume for MapLight<'_> {
+    fn light(&self, position: CubePt, channel: LightChannel) -> Option<u8> {
+        let ChunkCubePt { chunk, local } = position.into();
+        Some(
+            self.map
+                .get(&chunk)?
+                .mesh
+                .read()
+                .light(local, channel),
+        )
+    }
+
+    fn set_light(&mut self, position: CubePt, channel: LightChannel, level: u8) {
+        let ChunkCubePt { chunk, local } = position.into();
+        let Some(chunk) = self.map.get(&chunk) else { return };
+        chunk
+            .mesh
+            .write()
+            .set_light(local, channel, level);
+
+        // Each face is lit by the cube in front of it.
+        for face in CubeFace::values() {
+            let ChunkCubePt { chunk, local } = (position - face.normal()).into();
+            let Some(chunk) = self.map.get(&chunk) else { continue };
+            let mut mesh = chunk.mesh.write();
+
+            let cube = &mut mesh.data[local.linearize()];
+            if cube.material.is_none() || cube.flags.light_levels(channel).get(face) == level {
+                continue;
+            }
+
+            cube.flags.set_light(channel, face, level);
+            mesh.updated_positions.push(local);
+        }
+    }
+
+    fn light_properties(&self, position: CubePt, channel: LightChannel) -> Option<LightProperties> {
+        let ChunkCubePt { chunk, local } = position.into();
+        let mesh = self.map.get(&chunk)?.mesh.read();
+
+        let is_opaque = mesh.get(local).is_opaque(&mesh.palette);
+        let emission = match channel {
+            LightChannel::Block => mesh
+                .get(local)
+                .and_then(|id| mesh.palette.get_by_id(id))
+                .map_or(0, |material| material.light),
+            // The top of a chunk with nothing loaded above it is open to the sky.
+            LightChannel::Sky if !is_opaque
+                && local.y() == CHUNK_LENGTH as u8 - 1
+                && !self.map.contains_key(&(chunk + CubeFace::Up.normal())) => MAX_LIGHT,
+            LightChannel::Sky => 0,
+        };
+
+        Some(LightProperties { is_opaque, emission })
     }
 }
 
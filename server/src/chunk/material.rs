@@ -7,12 +7,13 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 
 use hashbrown::{Equivalent, HashMap};
 use lib::color::Rgba;
-use lib::spatial::CubeFaces;
+use lib::spatial::{CubeFace, CubeFaces};
 use lib::util::GroupKeyBuf;
 use serde::{Deserialize, Serialize};
 
 use crate::chunk::cube::Cube;
 use crate::chunk::handle::ClientChunkHandle;
+use crate::chunk::light::MAX_LIGHT;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Material {
@@ -20,125 +21,238 @@ pub struct Material {
     pub has_collider: bool,
     pub cullable_faces: CubeFaces,
     pub texture: Texture,
+    pub transparency: Transparency,
+    /// The level of light the material gives off, up to [`MAX_LIGHT`].
+    pub light: u8,
     pub toughness: f32,
+    pub physics: MaterialPhysics,
 }
 
 impl Material {
-    pub fn stone() -> Self {
-        Self {
-            group_key: GroupKeyBuf::new("herbolution", "stone"),
-            has_collider: true,
-            cullable_faces: CubeFaces::all(),
-            texture: Texture::Colors {
-                vec: vec![Rgba::new(0.5, 0.5, 0.5, 1.0), Rgba::new(0.6, 0.6, 0.6, 1.0), Rgba::new(0.7, 0.7, 0.7, 1.0)],
-            },
-            toughness: 10.0,
-        }
-    }
-
-    pub fn dirt() -> Self {
-        Self {
-            group_key: GroupKeyBuf::new("herbolution", "dirt"),
-            has_collider: true,
-            cullable_faces: CubeFaces::all(),
-            texture: Texture::Colors {
-                vec: vec![Rgba::new(0.4, 0.3, 0.2, 1.0), Rgba::new(0.5, 0.4, 0.3, 1.0), Rgba::new(0.6, 0.5, 0.4, 1.0)],
-            },
-            toughness: 0.95,
-        }
-    }
-
-    pub fn grass() -> Self {
-        Self {
-            group_key: GroupKeyBuf::new("herbolution", "grass"),
-            has_collider: true,
-            cullable_faces: CubeFaces::all(),
-            texture: Texture::Colors {
-                vec: vec![Rgba::new(0.1, 0.8, 0.1, 1.0), Rgba::new(0.2, 0.9, 0.2, 1.0), Rgba::new(0.3, 1.0, 0.3, 1.0)],
-            },
-            toughness: 1.05,
-        }
-    }
-
-    pub fn values() -> [Self; 3] {
-        [Self::stone(), Self::dirt(), Self::grass()]
-    }
-
     pub fn get_color(&self, p: f32) -> Rgba<f32> {
         match &self.texture {
-            Texture::Colors { vec } => vec[(vec.len().saturating_sub(1) as f32 * p) as usize],
+            Texture::Colors { vec } | Texture::Image { colors: vec, .. } => vec[(vec.len().saturating_sub(1) as f32 * p) as usize],
         }
     }
 
+    /// Whether a face of the cube is hidden by the neighbouring material it faces. Besides opaque neighbours,
+    /// translucent materials hide the faces they share with themselves, so the inside of a body of water is not drawn.
+    pub fn is_face_hidden_by(&self, face: CubeFace, neighbour: &Material) -> bool {
+        neighbour
+            .cullable_faces
+            .contains(face.inverse())
+            || self.transparency == Transparency::Translucent && neighbour.group_key == self.group_key
+    }
+
     pub fn encode(&self, buf: &mut Vec<u8>) {
         buf.push(self.group_key.group().len() as u8);
         buf.push(self.group_key.key().len() as u8);
         buf.extend(self.group_key.group().bytes());
         buf.extend(self.group_key.key().bytes());
 
-        let encoded_0 = self.cullable_faces.bits() << 6 | self.has_collider as u8;
+        // The highest bit is only set for materials with physics of their own, which are then encoded after the
+        // toughness. Most materials have the default physics, and their encoding does not grow by them.
+        let has_physics = self.physics != MaterialPhysics::default();
+        let encoded_0 = (has_physics as u8) << 7 | self.cullable_faces.bits() << 1 | self.has_collider as u8;
         buf.push(encoded_0);
 
+        // The texture tag also holds the transparency and the light, so that materials encoded before they existed
+        // are opaque and give off no light.
+        let encoded_1 = (self.transparency as u8) << 2 | self.light.min(MAX_LIGHT) << 4;
         match &self.texture {
             Texture::Colors { vec } => {
-                buf.push(0);
-                buf.extend((vec.len() as u16).to_le_bytes());
-                for rgba in vec {
-                    buf.extend(rgba.r.to_le_bytes());
-                    buf.extend(rgba.g.to_le_bytes());
-                    buf.extend(rgba.b.to_le_bytes());
-                    buf.extend(rgba.a.to_le_bytes());
+                buf.push(encoded_1);
+                encode_colors(vec, buf);
+            }
+            Texture::Image { top, side, bottom, colors } => {
+                buf.push(encoded_1 | 1);
+                for image in [top, side, bottom] {
+                    buf.push(image.len() as u8);
+                    buf.extend(image.bytes());
                 }
+                encode_colors(colors, buf);
             }
         }
 
         buf.extend(self.toughness.to_le_bytes());
-    }
 
-    pub fn decode(buf: &[u8]) -> Option<Self> {
-        let group_len = *buf.get(0)? as usize;
-        let key_len = *buf.get(1)? as usize;
-        let group_str = str::from_utf8(&buf[0..group_len]).ok()?;
-        let key_str = str::from_utf8(&buf[group_len + 1..group_len + 1 + key_len]).ok()?;
-        let group_key = GroupKeyBuf::new(&group_str, &key_str);
+        if has_physics {
+            buf.extend(self.physics.friction.to_le_bytes());
+            buf.extend(self.physics.bounciness.to_le_bytes());
+            buf.extend(self.physics.speed_multiplier.to_le_bytes());
+            buf.push(self.physics.is_climbable as u8);
+        }
+    }
 
-        let mut bytes = buf.iter().skip(group_len + 1 + key_len).copied();
+    pub fn decode(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        let group_len = bytes.next()? as usize;
+        let key_len = bytes.next()? as usize;
+        let group = String::from_utf8(bytes.by_ref().take(group_len).collect()).ok()?;
+        let key = String::from_utf8(bytes.by_ref().take(key_len).collect()).ok()?;
+        if group.len() != group_len || key.len() != key_len {
+            return None;
+        }
+        let group_key = GroupKeyBuf::new(&group, &key);
 
         let encoded_0 = bytes.next()?;
-        let has_collider = (encoded_0 >> 6) != 0;
-        let cullable_faces = CubeFaces::from(encoded_0);
+        let has_collider = encoded_0 & 1 != 0;
+        let cullable_faces = CubeFaces::from(encoded_0 >> 1 & 0b111111);
+        let has_physics = encoded_0 >> 7 != 0;
+
+        let encoded_1 = bytes.next()?;
+        let transparency = Transparency::from_u8(encoded_1 >> 2 & 0b11)?;
+        let light = encoded_1 >> 4;
 
         let texture;
-        match bytes.next()? {
+        match encoded_1 & 0b11 {
             0 => {
-                let mut vec = Vec::with_capacity(u16::from_le_bytes(bytes.next_chunk::<2>().unwrap()) as usize);
-                vec.fill_with(|| Rgba {
-                    r: f32::from_le_bytes(bytes.next_chunk().unwrap()),
-                    g: f32::from_le_bytes(bytes.next_chunk().unwrap()),
-                    b: f32::from_le_bytes(bytes.next_chunk().unwrap()),
-                    a: f32::from_le_bytes(bytes.next_chunk().unwrap()),
-                });
-
-                texture = Texture::Colors { vec };
+                texture = Texture::Colors {
+                    vec: decode_colors(bytes)?,
+                };
+            }
+            1 => {
+                let mut decode_image = || {
+                    let len = bytes.next()? as usize;
+                    let image = String::from_utf8(bytes.by_ref().take(len).collect()).ok()?;
+                    (image.len() == len).then_some(image)
+                };
+
+                texture = Texture::Image {
+                    top: decode_image()?,
+                    side: decode_image()?,
+                    bottom: decode_image()?,
+                    colors: decode_colors(bytes)?,
+                };
             }
             _ => return None,
         }
 
-        let toughness = f32::from_le_bytes(bytes.next_chunk().unwrap());
+        let toughness = f32::from_le_bytes(bytes.next_chunk().ok()?);
+
+        let mut physics = MaterialPhysics::default();
+        if has_physics {
+            physics.friction = f32::from_le_bytes(bytes.next_chunk().ok()?);
+            physics.bounciness = f32::from_le_bytes(bytes.next_chunk().ok()?);
+            physics.speed_multiplier = f32::from_le_bytes(bytes.next_chunk().ok()?);
+            physics.is_climbable = bytes.next()? != 0;
+        }
 
         Some(Self {
             group_key,
             has_collider,
             cullable_faces,
             texture,
+            transparency,
+            light,
             toughness,
+            physics,
         })
     }
 }
 
+fn encode_colors(vec: &[Rgba<f32>], buf: &mut Vec<u8>) {
+    buf.extend((vec.len() as u16).to_le_bytes());
+    for rgba in vec {
+        buf.extend(rgba.r.to_le_bytes());
+        buf.extend(rgba.g.to_le_bytes());
+        buf.extend(rgba.b.to_le_bytes());
+        buf.extend(rgba.a.to_le_bytes());
+    }
+}
+
+fn decode_colors(bytes: &mut impl Iterator<Item = u8>) -> Option<Vec<Rgba<f32>>> {
+    let len = u16::from_le_bytes(bytes.next_chunk().ok()?) as usize;
+    let mut vec = Vec::with_capacity(len);
+    for _ in 0..len {
+        vec.push(Rgba {
+            r: f32::from_le_bytes(bytes.next_chunk().ok()?),
+            g: f32::from_le_bytes(bytes.next_chunk().ok()?),
+            b: f32::from_le_bytes(bytes.next_chunk().ok()?),
+            a: f32::from_le_bytes(bytes.next_chunk().ok()?),
+        });
+    }
+
+    Some(vec)
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum Texture {
     Colors { vec: Vec<Rgba<f32>> },
+    /// Images of the block atlas for each side of the cube. The colors are used where an image cannot be, such as for
+    /// particles.
+    Image {
+        top: String,
+        side: String,
+        bottom: String,
+        colors: Vec<Rgba<f32>>,
+    },
+}
+
+impl Texture {
+    /// The name of the image shown on a face, if the texture has images.
+    pub fn image(&self, face: CubeFace) -> Option<&str> {
+        match self {
+            Texture::Colors { .. } => None,
+            Texture::Image { top, side, bottom, .. } => Some(match face {
+                CubeFace::Up => top,
+                CubeFace::Down => bottom,
+                _ => side,
+            }),
+        }
+    }
+}
+
+/// How much of what is behind a material shows through it.
+#[repr(u8)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transparency {
+    #[default]
+    Opaque,
+    /// Parts of the material are fully transparent, like the gaps between leaves.
+    Cutout,
+    /// The material is partially see-through, like glass or water, and is blended with what is behind it.
+    Translucent,
+}
+
+impl Transparency {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Opaque),
+            1 => Some(Self::Cutout),
+            2 => Some(Self::Translucent),
+            _ => None,
+        }
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self == Self::Opaque
+    }
+}
+
+/// How a material affects the entities that stand on it or are inside it.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaterialPhysics {
+    /// How quickly entities standing on the material slow down, relative to ordinary ground.
+    pub friction: f32,
+    /// How much of an entity's falling speed is kept, upward, when it lands on the material.
+    pub bounciness: f32,
+    /// How fast entities move while standing on the material or inside it.
+    pub speed_multiplier: f32,
+    /// Whether entities inside the material can climb up and down it.
+    pub is_climbable: bool,
+}
+
+impl Default for MaterialPhysics {
+    fn default() -> Self {
+        Self {
+            friction: 1.0,
+            bounciness: 0.0,
+            speed_multiplier: 1.0,
+            is_climbable: false,
+        }
+    }
 }
 
 pub type PaletteCube = Cube<Option<PaletteMaterialId>>;
@@ -237,6 +351,22 @@ pub trait PaletteMaterialOptionExt: Copy {
         self.using(palette, |material| material.cullable_faces)
             .unwrap_or(CubeFaces::none())
     }
+
+    fn is_opaque(self, palette: &Palette) -> bool {
+        self.using(palette, |material| material.transparency.is_opaque())
+            .unwrap_or(false)
+    }
+
+    /// Whether a face of the cube is hidden by the neighbouring cube it faces, which may be in another chunk with its
+    /// own palette. Air has no faces to show.
+    fn is_face_hidden(self, palette: &Palette, face: CubeFace, neighbour: Option<PaletteMaterialId>, neighbour_palette: &Palette) -> bool {
+        self.using(palette, |material| {
+            neighbour
+                .using(neighbour_palette, |x| material.is_face_hidden_by(face, x))
+                .unwrap_or(false)
+        })
+        .unwrap_or(true)
+    }
 }
 
 impl PaletteMaterialOptionExt for Option<PaletteMaterialId> {
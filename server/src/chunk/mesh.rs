@@ -1,21 +1,26 @@
 use lib::point::ChunkPt;
-use lib::spatial::{CubeFace, CubeFaces};
+use lib::spatial::{CubeFace, CubeFaces, PerFaceU5};
 use lib::vector::{vec3u5, Vec3};
 use lib::world::{CHUNK_LENGTH, CHUNK_VOLUME};
 use rayon::prelude::{IntoParallelRefMutIterator, ParallelIterator};
 use std::iter::zip;
-use std::ops::{Not, Range};
+use std::ops::Range;
 
-use crate::chunk::cube::Cube;
+use crate::chunk::cube::{Cube, CubeFlags};
+use crate::chunk::light::LightChannel;
 use crate::chunk::material::{Palette, PaletteCube, PaletteMaterialId, PaletteMaterialOptionExt};
 
 #[derive(Debug, Clone)]
 pub struct CubeMesh {
     pub position: ChunkPt,
     pub(crate) data: Box<[PaletteCube; CHUNK_VOLUME]>,
+    /// The light at each cube, with block light in the lower four bits and sky light in the upper four. It is
+    /// recomputed whenever the chunk is loaded.
+    pub(crate) light: Box<[u8; CHUNK_VOLUME]>,
     pub(crate) updated_positions: Vec<vec3u5>,
     pub(crate) exposed_faces: CubeFaces,
     pub(crate) palette: Palette,
+    pub(crate) is_dirty: bool,
 }
 
 impl CubeMesh {
@@ -23,9 +28,11 @@ impl CubeMesh {
         Self {
             position,
             data: Box::new([Cube::new(None); CHUNK_VOLUME]),
+            light: Box::new([0; CHUNK_VOLUME]),
             updated_positions: vec![],
             exposed_faces: CubeFaces::all(),
             palette: Palette::new(),
+            is_dirty: false,
         }
     }
 
@@ -33,6 +40,75 @@ impl CubeMesh {
         self.data[position.linearize()].material
     }
 
+    /// The faces of the cube that are shown.
+    pub fn faces(&self, position: vec3u5) -> CubeFaces {
+        self.data[position.linearize()].flags.faces()
+    }
+
+    /// The level of light at the cube.
+    pub fn light(&self, position: vec3u5, channel: LightChannel) -> u8 {
+        unpack_light(self.light[position.linearize()], channel)
+    }
+
+    pub(crate) fn set_light(&mut self, position: vec3u5, channel: LightChannel, level: u8) {
+        let light = &mut self.light[position.linearize()];
+        *light = match channel {
+            LightChannel::Block => *light & 0xF0 | level,
+            LightChannel::Sky => *light & 0x0F | level << 4,
+        };
+    }
+
+    /// The light that reaches each face of the cube.
+    pub fn face_light(&self, position: vec3u5, channel: LightChannel) -> PerFaceU5 {
+        self.data[position.linearize()]
+            .flags
+            .light_levels(channel)
+    }
+
+    /// Sets the light that reaches a face of the cube, packed as in [`CubeMesh::light`].
+    pub(crate) fn set_face_light(&mut self, position: vec3u5, face: CubeFace, light: u8) {
+        let cube = &mut self.data[position.linearize()];
+        if cube.material.is_none() {
+            return;
+        }
+
+        let flags = cube.flags;
+        for channel in LightChannel::values() {
+            cube.flags
+                .set_light(channel, face, unpack_light(light, channel));
+        }
+        if cube.flags != flags {
+            self.updated_positions.push(position);
+        }
+    }
+
+    /// Sets the light of every face from the cube in front of it. The light of the cubes in the neighbouring chunks
+    /// is given by `outside`, from the face and the position in the neighbouring chunk.
+    pub(crate) fn refresh_face_light(&mut self, outside: impl Fn(CubeFace, vec3u5) -> u8) {
+        for i in 0..CHUNK_VOLUME {
+            if self.data[i].material.is_none() {
+                continue;
+            }
+
+            let position = vec3u5::delinearize(i);
+            let mut flags = self.data[i].flags;
+            for face in CubeFace::values() {
+                let light = match neighbour(position, face) {
+                    Some(adj_position) => self.light[adj_position.linearize()],
+                    None => outside(face, vec3u5::from((position.try_cast::<i32>().unwrap() + face.normal()) & (CHUNK_LENGTH as i32 - 1))),
+                };
+                for channel in LightChannel::values() {
+                    flags.set_light(channel, face, unpack_light(light, channel));
+                }
+            }
+
+            if flags != self.data[i].flags {
+                self.data[i].flags = flags;
+                self.updated_positions.push(position);
+            }
+        }
+    }
+
     pub fn cull_shared_face(&mut self, other: &CubeMesh) {
         let Some(face) = CubeFace::from_normal(other.position.0 - self.position.0) else {
             return;
@@ -62,12 +138,19 @@ impl CubeMesh {
                     let cube = &mut self.data[position.linearize()];
                     let adj_cube = &other.data[position_adj.linearize()];
 
-                    let cullable_faces = cube.material.cullable_faces(&self.palette);
-                    let adj_cullable_faces = adj_cube.material.cullable_faces(&other.palette);
-                    if cullable_faces.contains(face) && adj_cullable_faces.contains(inverse_face) {
+                    if cube.material.is_none() {
+                        continue;
+                    }
+
+                    if cube
+                        .material
+                        .is_face_hidden(&self.palette, face, adj_cube.material, &other.palette)
+                    {
                         cube.flags.remove_faces(face);
-                        self.updated_positions.push(position);
+                    } else {
+                        cube.flags.insert_faces(face);
                     }
+                    self.updated_positions.push(position);
                 }
             }
         }
@@ -91,24 +174,23 @@ impl CubeMesh {
                     let cube = &mut self.data[position.linearize()];
                     let other_cube = &mut other.data[other_position.linearize()];
 
-                    if !cube
-                        .material
-                        .cullable_faces(&self.palette)
-                        .is_empty()
-                        && !other_cube
+                    if cube.material.is_some() {
+                        let hidden = cube
                             .material
-                            .cullable_faces(&other.palette)
-                            .is_empty()
-                    {
-                        cube.flags
-                            .remove_faces(CubeFaces::from(shared_face));
-                        other_cube
-                            .flags
-                            .remove_faces(CubeFaces::from(other_shared_face));
-
+                            .is_face_hidden(&self.palette, shared_face, other_cube.material, &other.palette);
+                        set_face(&mut cube.flags, shared_face, !hidden);
                         self.updated_positions.push(position);
+                    }
+                    if other_cube.material.is_some() {
+                        let hidden = other_cube
+                            .material
+                            .is_face_hidden(&other.palette, other_shared_face, cube.material, &self.palette);
+                        set_face(&mut other_cube.flags, other_shared_face, !hidden);
                         other.updated_positions.push(other_position);
-                    } else {
+                    }
+
+                    // The chunks can see into each other wherever either side is not opaque.
+                    if !cube.material.is_opaque(&self.palette) || !other_cube.material.is_opaque(&other.palette) {
                         is_exposed = true;
                     }
                 }
@@ -132,29 +214,29 @@ impl CubeMesh {
 
         self.data[i].material = new_material;
 
-        if old_material
-            .cullable_faces(&self.palette)
-            .is_empty()
-            && !new_material
-                .cullable_faces(&self.palette)
-                .is_empty()
-        {
-            let missing = self.data[i].flags.faces().not();
-            self.data[i].flags.set_opaque(CubeFaces::all());
-            self.remove_neighboring_faces(missing, position);
-        }
+        // Only the faces between the cube and its neighbours change. Faces on the chunk boundary are shown until they
+        // are culled against the neighbouring chunk.
+        let mut faces = CubeFaces::none();
+        for face in CubeFace::values() {
+            let Some(adj_position) = neighbour(position, face) else {
+                if new_material.is_some() {
+                    faces += face;
+                }
+                continue;
+            };
+            let adj_material = self.data[adj_position.linearize()].material;
+
+            if !new_material.is_face_hidden(&self.palette, face, adj_material, &self.palette) {
+                faces += face;
+            }
 
-        if !old_material
-            .cullable_faces(&self.palette)
-            .is_empty()
-            && new_material
-                .cullable_faces(&self.palette)
-                .is_empty()
-        {
-            let present = self.data[i].flags.faces();
-            self.data[i].flags.set_opaque(CubeFaces::none());
-            self.add_neighboring_faces(present, position);
+            if adj_material.is_some() {
+                let hidden = adj_material.is_face_hidden(&self.palette, face.inverse(), new_material, &self.palette);
+                set_face(&mut self.data[adj_position.linearize()].flags, face.inverse(), !hidden);
+                self.updated_positions.push(adj_position);
+            }
         }
+        self.data[i].flags.set_faces(faces);
 
         let (x, y, z) = (position.x(), position.y(), position.z());
         if x == 0 || x == 15 && new_material.is_none() {
@@ -205,53 +287,33 @@ impl CubeMesh {
             }
         }
     }
+}
 
-    fn remove_neighboring_faces(&mut self, faces: CubeFaces, position: vec3u5) {
-        faces
-            .iter()
-            .map(|f| (f, f.normal()))
-            .map(|(f, v)| (f, position.try_cast::<i32>().unwrap() + v))
-            .filter_map(|(f, v)| v.try_cast::<u8>().map(|x| (f, x)))
-            .filter_map(|(f, v)| vec3u5::try_from(v).map(|x| (f, x)))
-            .for_each(|(f, v)| {
-                let index = v.linearize();
-                self.updated_positions.push(v);
-
-                if !self.data[index]
-                    .material
-                    .cullable_faces(&self.palette)
-                    .is_empty()
-                {
-                    self.data[position.linearize()]
-                        .flags
-                        .remove_faces(f);
-                }
+/// The position of the neighbouring cube across a face, if it is in the same chunk.
+pub(crate) fn neighbour(position: vec3u5, face: CubeFace) -> Option<vec3u5> {
+    (position.try_cast::<i32>().unwrap() + face.normal())
+        .try_cast::<u8>()
+        .and_then(vec3u5::try_from)
+}
 
-                self.data[index].flags.remove_faces(f.inverse());
-            });
+/// Takes the level of a channel from light that is packed as in [`CubeMesh::light`].
+pub(crate) fn unpack_light(light: u8, channel: LightChannel) -> u8 {
+    match channel {
+        LightChannel::Block => light & 0xF,
+        LightChannel::Sky => light >> 4,
     }
+}
 
-    fn add_neighboring_faces(&mut self, faces: CubeFaces, position: vec3u5) {
-        let position = position.try_cast::<i32>().unwrap();
-
-        let in_chunk = faces.not().iter().filter_map(|a| {
-            (a.normal() + position)
-                .try_cast::<u8>()
-                .map(|v| vec3u5::try_from(v))
-                .flatten()
-                .map(|b| (a, b))
-        });
-
-        for (f, position) in in_chunk {
-            self.updated_positions.push(position);
-            self.data[position.linearize()]
-                .flags
-                .insert_faces(f.inverse());
-        }
+fn set_face(flags: &mut CubeFlags, face: CubeFace, visible: bool) {
+    if visible {
+        flags.insert_faces(face);
+    } else {
+        flags.remove_faces(face);
     }
 }
 
-fn boundary(face: CubeFace) -> Vec3<Range<u8>> {
+/// The positions of the cubes on a side of the chunk.
+pub(crate) fn boundary(face: CubeFace) -> Vec3<Range<u8>> {
     let l = CHUNK_LENGTH as u8;
     match face {
         CubeFace::East => Vec3::new(l - 1..l, 0..l, 0..l),
@@ -0,0 +1,102 @@
+use std::fs::{read_to_string, remove_file};
+use std::path::Path;
+
+use lib::point::CubePt;
+use lib::save::{ChunkCompression, Migration, MigrationKind, Migrations, SaveError};
+use lib::util::DisplayJoined;
+use lib::vector::vec3i;
+use serde::Deserialize;
+use toml::Table;
+use tracing::warn;
+
+use crate::chunk::codec::CubeGrid;
+use crate::chunk::pending::PendingWrites;
+use crate::chunk::region::RegionStore;
+
+/// Every migration that saves are opened with, including the steps that re-encode chunks.
+pub fn migrations() -> Migrations {
+    let mut migrations = Migrations::new();
+    migrations.insert(Migration {
+        kind: MigrationKind::World,
+        from_version: 1,
+        description: "Move chunk files into region files and re-encode chunks in the current chunk format",
+        apply: migrate_chunks,
+    });
+    migrations.insert(Migration {
+        kind: MigrationKind::World,
+        from_version: 2,
+        description: "Move pending writes from pending.toml into a region store, grouped by chunk",
+        apply: migrate_pending_writes,
+    });
+
+    migrations
+}
+
+fn migrate_chunks(world_path: &Path, descriptor: &mut Table) -> Result<(), SaveError> {
+    let compression = match descriptor.get("chunk_compression") {
+        Some(value) => ChunkCompression::deserialize(value.clone()).map_err(|e| SaveError::Migration(e.to_string()))?,
+        None => ChunkCompression::default(),
+    };
+
+    let regions = RegionStore::new(world_path.join("regions"));
+    regions.migrate_chunk_files(world_path)?;
+
+    for position in regions.positions()? {
+        let Some(bytes) = regions.read(position)? else {
+            continue;
+        };
+
+        let grid = match CubeGrid::decode(&bytes) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Leaving unreadable chunk at {} as is: {}", position.0.display_joined(", "), e);
+                continue;
+            }
+        };
+
+        let mut buf = vec![];
+        grid.encode(compression, &mut buf);
+        regions.write(position, &buf)?;
+    }
+    regions.sync()?;
+
+    Ok(())
+}
+
+fn migrate_pending_writes(world_path: &Path, _: &mut Table) -> Result<(), SaveError> {
+    let path = world_path.join("pending.toml");
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let file = match toml::from_str::<PendingFile>(&read_to_string(&path)?) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Leaving unreadable pending writes in {} as is: {}", path.display(), e);
+            return Ok(());
+        }
+    };
+
+    let pending = PendingWrites::new(world_path.join("pending"));
+    pending.extend(
+        file.writes
+            .into_iter()
+            .map(|entry| (CubePt(entry.position), entry.material)),
+    );
+    pending.save()?;
+    remove_file(path)?;
+
+    Ok(())
+}
+
+/// The pending writes of worlds from before they were grouped by chunk, with an entry for each cube.
+#[derive(Deserialize)]
+struct PendingFile {
+    writes: Vec<PendingEntry>,
+}
+
+#[derive(Deserialize)]
+struct PendingEntry {
+    position: vec3i,
+    material: String,
+}
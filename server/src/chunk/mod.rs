@@ -1,4 +1,3 @@
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use lib::point::ChunkPt;
@@ -7,14 +6,21 @@ use parking_lot::RwLock;
 
 use crate::chunk::handle::{ChunkCube, ClientChunkHandle, CubeUpdate};
 use crate::chunk::mesh::CubeMesh;
+use crate::chunk::provider::ChunkProvider;
 
 pub mod codec;
+pub mod compression;
 pub mod cube;
 pub mod handle;
+pub mod light;
 pub mod map;
 pub mod material;
 pub mod mesh;
+pub mod pending;
+pub mod migration;
 pub mod provider;
+pub mod region;
+pub mod registry;
 
 #[derive(Debug)]
 pub struct Chunk {
@@ -66,7 +72,18 @@ impl Chunk {
         });
     }
 
-    pub fn update(&self, _: PathBuf) {
+    pub fn update(&self) {
         self.sync_with_client();
     }
+
+    pub fn save(&self, provider: &ChunkProvider) {
+        let mut mesh = self.mesh.write();
+        if !mesh.is_dirty {
+            return;
+        }
+
+        if provider.write(&mesh) {
+            mesh.is_dirty = false;
+        }
+    }
 }
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+
+use lib::point::{ChunkCubePt, ChunkPt, CubePt};
+use lib::util::DisplayJoined;
+use lib::vector::vec3u5;
+use lib::world::CHUNK_VOLUME;
+use parking_lot::Mutex;
+use tracing::error;
+
+use crate::chunk::mesh::CubeMesh;
+use crate::chunk::region::RegionStore;
+use crate::chunk::registry::MaterialRegistry;
+
+// Pending writes of a chunk (all integers little-endian):
+//
+// | version: u8 | material count: u16 | materials: (length: u16, key) | write count: u16 | writes: (cube: u16, material: u16) |
+//
+// Each write refers to its material by its index, and its cube by its linear index within the chunk.
+
+const FORMAT_VERSION: u8 = 1;
+
+/// Cubes that a generated feature placed outside of the chunk it is anchored in.
+///
+/// Writes are kept until the chunk they belong to is generated or loaded, and are saved with the world so that
+/// features are completed even if the neighbouring chunk is first visited in a later session. They are stored per
+/// chunk in a region store, and saving only rewrites the chunks whose writes changed since the last save.
+#[derive(Debug)]
+pub struct PendingWrites {
+    regions: RegionStore,
+    map: Mutex<PendingMap>,
+}
+
+#[derive(Debug, Default)]
+struct PendingMap {
+    writes: HashMap<ChunkPt, Vec<PendingWrite>>,
+    /// The chunks whose writes were added or taken since the last save.
+    changed: HashSet<ChunkPt>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PendingWrite {
+    pub local: vec3u5,
+    pub material: String,
+}
+
+impl PendingWrites {
+    pub fn new(dir_path: PathBuf) -> Self {
+        Self {
+            regions: RegionStore::new(dir_path),
+            map: Mutex::new(PendingMap::default()),
+        }
+    }
+
+    pub fn open(dir_path: PathBuf) -> Self {
+        let writes = Self::new(dir_path);
+        let positions = match writes.regions.positions() {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Failed to read pending writes: {}", e);
+                return writes;
+            }
+        };
+
+        let mut map = writes.map.lock();
+        for position in positions {
+            match writes.regions.read(position).map(|x| x.and_then(|x| decode(&x))) {
+                Ok(Some(chunk_writes)) => {
+                    map.writes.insert(position, chunk_writes);
+                }
+                Ok(None) => error!("Discarding unreadable pending writes of chunk at {}", position.0.display_joined(", ")),
+                Err(e) => error!("Failed to read pending writes of chunk at {}: {}", position.0.display_joined(", "), e),
+            }
+        }
+        drop(map);
+
+        writes
+    }
+
+    /// Adds a write, unless the cube already has one. Only the first write to a cube would be applied, since writes
+    /// only fill empty cubes.
+    pub fn push(&self, position: CubePt, material: String) {
+        let ChunkCubePt { chunk, local } = position.into();
+        let mut map = self.map.lock();
+
+        let writes = map.writes.entry(chunk).or_default();
+        if writes.iter().any(|x| x.local == local) {
+            return;
+        }
+        writes.push(PendingWrite { local, material });
+        map.changed.insert(chunk);
+    }
+
+    pub fn extend(&self, writes: impl IntoIterator<Item = (CubePt, String)>) {
+        for (position, material) in writes {
+            self.push(position, material);
+        }
+    }
+
+    pub fn take(&self, chunk: ChunkPt) -> Vec<PendingWrite> {
+        let mut map = self.map.lock();
+        let Some(writes) = map.writes.remove(&chunk) else {
+            return vec![];
+        };
+
+        map.changed.insert(chunk);
+        writes
+    }
+
+    pub fn take_where(&self, mut f: impl FnMut(ChunkPt) -> bool) -> Vec<(ChunkPt, Vec<PendingWrite>)> {
+        let mut map = self.map.lock();
+        if map.writes.is_empty() {
+            return vec![];
+        }
+
+        let positions: Vec<_> = map.writes.keys().copied().filter(|&x| f(x)).collect();
+        map.changed.extend(&positions);
+        positions
+            .into_iter()
+            .map(|position| (position, map.writes.remove(&position).unwrap()))
+            .collect()
+    }
+
+    /// Writes the chunks whose writes changed since the last save, and removes those whose writes were all taken.
+    pub fn save(&self) -> io::Result<()> {
+        let changed = {
+            let mut map = self.map.lock();
+            let changed = std::mem::take(&mut map.changed);
+            changed
+                .into_iter()
+                .map(|position| (position, map.writes.get(&position).map(|x| encode(x))))
+                .collect::<Vec<_>>()
+        };
+
+        let result = changed
+            .iter()
+            .try_for_each(|(position, bytes)| match bytes {
+                Some(bytes) => self.regions.write(*position, bytes),
+                None => self.regions.remove(*position),
+            })
+            .and_then(|_| self.regions.sync());
+
+        // The chunks are saved again the next time.
+        if result.is_err() {
+            self.map
+                .lock()
+                .changed
+                .extend(changed.into_iter().map(|(position, _)| position));
+        }
+
+        result
+    }
+}
+
+/// Applies writes to a chunk, only filling cubes that are still empty. Returns whether the chunk was changed.
+pub fn apply(mesh: &mut CubeMesh, writes: Vec<PendingWrite>, materials: &MaterialRegistry) -> bool {
+    let mut changed = false;
+    for write in writes {
+        if mesh.get(write.local).is_some() {
+            continue;
+        }
+
+        let Some(material) = materials.get(write.material.as_str()) else {
+            error!("Dropping pending write of unknown material '{}'", write.material);
+            continue;
+        };
+
+        let id = mesh.palette.insert(material.clone());
+        mesh.set(write.local, Some(id));
+        changed = true;
+    }
+
+    if changed {
+        mesh.is_dirty = true;
+    }
+
+    changed
+}
+
+fn encode(writes: &[PendingWrite]) -> Vec<u8> {
+    let mut materials = vec![];
+    let indices = writes
+        .iter()
+        .map(|write| match materials.iter().position(|&x| x == write.material.as_str()) {
+            Some(index) => index,
+            None => {
+                materials.push(write.material.as_str());
+                materials.len() - 1
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut buf = vec![FORMAT_VERSION];
+    buf.extend((materials.len() as u16).to_le_bytes());
+    for material in materials {
+        buf.extend((material.len() as u16).to_le_bytes());
+        buf.extend(material.bytes());
+    }
+
+    buf.extend((writes.len() as u16).to_le_bytes());
+    for (write, index) in writes.iter().zip(indices) {
+        buf.extend((write.local.linearize() as u16).to_le_bytes());
+        buf.extend((index as u16).to_le_bytes());
+    }
+
+    buf
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<PendingWrite>> {
+    let mut bytes = bytes.iter().copied();
+    if bytes.next()? != FORMAT_VERSION {
+        return None;
+    }
+
+    let material_count = u16::from_le_bytes(bytes.next_chunk().ok()?);
+    let mut materials = vec![];
+    for _ in 0..material_count {
+        let len = u16::from_le_bytes(bytes.next_chunk().ok()?) as usize;
+        let material = String::from_utf8(bytes.by_ref().take(len).collect()).ok()?;
+        if material.len() != len {
+            return None;
+        }
+        materials.push(material);
+    }
+
+    let write_count = u16::from_le_bytes(bytes.next_chunk().ok()?);
+    let mut writes = vec![];
+    for _ in 0..write_count {
+        let cube = u16::from_le_bytes(bytes.next_chunk().ok()?) as usize;
+        let material = materials.get(u16::from_le_bytes(bytes.next_chunk().ok()?) as usize)?;
+        if cube >= CHUNK_VOLUME {
+            return None;
+        }
+
+        writes.push(PendingWrite {
+            local: vec3u5::delinearize(cube),
+            material: material.clone(),
+        });
+    }
+
+    bytes.next().is_none().then_some(writes)
+}
@@ -3,61 +3,125 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use crossbeam_channel::{Receiver, Sender, TryIter, unbounded};
+use lib::collections::Mailbox;
 use lib::point::ChunkPt;
+use lib::save::{ChunkCompression, GeneratorConfig, WorldDescriptor, WorldType};
 use lib::task::THREAD_POOL;
 use lib::util::DisplayJoined;
 use tracing::error;
 
 use crate::chunk::codec::CubeGrid;
-use crate::chunk::material::{Material, Palette};
+use crate::chunk::light;
 use crate::chunk::mesh::CubeMesh;
+use crate::chunk::pending::PendingWrites;
+use crate::chunk::region::RegionStore;
+use crate::chunk::registry::MaterialRegistry;
+use crate::generator::biome::BiomeTable;
+use crate::generator::ore::Ore;
+use crate::generator::stage::GenerationPipeline;
 use crate::generator::{ChunkGenerator, GenerationParams};
 
 #[derive(Debug)]
 pub struct ChunkProvider {
-    pub(crate) dir_path: PathBuf,
     pub(crate) generator: ChunkGenerator,
     pub(crate) reader: ChunkReader,
+    pub(crate) regions: Arc<RegionStore>,
+    pub(crate) pending: Arc<PendingWrites>,
+    pub(crate) materials: Arc<MaterialRegistry>,
+    compression: ChunkCompression,
 }
 
 #[derive(Debug)]
 pub struct ChunkReader {
     tx: Sender<CubeMesh>,
     rx: Receiver<CubeMesh>,
+    failed: Mailbox<ChunkPt>,
 }
 
 impl ChunkProvider {
-    pub fn new(dir_path: PathBuf, seed: i64) -> Self {
+    pub fn new(dir_path: PathBuf, descriptor: &WorldDescriptor, materials: Arc<MaterialRegistry>, biomes: Arc<BiomeTable>) -> Self {
         if !dir_path.exists() {
             std::fs::create_dir_all(&dir_path).unwrap();
         }
 
-        let mut global_palette = Palette::new();
-        global_palette.insert(Arc::new(Material::stone()));
-        global_palette.insert(Arc::new(Material::dirt()));
-        global_palette.insert(Arc::new(Material::grass()));
-        let global_palette = Arc::new(global_palette);
+        let mut config = descriptor.generator.clone();
+        let unknown = config
+            .materials()
+            .find(|&key| !materials.contains(key))
+            .map(str::to_string);
+        if let Some(key) = unknown {
+            error!("World generator config references unknown material '{}', using the default config", key);
+            config = GeneratorConfig::default();
+        }
+
+        let mut world_type = descriptor.world_type.clone();
+        let unknown = world_type
+            .materials()
+            .find(|&key| !materials.contains(key))
+            .map(str::to_string);
+        if let Some(key) = unknown {
+            error!("World type references unknown material '{}', using the default layers", key);
+            world_type = WorldType::flat();
+        }
+
+        let mut ores = Ore::defaults();
+        ores.retain(|ore| {
+            let is_known = materials.contains(ore.material.as_str());
+            if !is_known {
+                error!("Ore references unknown material '{}', leaving it out", ore.material);
+            }
+            is_known
+        });
+
+        let regions = RegionStore::new(dir_path.join("regions"));
+        let pending = Arc::new(PendingWrites::open(dir_path.join("pending")));
 
         Self {
-            dir_path,
-            generator: ChunkGenerator::new(Arc::new(GenerationParams::new(seed, global_palette.clone()))),
+            generator: ChunkGenerator::new(
+                Arc::new(GenerationParams::new(descriptor.seed, descriptor.terrain, config, materials.clone(), biomes).with_ores(ores)),
+                GenerationPipeline::for_world(&world_type, descriptor.terrain),
+                pending.clone(),
+            ),
             reader: ChunkReader::new(),
+            regions: Arc::new(regions),
+            pending,
+            materials,
+            compression: descriptor.chunk_compression,
         }
     }
 
     pub fn request(&self, position: ChunkPt) {
-        let path = self
-            .dir_path
-            .join(&position.0.display_joined(".").to_string());
-
-        if path.is_file() {
-            self.reader.request(path, position);
+        if self.regions.contains(position) {
+            self.reader.request(self.regions.clone(), position);
         } else {
             self.generator.request(position);
         }
     }
 
+    pub fn write(&self, mesh: &CubeMesh) -> bool {
+        let mut buf = vec![];
+        CubeGrid::from_mesh(mesh).encode(self.compression, &mut buf);
+
+        if let Err(e) = self.regions.write(mesh.position, &buf) {
+            error!("Failed to write chunk at {}: {}", mesh.position.0.display_joined(", "), e);
+            return false;
+        }
+
+        true
+    }
+
+    /// Commits the chunks written since the last sync to disk.
+    pub fn sync(&self) {
+        if let Err(e) = self.regions.sync() {
+            error!("Failed to sync chunks to disk: {}", e);
+        }
+    }
+
     pub fn dequeue(&self) -> Chain<TryIter<'_, CubeMesh>, TryIter<'_, CubeMesh>> {
+        for position in &self.reader.failed {
+            self.generator.request(position);
+        }
+
         Iterator::chain(self.reader.rx.try_iter(), self.generator.dequeue())
     }
 }
@@ -66,21 +130,45 @@ impl ChunkReader {
     pub fn new() -> Self {
         let (tx, rx) = unbounded();
 
-        Self { tx, rx }
+        Self {
+            tx,
+            rx,
+            failed: Mailbox::default(),
+        }
     }
 
-    pub fn request(&self, path: PathBuf, position: ChunkPt) {
+    pub fn request(&self, regions: Arc<RegionStore>, position: ChunkPt) {
         let tx = self.tx.clone();
+        let failed = self.failed.sender();
 
         THREAD_POOL.spawn(move || {
             let bytes;
-            match std::fs::read(path) {
-                Ok(x) => bytes = x,
-                Err(e) => return error!("Failed to read chunk file: {}", e),
+            match regions.read(position) {
+                Ok(Some(x)) => bytes = x,
+                Ok(None) => {
+                    let _ = failed.send(position);
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to read chunk at {}: {}", position.0.display_joined(", "), e);
+                    let _ = failed.send(position);
+                    return;
+                }
             }
-            let material_mesh = CubeGrid::decode(&bytes);
 
-            tx.send(material_mesh.to_mesh(position)).unwrap();
+            match CubeGrid::decode(&bytes) {
+                Ok(grid) => {
+                    // Light is not stored, so the chunk is lit here rather than on the game thread, as open to the sky
+                    // until it is loaded under another chunk.
+                    let mut mesh = grid.to_mesh(position);
+                    light::light_chunk(&mut mesh, |_, _| true);
+                    let _ = tx.send(mesh);
+                }
+                Err(e) => {
+                    error!("Failed to decode chunk at {}; regenerating it: {}", position.0.display_joined(", "), e);
+                    let _ = failed.send(position);
+                }
+            }
         });
     }
 }
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::{File, OpenOptions, create_dir_all, read_dir, remove_file};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use lib::fs::write_atomic;
+use lib::point::ChunkPt;
+use lib::util::DisplayJoined;
+use lib::vector::{Vec3, vec3i};
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+// Region file layout (all integers little-endian):
+//
+// | magic "HBRG" | version: u16 | reserved: u16 | table: REGION_VOLUME x (sector offset: u32, byte length: u32) | sectors... |
+//
+// The header is padded to a whole number of sectors. Each chunk payload occupies a contiguous run of sectors, and a
+// table entry with a length of zero marks a chunk that has not been written yet. A rewritten chunk is always written
+// to free sectors, and the table entries of the chunks written since the last sync are only updated once their
+// payloads are synced to disk, so an interrupted write never clobbers the previous payload. Removed chunks have their
+// table entries cleared in the same way. New region files are created atomically with an empty table.
+
+pub const REGION_LENGTH: i32 = 16;
+pub const REGION_VOLUME: usize = (REGION_LENGTH * REGION_LENGTH * REGION_LENGTH) as usize;
+
+const MAGIC: [u8; 4] = *b"HBRG";
+const FORMAT_VERSION: u16 = 1;
+const SECTOR_SIZE: u64 = 512;
+const ENTRY_SIZE: u64 = 8;
+const TABLE_OFFSET: u64 = 8;
+const HEADER_SECTORS: u32 = (TABLE_OFFSET + REGION_VOLUME as u64 * ENTRY_SIZE).div_ceil(SECTOR_SIZE) as u32;
+
+#[derive(Debug)]
+pub struct RegionStore {
+    dir_path: PathBuf,
+    regions: Mutex<HashMap<vec3i, Arc<Mutex<Region>>>>,
+}
+
+impl RegionStore {
+    pub fn new(dir_path: PathBuf) -> Self {
+        Self {
+            dir_path,
+            regions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn contains(&self, position: ChunkPt) -> bool {
+        let (region_position, index) = locate(position);
+
+        match self.get(region_position, false) {
+            Ok(Some(region)) => region.lock().table[index].len != 0,
+            Ok(None) => false,
+            Err(e) => {
+                warn!("Failed to open region {}: {}", region_position.display_joined(", "), e);
+                false
+            }
+        }
+    }
+
+    pub fn read(&self, position: ChunkPt) -> io::Result<Option<Vec<u8>>> {
+        let (region_position, index) = locate(position);
+
+        match self.get(region_position, false)? {
+            Some(region) => region.lock().read(index),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes a chunk, which is read back from then on but only kept on disk once the store is synced.
+    pub fn write(&self, position: ChunkPt, bytes: &[u8]) -> io::Result<()> {
+        let (region_position, index) = locate(position);
+
+        let region = self.get(region_position, true)?.unwrap();
+        region.lock().write(index, bytes)
+    }
+
+    /// Removes a chunk, which is no longer read back from then on but only removed on disk once the store is synced.
+    pub fn remove(&self, position: ChunkPt) -> io::Result<()> {
+        let (region_position, index) = locate(position);
+
+        if let Some(region) = self.get(region_position, false)? {
+            region.lock().remove(index);
+        }
+        Ok(())
+    }
+
+    /// Commits the chunks written since the last sync to disk, with two syncs for each region that was written to.
+    pub fn sync(&self) -> io::Result<()> {
+        let regions = self
+            .regions
+            .lock()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        for region in regions {
+            if let Err(e) = region.lock().sync() {
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    pub fn positions(&self) -> io::Result<Vec<ChunkPt>> {
+        let mut positions = vec![];
+        if !self.dir_path.is_dir() {
+            return Ok(positions);
+        }
+
+        for entry in read_dir(&self.dir_path)? {
+            let Some(region_position) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(".region"))
+                .and_then(parse_position)
+            else {
+                continue;
+            };
+
+            let region = self.get(region_position, false)?.unwrap();
+            let region = region.lock();
+            for (index, entry) in region.table.iter().enumerate() {
+                if entry.len != 0 {
+                    positions.push(ChunkPt(region_position * REGION_LENGTH + unlocate(index)));
+                }
+            }
+        }
+
+        Ok(positions)
+    }
+
+    pub fn migrate_chunk_files(&self, dir_path: &Path) -> io::Result<usize> {
+        let mut migrated = vec![];
+
+        for entry in read_dir(dir_path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let Some(position) = entry
+                .file_name()
+                .to_str()
+                .and_then(parse_position)
+                .map(ChunkPt)
+            else {
+                continue;
+            };
+
+            let bytes = std::fs::read(entry.path())?;
+            self.write(position, &bytes)?;
+            migrated.push(entry.path());
+        }
+
+        // The chunk files are only removed once the regions hold their chunks.
+        self.sync()?;
+        for path in &migrated {
+            remove_file(path)?;
+        }
+        let count = migrated.len();
+
+        if count != 0 {
+            info!("Migrated {count} chunk files in {} into region files", dir_path.display());
+        }
+
+        Ok(count)
+    }
+
+    fn get(&self, region_position: vec3i, create: bool) -> io::Result<Option<Arc<Mutex<Region>>>> {
+        let mut regions = self.regions.lock();
+        if let Some(region) = regions.get(&region_position) {
+            return Ok(Some(region.clone()));
+        }
+
+        let path = self
+            .dir_path
+            .join(format!("{}.region", region_position.display_joined(".")));
+        if !create && !path.is_file() {
+            return Ok(None);
+        }
+
+        create_dir_all(&self.dir_path)?;
+
+        let region = Arc::new(Mutex::new(Region::open(&path)?));
+        regions.insert(region_position, region.clone());
+
+        Ok(Some(region))
+    }
+}
+
+#[derive(Debug)]
+struct Region {
+    file: File,
+    table: Box<[RegionEntry; REGION_VOLUME]>,
+    used_sectors: Vec<bool>,
+    /// The entries on disk of the chunks written since the last sync, whose sectors are freed once it is done.
+    unsynced: HashMap<usize, RegionEntry>,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct RegionEntry {
+    offset: u32,
+    len: u32,
+}
+
+impl RegionEntry {
+    fn sectors(&self) -> u32 {
+        sectors_for(self.len as usize)
+    }
+}
+
+impl Region {
+    fn open(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Self::create(path);
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        let file_len = file.metadata()?.len();
+        let mut header = vec![0; (HEADER_SECTORS as u64 * SECTOR_SIZE) as usize];
+        file.read_exact(&mut header)?;
+
+        if header[0..4] != MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "region file does not start with the region magic number"));
+        }
+
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("unsupported region format version {version}")));
+        }
+
+        let total_sectors = file_len.div_ceil(SECTOR_SIZE) as usize;
+        let mut used_sectors = vec![false; total_sectors];
+        used_sectors[..HEADER_SECTORS as usize].fill(true);
+
+        let mut table = Box::new([RegionEntry::default(); REGION_VOLUME]);
+        for (index, entry) in table.iter_mut().enumerate() {
+            let start = (TABLE_OFFSET + index as u64 * ENTRY_SIZE) as usize;
+            let bytes = &header[start..start + ENTRY_SIZE as usize];
+
+            let candidate = RegionEntry {
+                offset: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+                len: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            };
+            if candidate.len == 0 {
+                continue;
+            }
+
+            let sectors = candidate.offset as usize..(candidate.offset + candidate.sectors()) as usize;
+            if sectors.start < HEADER_SECTORS as usize || sectors.end > total_sectors || used_sectors[sectors.clone()].contains(&true) {
+                warn!("Discarding invalid entry {index} in region file {}", path.display());
+                continue;
+            }
+
+            used_sectors[sectors].fill(true);
+            *entry = candidate;
+        }
+
+        Ok(Self {
+            file,
+            table,
+            used_sectors,
+            unsynced: HashMap::new(),
+        })
+    }
+
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut header = vec![0; (HEADER_SECTORS as u64 * SECTOR_SIZE) as usize];
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4..6].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+        write_atomic(path, &header)?;
+
+        Ok(Self {
+            file: OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)?,
+            table: Box::new([RegionEntry::default(); REGION_VOLUME]),
+            used_sectors: vec![true; HEADER_SECTORS as usize],
+            unsynced: HashMap::new(),
+        })
+    }
+
+    fn read(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let entry = self.table[index];
+        if entry.len == 0 {
+            return Ok(None);
+        }
+
+        let mut bytes = vec![0; entry.len as usize];
+        self.file
+            .seek(SeekFrom::Start(entry.offset as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(Some(bytes))
+    }
+
+    fn write(&mut self, index: usize, bytes: &[u8]) -> io::Result<()> {
+        let new_entry = RegionEntry {
+            offset: self.allocate(sectors_for(bytes.len())),
+            len: bytes.len() as u32,
+        };
+
+        let mut padded = bytes.to_vec();
+        padded.resize((new_entry.sectors() as u64 * SECTOR_SIZE) as usize, 0);
+
+        self.file
+            .seek(SeekFrom::Start(new_entry.offset as u64 * SECTOR_SIZE))?;
+        if let Err(e) = self.file.write_all(&padded) {
+            self.free(new_entry);
+            return Err(e);
+        }
+
+        self.replace(index, new_entry);
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) {
+        if self.table[index].len != 0 {
+            self.replace(index, RegionEntry::default());
+        }
+    }
+
+    fn replace(&mut self, index: usize, new_entry: RegionEntry) {
+        let old_entry = std::mem::replace(&mut self.table[index], new_entry);
+        match self.unsynced.entry(index) {
+            // The replaced payload was never referenced on disk, so its sectors can be reused right away.
+            Entry::Occupied(_) => self.free(old_entry),
+            Entry::Vacant(entry) => {
+                entry.insert(old_entry);
+            }
+        }
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced.is_empty() {
+            return Ok(());
+        }
+
+        self.file.sync_data()?;
+        for &index in self.unsynced.keys() {
+            let entry = self.table[index];
+            self.file
+                .seek(SeekFrom::Start(TABLE_OFFSET + index as u64 * ENTRY_SIZE))?;
+            self.file.write_all(&entry.offset.to_le_bytes())?;
+            self.file.write_all(&entry.len.to_le_bytes())?;
+        }
+        self.file.sync_data()?;
+
+        for (_, old_entry) in std::mem::take(&mut self.unsynced) {
+            self.free(old_entry);
+        }
+
+        Ok(())
+    }
+
+    fn free(&mut self, entry: RegionEntry) {
+        if entry.len != 0 {
+            self.used_sectors[entry.offset as usize..(entry.offset + entry.sectors()) as usize].fill(false);
+        }
+    }
+
+    fn allocate(&mut self, sectors: u32) -> u32 {
+        let sectors = sectors as usize;
+
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (i, &used) in self.used_sectors.iter().enumerate() {
+            if used {
+                run_len = 0;
+                continue;
+            }
+
+            if run_len == 0 {
+                run_start = i;
+            }
+            run_len += 1;
+
+            if run_len == sectors {
+                self.used_sectors[run_start..run_start + sectors].fill(true);
+                return run_start as u32;
+            }
+        }
+
+        // Extend the trailing free run (if any) to the end of the file.
+        let start = if run_len != 0 && run_start + run_len == self.used_sectors.len() {
+            run_start
+        } else {
+            self.used_sectors.len()
+        };
+
+        self.used_sectors.resize(start + sectors, true);
+        self.used_sectors[start..].fill(true);
+
+        start as u32
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            warn!("Failed to sync region file: {e}");
+        }
+    }
+}
+
+fn sectors_for(len: usize) -> u32 {
+    (len as u64).div_ceil(SECTOR_SIZE) as u32
+}
+
+fn locate(position: ChunkPt) -> (vec3i, usize) {
+    let region_position = position.0.div_euclid_each(REGION_LENGTH);
+    let Vec3 { x, y, z } = position.0.rem_euclid_each(REGION_LENGTH);
+
+    (region_position, (x * REGION_LENGTH * REGION_LENGTH + z * REGION_LENGTH + y) as usize)
+}
+
+fn unlocate(index: usize) -> vec3i {
+    let index = index as i32;
+
+    Vec3::new(index / (REGION_LENGTH * REGION_LENGTH), index % REGION_LENGTH, index / REGION_LENGTH % REGION_LENGTH)
+}
+
+fn parse_position(name: &str) -> Option<vec3i> {
+    let mut components = name.split('.').map(|x| x.parse::<i32>().ok());
+    let position = Vec3::new(components.next()??, components.next()??, components.next()??);
+
+    components
+        .next()
+        .is_none()
+        .then_some(position)
+}
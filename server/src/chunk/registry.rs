@@ -0,0 +1,279 @@
+use std::fs::{read_dir, read_to_string};
+use std::hash::Hash;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use hashbrown::{Equivalent, HashMap};
+use lib::color::Rgba;
+use lib::spatial::CubeFaces;
+use lib::util::GroupKeyBuf;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::chunk::light::MAX_LIGHT;
+use crate::chunk::material::{Material, MaterialPhysics, Palette, Texture, Transparency};
+
+const DEFAULT_GROUP: &str = "herbolution";
+/// The key of the material that players place until they choose another.
+const DEFAULT_MATERIAL: &str = "stone";
+
+/// Every material that can be placed in a world, keyed by group key.
+///
+/// Materials are loaded from the TOML files of a directory, where the file stem is the key of the material. Later
+/// directories, such as the materials of a save, override materials with the same group key.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialRegistry {
+    vec: Vec<Arc<Material>>,
+    indices: HashMap<GroupKeyBuf, usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDefinition {
+    #[serde(default = "default_group")]
+    group: String,
+    #[serde(default = "default_true")]
+    has_collider: bool,
+    colors: Vec<[f32; 3]>,
+    images: Option<ImageDefinition>,
+    #[serde(default)]
+    transparency: Transparency,
+    #[serde(default = "default_alpha")]
+    alpha: f32,
+    #[serde(default)]
+    light: u8,
+    toughness: f32,
+    #[serde(default)]
+    physics: MaterialPhysics,
+}
+
+/// The block atlas images of a material. The top and bottom of the cube show the side image unless set.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImageDefinition {
+    side: String,
+    top: Option<String>,
+    bottom: Option<String>,
+}
+
+impl MaterialRegistry {
+    pub fn load(dir_path: &Path) -> Result<Self, MaterialError> {
+        let mut registry = Self::default();
+        registry.load_dir(dir_path)?;
+
+        Ok(registry)
+    }
+
+    /// Loads the materials of a directory over the existing ones. A directory that does not exist has no overrides.
+    pub fn load_overrides(&mut self, dir_path: &Path) -> Result<(), MaterialError> {
+        if !dir_path.exists() {
+            return Ok(());
+        }
+
+        self.load_dir(dir_path)
+    }
+
+    fn load_dir(&mut self, dir_path: &Path) -> Result<(), MaterialError> {
+        let io_error = |source| MaterialError::Io {
+            path: dir_path.to_path_buf(),
+            source,
+        };
+
+        let mut paths = read_dir(dir_path)
+            .map_err(io_error)?
+            .map(|entry| entry.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(io_error)?;
+        paths.retain(|path| path.extension().is_some_and(|x| x == "toml"));
+        paths.sort();
+
+        for path in paths {
+            let material = read_material(&path)?;
+            self.insert(material);
+        }
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, material: Material) {
+        match self.indices.get(&material.group_key) {
+            Some(&i) => self.vec[i] = Arc::new(material),
+            None => {
+                self.indices
+                    .insert(material.group_key.clone(), self.vec.len());
+                self.vec.push(Arc::new(material));
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&Arc<Material>>
+    where
+        Q: Hash + Equivalent<GroupKeyBuf> + ?Sized,
+    {
+        self.indices.get(key).map(|&i| &self.vec[i])
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<GroupKeyBuf> + ?Sized,
+    {
+        self.indices.contains_key(key)
+    }
+
+    /// Returns the material that players place until they choose another, or the first solid material if the
+    /// default one was not loaded.
+    pub fn default_material(&self) -> Option<&Arc<Material>> {
+        self.get(&GroupKeyBuf::new(DEFAULT_GROUP, DEFAULT_MATERIAL))
+            .or_else(|| self.vec.iter().find(|x| x.has_collider))
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Material>> {
+        self.vec.iter()
+    }
+
+    /// Creates a palette that contains every material, in the order they were loaded.
+    pub fn to_palette(&self) -> Palette {
+        let mut palette = Palette::new();
+        for material in &self.vec {
+            palette.insert(material.clone());
+        }
+
+        palette
+    }
+}
+
+fn read_material(path: &Path) -> Result<Material, MaterialError> {
+    let contents = read_to_string(path).map_err(|source| MaterialError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let definition: MaterialDefinition = toml::from_str(&contents).map_err(|source| MaterialError::TomlDe {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let invalid = |field, message: &str| MaterialError::Invalid {
+        path: path.to_path_buf(),
+        field,
+        message: message.to_string(),
+    };
+
+    let key = path
+        .file_stem()
+        .unwrap()
+        .to_string_lossy();
+    if !is_valid_key(&key) {
+        return Err(invalid("file name", "must only contain lowercase letters, digits and underscores"));
+    }
+    if !is_valid_key(&definition.group) {
+        return Err(invalid("group", "must only contain lowercase letters, digits and underscores"));
+    }
+    if definition.colors.is_empty() {
+        return Err(invalid("colors", "must contain at least one color"));
+    }
+    if definition
+        .colors
+        .iter()
+        .flatten()
+        .any(|x| !(0.0..=1.0).contains(x))
+    {
+        return Err(invalid("colors", "must only contain components between 0 and 1"));
+    }
+    if !(0.0..=1.0).contains(&definition.alpha) {
+        return Err(invalid("alpha", "must be between 0 and 1"));
+    }
+    if definition.alpha < 1.0 && definition.transparency != Transparency::Translucent {
+        return Err(invalid("alpha", "must be 1 unless the material is translucent"));
+    }
+    if definition.light > MAX_LIGHT {
+        return Err(invalid("light", "must be at most 15"));
+    }
+    if let Some(images) = &definition.images
+        && [Some(&images.side), images.top.as_ref(), images.bottom.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|x| !is_valid_key(x))
+    {
+        return Err(invalid("images", "must only name images with lowercase letters, digits and underscores"));
+    }
+    if !definition.toughness.is_finite() || definition.toughness < 0.0 {
+        return Err(invalid("toughness", "must be a non-negative number"));
+    }
+    let physics = &definition.physics;
+    if !physics.friction.is_finite() || physics.friction < 0.0 {
+        return Err(invalid("physics.friction", "must be a non-negative number"));
+    }
+    if !(0.0..=1.0).contains(&physics.bounciness) {
+        return Err(invalid("physics.bounciness", "must be between 0 and 1"));
+    }
+    if !physics.speed_multiplier.is_finite() || physics.speed_multiplier <= 0.0 {
+        return Err(invalid("physics.speed_multiplier", "must be a positive number"));
+    }
+
+    Ok(Material {
+        group_key: GroupKeyBuf::new(&definition.group, &key),
+        has_collider: definition.has_collider,
+        // Only opaque cubes hide the faces of their neighbours.
+        cullable_faces: if definition.transparency.is_opaque() { CubeFaces::all() } else { CubeFaces::none() },
+        texture: texture(definition.colors, definition.alpha, definition.images),
+        transparency: definition.transparency,
+        light: definition.light,
+        toughness: definition.toughness,
+        physics: definition.physics,
+    })
+}
+
+fn texture(colors: Vec<[f32; 3]>, alpha: f32, images: Option<ImageDefinition>) -> Texture {
+    let colors = colors
+        .into_iter()
+        .map(|[r, g, b]| Rgba::new(r, g, b, alpha))
+        .collect();
+
+    match images {
+        None => Texture::Colors { vec: colors },
+        Some(ImageDefinition { side, top, bottom }) => Texture::Image {
+            top: top.unwrap_or_else(|| side.clone()),
+            bottom: bottom.unwrap_or_else(|| side.clone()),
+            side,
+            colors,
+        },
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn default_group() -> String {
+    DEFAULT_GROUP.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_alpha() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Error)]
+pub enum MaterialError {
+    #[error("Failed to read material definitions from {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid material definition {}: {source}", path.display())]
+    TomlDe { path: PathBuf, source: toml::de::Error },
+    #[error("Invalid material definition {}: field `{field}` {message}", path.display())]
+    Invalid { path: PathBuf, field: &'static str, message: String },
+}
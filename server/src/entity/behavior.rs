@@ -110,9 +110,17 @@ impl EntityBehaviors {
         self
     }
 
+    pub fn contains<T: EntityBehavior>(&self) -> bool {
+        self.indices.contains_key(&TypeId::of::<T>())
+    }
+
     pub fn get_mut<T: EntityBehavior>(&mut self) -> &mut T {
-        let index = *self.indices.get(&TypeId::of::<T>()).unwrap();
+        self.try_get_mut().unwrap()
+    }
+
+    pub fn try_get_mut<T: EntityBehavior>(&mut self) -> Option<&mut T> {
+        let index = *self.indices.get(&TypeId::of::<T>())?;
 
-        T::select_from(&mut self.vec[index]).unwrap()
+        T::select_from(&mut self.vec[index])
     }
 }
@@ -1,4 +1,5 @@
 use lib::aabb::Aabb3;
+use lib::point::{ChunkCubePt, CubePt};
 use lib::rotation::Euler;
 use lib::size::size3f;
 use lib::vector::{vec3d, vec3f, Vec3};
@@ -7,11 +8,17 @@ use std::ops::Add;
 use time::Duration;
 
 use crate::chunk::map::ChunkMap;
+use crate::chunk::material::MaterialPhysics;
 
 const GRAVITY: f64 = 64.0;
 const JUMP_FORCE: f64 = 12.0;
 const GROUND_FRICTION: f64 = 11.0;
 const AIR_FRICTION: f64 = 2.0;
+const CLIMB_SPEED: f64 = 4.0;
+/// The slowest landing that bouncy materials bounce back from, so that bodies come to rest on them.
+const MIN_BOUNCE_SPEED: f64 = 2.0;
+/// How far below the body the ground is looked for, since resting bodies touch it without overlapping it.
+const GROUND_DEPTH: f64 = 0.01;
 
 #[derive(Debug, Clone)]
 pub struct EntityBody {
@@ -22,6 +29,10 @@ pub struct EntityBody {
     pub(crate) motion: vec3f,
     is_on_ground: bool,
     near_colliders: Vec<Aabb3<f64>>,
+    /// The physics of the material under the body, which is ordinary ground if there is none.
+    ground: MaterialPhysics,
+    /// The physics of the materials that the body is inside of.
+    surroundings: Surroundings,
     pub attrs: EntityAttrs,
     fall: f32,
     pub(crate) last_fell: Option<f32>,
@@ -45,12 +56,28 @@ impl EntityBody {
             is_on_ground: false,
             attrs,
             near_colliders: vec![],
+            ground: MaterialPhysics::default(),
+            surroundings: Surroundings::default(),
             fall: 0.0,
             last_fell: None,
         }
     }
 
+    pub fn teleport(&mut self, position: vec3d) {
+        self.position = position;
+        self.velocity = Vec3::ZERO;
+        self.is_on_ground = false;
+        self.fall = 0.0;
+        self.last_fell = None;
+    }
+
     pub fn update(&mut self, chunk_map: &mut ChunkMap, dt: Duration) {
+        // The body waits for the chunk it is in to be loaded, rather than falling through ground that is not there yet.
+        let ChunkCubePt { chunk, .. } = CubePt(self.position.floor().cast()).into();
+        if chunk_map.get_chunk(chunk).is_none() {
+            return;
+        }
+
         let dt_secs = dt.as_seconds_f64();
 
         self.apply_physics_and_collision(chunk_map, dt_secs);
@@ -61,6 +88,11 @@ impl EntityBody {
 
         if self.attrs.has_gravity {
             self.velocity.y -= GRAVITY * dt_secs;
+
+            // Climbing goes up while jumping and otherwise slides slowly down.
+            if self.surroundings.is_climbable {
+                self.velocity.y = if self.motion.y > 0.0 { CLIMB_SPEED } else { self.velocity.y.max(-CLIMB_SPEED) };
+            }
         }
 
         self.apply_friction(dt_secs);
@@ -81,22 +113,28 @@ impl EntityBody {
             .add(perpendicular.cast() * self.motion.z as f64)
             .normalize();
 
-        let mut speed = self.attrs.acceleration_rate;
+        let speed_multiplier = self.speed_multiplier();
+        let mut speed = self.attrs.acceleration_rate * speed_multiplier;
         if self.is_on_ground || !self.attrs.has_gravity {
             speed *= 3.0;
         }
+        // Grip scales with friction, so slippery ground is as fast to walk on but slow to speed up and stop on.
+        if self.is_on_ground {
+            speed *= self.ground.friction as f64;
+        }
 
         self.velocity.x += direction.x * speed * dt_secs;
         self.velocity.z += direction.z * speed * dt_secs;
 
+        let terminal_velocity = self.attrs.terminal_velocity * speed_multiplier;
         self.velocity.x = self
             .velocity
             .x
-            .clamp(-self.attrs.terminal_velocity, self.attrs.terminal_velocity);
+            .clamp(-terminal_velocity, terminal_velocity);
         self.velocity.z = self
             .velocity
             .z
-            .clamp(-self.attrs.terminal_velocity, self.attrs.terminal_velocity);
+            .clamp(-terminal_velocity, terminal_velocity);
 
         if self.is_on_ground || !self.attrs.has_gravity {
             self.velocity.y = JUMP_FORCE * self.motion.y as f64;
@@ -110,7 +148,7 @@ impl EntityBody {
             return;
         }
 
-        let friction = if self.is_on_ground { GROUND_FRICTION } else { AIR_FRICTION };
+        let friction = if self.is_on_ground { GROUND_FRICTION * self.ground.friction as f64 } else { AIR_FRICTION };
         let friction_step = (-friction * dt_secs).exp();
 
         self.velocity.x *= friction_step;
@@ -136,10 +174,62 @@ impl EntityBody {
         for collider in &self.near_colliders {
             clipped_step.z = collider.clip_dz_collision(&bounds, clipped_step.z);
         }
+        bounds.add_z(clipped_step.z);
+
+        self.sense_materials(chunk_map, bounds);
 
         clipped_step
     }
 
+    /// Looks up the materials under and around the body once it has moved within the bounds.
+    fn sense_materials(&mut self, chunk_map: &ChunkMap, bounds: Aabb3<f64>) {
+        // The ground is the cube that carries most of the body's footprint, so that a body standing over an edge or
+        // a gap still feels the cube it rests on.
+        self.ground = MaterialPhysics::default();
+        let ground_y = (bounds.min.y - GROUND_DEPTH).floor() as i32;
+        let mut ground_area = 0.0;
+        for x in bounds.min.x.floor() as i32..bounds.max.x.ceil() as i32 {
+            for z in bounds.min.z.floor() as i32..bounds.max.z.ceil() as i32 {
+                let Some(material) = chunk_map.get_material(Vec3::new(x, ground_y, z)) else { continue };
+                if !material.has_collider {
+                    continue;
+                }
+
+                let area = footprint_overlap(bounds.min.x, bounds.max.x, x) * footprint_overlap(bounds.min.z, bounds.max.z, z);
+                if area > ground_area {
+                    ground_area = area;
+                    self.ground = material.physics;
+                }
+            }
+        }
+
+        self.surroundings = Surroundings::default();
+        let min = bounds.min.floor().cast::<i32>();
+        let max = bounds.max.ceil().cast::<i32>();
+        for x in min.x..max.x {
+            for y in min.y..max.y {
+                for z in min.z..max.z {
+                    let Some(material) = chunk_map.get_material(Vec3::new(x, y, z)) else { continue };
+                    if material.has_collider {
+                        continue;
+                    }
+
+                    self.surroundings.speed_multiplier = self
+                        .surroundings
+                        .speed_multiplier
+                        .min(material.physics.speed_multiplier);
+                    self.surroundings.is_climbable |= material.physics.is_climbable;
+                }
+            }
+        }
+    }
+
+    /// How fast the body moves on the ground it stands on and within what it is inside of.
+    fn speed_multiplier(&self) -> f64 {
+        let ground = if self.is_on_ground { self.ground.speed_multiplier } else { 1.0 };
+        ground.min(self.surroundings.speed_multiplier) as f64
+    }
+
     fn update_state_from_step(&mut self, step: vec3d, clipped_step: vec3d) {
         self.is_on_ground = (step.y < 0.0) && (clipped_step.y != step.y);
 
@@ -152,7 +242,13 @@ impl EntityBody {
             self.velocity.x = 0.0;
         }
         if clipped_step.y != step.y {
-            self.velocity.y = 0.0;
+            let bounce = -self.velocity.y * self.ground.bounciness as f64;
+            if self.is_on_ground && bounce > MIN_BOUNCE_SPEED {
+                self.velocity.y = bounce;
+                self.is_on_ground = false;
+            } else {
+                self.velocity.y = 0.0;
+            }
         }
         if clipped_step.z != step.z {
             self.velocity.z = 0.0;
@@ -171,6 +267,14 @@ impl EntityBody {
         self.position
     }
 
+    pub fn velocity(&self) -> vec3d {
+        self.velocity
+    }
+
+    pub fn set_motion(&mut self, motion: vec3f) {
+        self.motion = motion;
+    }
+
     pub fn rotation(&self) -> &Euler<f32> {
         &self.rotation
     }
@@ -186,6 +290,29 @@ impl EntityBody {
     }
 }
 
+/// How much of the span from `min` to `max` lies within the cube at `cube`, along a single axis.
+fn footprint_overlap(min: f64, max: f64, cube: i32) -> f64 {
+    (max.min(cube as f64 + 1.0) - min.max(cube as f64)).max(0.0)
+}
+
+/// The physics of the materials without colliders that a body is inside of, such as water or ladders.
+#[derive(Debug, Copy, Clone)]
+struct Surroundings {
+    /// The slowest speed multiplier of the materials.
+    speed_multiplier: f32,
+    /// Whether any of the materials can be climbed.
+    is_climbable: bool,
+}
+
+impl Default for Surroundings {
+    fn default() -> Self {
+        Self {
+            speed_multiplier: 1.0,
+            is_climbable: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Bounds {
     pub size: size3f,
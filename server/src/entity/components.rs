@@ -5,31 +5,40 @@ use lib::point::ChunkPt;
 use lib::vector::vec3i;
 use lib::world::CHUNK_LENGTH;
 
+use crate::chunk::map::ChunkMap;
 use crate::entity::behavior::{EntityBehavior, EntityBehaviorType, EntityContext};
 
 #[derive(Debug)]
 pub struct ChunkLoader {
-    pub(crate) prev_chunk_position: ChunkPt,
+    pub(crate) prev_chunk_position: Option<ChunkPt>,
     radial_chunk_positions: HashSet<ChunkPt>,
 }
 
 impl ChunkLoader {
     pub fn new() -> Self {
         Self {
-            prev_chunk_position: ChunkPt::ZERO,
+            prev_chunk_position: None,
             radial_chunk_positions: HashSet::new(),
         }
     }
+
+    pub fn release(&mut self, chunk_map: &ChunkMap) {
+        for position in self.radial_chunk_positions.drain() {
+            chunk_map.queue_unload(position);
+        }
+
+        self.prev_chunk_position = None;
+    }
 }
 
 impl EntityBehavior for ChunkLoader {
     fn update(&mut self, ctx: &mut EntityContext<'_>) {
         let chunk_position = ChunkPt(ctx.entity.body.position().cast() / CHUNK_LENGTH as i32);
-        if chunk_position == self.prev_chunk_position {
+        if self.prev_chunk_position == Some(chunk_position) {
             return;
         }
 
-        self.prev_chunk_position = chunk_position;
+        self.prev_chunk_position = Some(chunk_position);
 
         let new_positions = fill_rhombus(chunk_position, 16);
 
@@ -22,6 +22,10 @@ impl EntitySet {
         EntityId(self.arena.insert(entity))
     }
 
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        self.arena.remove(id.0)
+    }
+
     pub fn update(&mut self, handle: &ClientHandle, chunk_map: &mut ChunkMap, dt: Duration) {
         for (_, entity) in self.arena.iter_mut() {
             entity.update(chunk_map, handle, dt);
@@ -0,0 +1,199 @@
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::chunk::registry::MaterialRegistry;
+
+const BLEND_DISTANCE: f32 = 0.15;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Biome {
+    #[serde(skip)]
+    pub name: String,
+    pub temperature: f32,
+    pub humidity: f32,
+    pub height_offset: f32,
+    pub height_scale: f32,
+    pub surface: String,
+    pub subsurface: String,
+    pub subsurface_depth: u32,
+    #[serde(default)]
+    pub tree_density: f32,
+    #[serde(default)]
+    pub boulder_density: f32,
+}
+
+impl Biome {
+    pub fn plains() -> Self {
+        Self {
+            name: "plains".to_string(),
+            temperature: 0.0,
+            humidity: 0.0,
+            height_offset: 0.0,
+            height_scale: 32.0,
+            surface: "herbolution:grass".to_string(),
+            subsurface: "herbolution:dirt".to_string(),
+            subsurface_depth: 5,
+            tree_density: 0.5,
+            boulder_density: 0.1,
+        }
+    }
+
+    fn climate_distance(&self, temperature: f32, humidity: f32) -> f32 {
+        ((self.temperature - temperature).powi(2) + (self.humidity - humidity).powi(2)).sqrt()
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BiomeId(u8);
+
+impl BiomeId {
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BiomeTable {
+    vec: Vec<Biome>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BiomeBlend {
+    pub dominant: BiomeId,
+    pub height_offset: f32,
+    pub height_scale: f32,
+}
+
+impl BiomeTable {
+    pub fn new(vec: Vec<Biome>) -> Result<Self, BiomeError> {
+        if vec.is_empty() {
+            return Err(BiomeError::Empty);
+        }
+        if vec.len() > u8::MAX as usize + 1 {
+            return Err(BiomeError::TooMany(vec.len()));
+        }
+
+        Ok(Self { vec })
+    }
+
+    /// Checks that every material the biomes refer to is registered.
+    pub fn validate(&self, materials: &MaterialRegistry) -> Result<(), BiomeError> {
+        for biome in &self.vec {
+            for key in [&biome.surface, &biome.subsurface] {
+                if !materials.contains(key.as_str()) {
+                    return Err(BiomeError::UnknownMaterial {
+                        biome: biome.name.clone(),
+                        material: key.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load(dir_path: &Path) -> Result<Self, BiomeError> {
+        let mut paths = read_dir(dir_path)?
+            .map(|entry| entry.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|x| x == "toml"));
+        paths.sort();
+
+        let mut vec = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut biome: Biome = toml::from_str(&read_to_string(&path)?)?;
+            biome.name = path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            vec.push(biome);
+        }
+
+        Self::new(vec)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn get(&self, id: BiomeId) -> &Biome {
+        &self.vec[id.0 as usize]
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<(BiomeId, &Biome)> {
+        self.iter().find(|(_, biome)| biome.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BiomeId, &Biome)> {
+        self.vec
+            .iter()
+            .enumerate()
+            .map(|(i, biome)| (BiomeId(i as u8), biome))
+    }
+
+    pub fn select(&self, temperature: f32, humidity: f32) -> BiomeId {
+        self.blend(temperature, humidity).dominant
+    }
+
+    pub fn blend(&self, temperature: f32, humidity: f32) -> BiomeBlend {
+        let mut distances = [0.0; u8::MAX as usize + 1];
+        let mut dominant = 0;
+        for (i, biome) in self.vec.iter().enumerate() {
+            distances[i] = biome.climate_distance(temperature, humidity);
+            if distances[i] < distances[dominant] {
+                dominant = i;
+            }
+        }
+
+        // Biomes within the blend distance of the nearest one contribute to the terrain shape, which keeps the
+        // heightmap continuous where the dominant biome changes.
+        let mut total_weight = 0.0;
+        let mut height_offset = 0.0;
+        let mut height_scale = 0.0;
+        for (i, biome) in self.vec.iter().enumerate() {
+            let weight = (1.0 - (distances[i] - distances[dominant]) / BLEND_DISTANCE)
+                .max(0.0)
+                .powi(2);
+
+            total_weight += weight;
+            height_offset += biome.height_offset * weight;
+            height_scale += biome.height_scale * weight;
+        }
+
+        BiomeBlend {
+            dominant: BiomeId(dominant as u8),
+            height_offset: height_offset / total_weight,
+            height_scale: height_scale / total_weight,
+        }
+    }
+}
+
+impl Default for BiomeTable {
+    fn default() -> Self {
+        Self { vec: vec![Biome::plains()] }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BiomeError {
+    #[error("Failed to read biome definitions: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to deserialize biome definition: {0}")]
+    TomlDe(#[from] toml::de::Error),
+    #[error("No biomes are defined")]
+    Empty,
+    #[error("Too many biomes are defined ({0})")]
+    TooMany(usize),
+    #[error("Biome '{biome}' references unknown material '{material}'")]
+    UnknownMaterial { biome: String, material: String },
+}
@@ -0,0 +1,107 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use lib::vector::vec2i;
+use parking_lot::Mutex;
+
+use crate::generator::Columns;
+
+/// The number of chunk columns kept by default, which covers the columns loaded around a player with room to spare.
+pub const DEFAULT_COLUMN_CACHE_CAPACITY: usize = 1024;
+
+/// Caches the heights and biomes of chunk columns, so that vertically stacked chunks share one noise evaluation.
+///
+/// Columns are evicted when the chunk map unloads the last chunk of a column, or in insertion order when the cache is
+/// full.
+#[derive(Debug)]
+pub struct ColumnCache {
+    capacity: usize,
+    inner: Mutex<ColumnCacheInner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct ColumnCacheInner {
+    map: HashMap<vec2i, Arc<Columns>>,
+    order: VecDeque<vec2i>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ColumnCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+}
+
+impl ColumnCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(ColumnCacheInner::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(super) fn get_or_insert_with(&self, position: vec2i, f: impl FnOnce() -> Columns) -> Arc<Columns> {
+        if let Some(columns) = self.inner.lock().map.get(&position) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return columns.clone();
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // The noise is evaluated without holding the lock, so other columns can be generated at the same time. If two
+        // threads miss the same column, the second result replaces an identical first one.
+        let columns = Arc::new(f());
+
+        let mut inner = self.inner.lock();
+        if inner
+            .map
+            .insert(position, columns.clone())
+            .is_none()
+        {
+            inner.order.push_back(position);
+        }
+        while inner.map.len() > self.capacity {
+            let Some(oldest) = inner.order.pop_front() else {
+                break;
+            };
+            inner.map.remove(&oldest);
+        }
+
+        columns
+    }
+
+    pub fn evict(&self, position: vec2i) {
+        let mut inner = self.inner.lock();
+        if inner.map.remove(&position).is_some() {
+            inner.order.retain(|&x| x != position);
+        }
+    }
+
+    pub fn contains(&self, position: vec2i) -> bool {
+        self.inner.lock().map.contains_key(&position)
+    }
+
+    pub fn stats(&self) -> ColumnCacheStats {
+        ColumnCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            len: self.inner.lock().map.len(),
+        }
+    }
+}
+
+impl ColumnCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.hits as f64 / total as f64
+    }
+}
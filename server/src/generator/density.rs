@@ -0,0 +1,169 @@
+use lib::vector::{vec3f, vec3u5};
+use lib::world::{CHUNK_AREA, CHUNK_LENGTH};
+use simd_noise::noise::{FbmNoise, Noise, NoiseDim, NoiseTransform, OctaveNoise};
+
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::stage::StageContext;
+use crate::generator::{BaseMaterials, GenerationParams};
+
+// How many cubes above or below the heightmap the density noise can move the surface.
+const DENSITY_SQUASH: f32 = 16.0;
+const DENSITY_SALT: i64 = 0x4445_4e53;
+const DENSITY_STEP: usize = 4;
+// Density is also sampled above the chunk so that surface materials are placed by the distance to the real surface,
+// not to the top of the chunk.
+const SURFACE_MARGIN: usize = 16;
+const DENSITY_XZ_EXTENT: usize = CHUNK_LENGTH / DENSITY_STEP + 1;
+const DENSITY_Y_EXTENT: usize = (CHUNK_LENGTH + SURFACE_MARGIN) / DENSITY_STEP + 1;
+
+const CAVE_A_SALT: i64 = 0x4341_5641;
+const CAVE_B_SALT: i64 = 0x4341_5642;
+const CAVE_STEP: usize = 4;
+const CAVE_EXTENT: usize = CHUNK_LENGTH / CAVE_STEP + 1;
+const CAVE_RADIUS: f32 = 0.08;
+
+impl GenerationParams {
+    pub(super) fn shape_density(&self, context: &mut StageContext) {
+        let (chunk, columns) = context.chunk_and_columns(self);
+        let base_y = chunk.position.0.y * CHUNK_LENGTH as i32;
+
+        let max_height = columns.heights.iter().copied().fold(f32::MIN, f32::max);
+        if base_y as f32 >= max_height + DENSITY_SQUASH {
+            return;
+        }
+
+        let base = BaseMaterials::new(self, chunk);
+        let origin = chunk.position.0.cast::<f32>() * CHUNK_LENGTH as f32;
+        let density = self.get_density_lattice(origin);
+
+        let mut depth_above = [0; CHUNK_AREA];
+        for x in 0..CHUNK_LENGTH {
+            for z in 0..CHUNK_LENGTH {
+                let i = x + z * CHUNK_LENGTH;
+                let height = columns.heights[i];
+
+                let mut depth = 0;
+                for local_y in (0..CHUNK_LENGTH + SURFACE_MARGIN).rev() {
+                    let y = base_y + local_y as i32;
+                    let solid = (height - y as f32) / DENSITY_SQUASH + density.sample(x, local_y, z) > 0.0;
+
+                    if local_y >= CHUNK_LENGTH {
+                        depth = if solid { depth + 1 } else { 0 };
+                        continue;
+                    }
+                    if local_y == CHUNK_LENGTH - 1 {
+                        depth_above[i] = depth;
+                    }
+
+                    if solid {
+                        chunk.set(vec3u5::new(x as u8, local_y as u8, z as u8), Some(base.get(y)));
+                    }
+                }
+            }
+        }
+
+        context.depth_above = depth_above;
+    }
+
+    pub(super) fn carve_caves(&self, chunk: &mut CubeMesh) {
+        let origin = chunk.position.0.cast::<f32>() * CHUNK_LENGTH as f32;
+        let cave_a = self.get_cave_lattice(origin, CAVE_A_SALT);
+        let cave_b = self.get_cave_lattice(origin, CAVE_B_SALT);
+
+        for x in 0..CHUNK_LENGTH {
+            for y in 0..CHUNK_LENGTH {
+                for z in 0..CHUNK_LENGTH {
+                    let position = vec3u5::new(x as u8, y as u8, z as u8);
+                    if chunk.get(position).is_none() {
+                        continue;
+                    }
+
+                    let a = cave_a.sample(x, y, z);
+                    let b = cave_b.sample(x, y, z);
+                    if a * a + b * b < CAVE_RADIUS * CAVE_RADIUS {
+                        chunk.set(position, None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_density_lattice(&self, origin: vec3f) -> Lattice {
+        let seed = self.seed ^ DENSITY_SALT;
+        let step = DENSITY_STEP as f32;
+        let transform: NoiseTransform<{ NoiseDim::new_3d(DENSITY_XZ_EXTENT, DENSITY_Y_EXTENT, DENSITY_XZ_EXTENT) }> =
+            NoiseTransform::from_seed(seed)
+                .with_x(origin.x / step)
+                .with_y(origin.y / step)
+                .with_z(origin.z / step);
+
+        let (noise, _, _) = FbmNoise::from(transform)
+            .with_seed(seed)
+            .with_freq([0.008 * step; 3])
+            .with_octaves(3)
+            .with_lacunarity(2.0)
+            .with_gain(0.5)
+            .generate();
+
+        Lattice {
+            values: noise.iter().map(|x| x.clamp(-1.0, 1.0)).collect(),
+            extents: [DENSITY_XZ_EXTENT, DENSITY_Y_EXTENT, DENSITY_XZ_EXTENT],
+            step: DENSITY_STEP,
+        }
+    }
+
+    fn get_cave_lattice(&self, origin: vec3f, salt: i64) -> Lattice {
+        let seed = self.seed ^ salt;
+        let step = CAVE_STEP as f32;
+        let transform: NoiseTransform<{ NoiseDim::new_3d(CAVE_EXTENT, CAVE_EXTENT, CAVE_EXTENT) }> = NoiseTransform::from_seed(seed)
+            .with_x(origin.x / step)
+            .with_y(origin.y / step)
+            .with_z(origin.z / step);
+
+        let (noise, _, _) = FbmNoise::from(transform)
+            .with_seed(seed)
+            .with_freq([0.012 * step; 3])
+            .with_octaves(2)
+            .with_lacunarity(2.0)
+            .with_gain(0.5)
+            .generate();
+
+        Lattice {
+            values: noise.to_vec(),
+            extents: [CAVE_EXTENT; 3],
+            step: CAVE_STEP,
+        }
+    }
+}
+
+/// Noise sampled every `step` cubes and trilinearly interpolated in between.
+struct Lattice {
+    values: Vec<f32>,
+    extents: [usize; 3],
+    step: usize,
+}
+
+impl Lattice {
+    fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[x + y * self.extents[0] + z * self.extents[0] * self.extents[1]]
+    }
+
+    fn sample(&self, x: usize, y: usize, z: usize) -> f32 {
+        let (x0, y0, z0) = (x / self.step, y / self.step, z / self.step);
+        let step = self.step as f32;
+        let fx = (x % self.step) as f32 / step;
+        let fy = (y % self.step) as f32 / step;
+        let fz = (z % self.step) as f32 / step;
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |y: usize| {
+            lerp(
+                lerp(self.get(x0, y, z0), self.get(x0 + 1, y, z0), fx),
+                lerp(self.get(x0, y, z0 + 1), self.get(x0 + 1, y, z0 + 1), fx),
+                fz,
+            )
+        };
+
+        lerp(plane(y0), plane(y0 + 1), fy)
+    }
+}
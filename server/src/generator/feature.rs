@@ -0,0 +1,191 @@
+use std::fmt::Debug;
+
+use fastrand::Rng;
+use lib::point::{ChunkCubePt, CubePt};
+use lib::vector::{vec3i, vec3u5, Vec3};
+use lib::world::CHUNK_LENGTH;
+
+use crate::chunk::registry::MaterialRegistry;
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::{chunk_seed, GenerationParams};
+
+const FEATURE_SALT: u64 = 0x4645_4154;
+
+pub trait Feature: Debug + Send + Sync {
+    /// Places the feature with its base at `origin`, which is the first empty cube above the surface.
+    fn place(&self, writer: &mut FeatureWriter, origin: vec3i, rng: &mut Rng);
+}
+
+#[derive(Debug, Clone)]
+pub struct Tree {
+    pub trunk: &'static str,
+    pub leaves: &'static str,
+    pub min_height: i32,
+    pub max_height: i32,
+    pub canopy_radius: i32,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Self {
+            trunk: "herbolution:log",
+            leaves: "herbolution:leaves",
+            min_height: 4,
+            max_height: 7,
+            canopy_radius: 2,
+        }
+    }
+}
+
+impl Feature for Tree {
+    fn place(&self, writer: &mut FeatureWriter, origin: vec3i, rng: &mut Rng) {
+        let height = rng.i32(self.min_height..=self.max_height);
+
+        for y in 0..height {
+            writer.set(origin + Vec3::new(0, y, 0), self.trunk);
+        }
+
+        let top = origin + Vec3::new(0, height, 0);
+        for dy in -self.canopy_radius..=1 {
+            let radius = if dy > 0 { self.canopy_radius - 1 } else { self.canopy_radius };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (dy > 0 || rng.bool()) {
+                        continue;
+                    }
+
+                    writer.set(top + Vec3::new(dx, dy, dz), self.leaves);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Boulder {
+    pub material: &'static str,
+    pub min_radius: i32,
+    pub max_radius: i32,
+}
+
+impl Default for Boulder {
+    fn default() -> Self {
+        Self {
+            material: "herbolution:stone",
+            min_radius: 1,
+            max_radius: 3,
+        }
+    }
+}
+
+impl Feature for Boulder {
+    fn place(&self, writer: &mut FeatureWriter, origin: vec3i, rng: &mut Rng) {
+        let radius = rng.i32(self.min_radius..=self.max_radius);
+        let center = origin + Vec3::new(0, radius - 1, 0);
+
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                for dz in -radius..=radius {
+                    let distance = (dx * dx + dy * dy + dz * dz) as f32;
+                    if distance <= (radius * radius) as f32 + rng.f32() {
+                        writer.set(center + Vec3::new(dx, dy, dz), self.material);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes the cubes of features into the chunk being generated, and collects the cubes that fall outside of it.
+pub struct FeatureWriter<'a> {
+    chunk: &'a mut CubeMesh,
+    materials: &'a MaterialRegistry,
+    outside: Vec<(CubePt, String)>,
+}
+
+impl<'a> FeatureWriter<'a> {
+    pub fn new(chunk: &'a mut CubeMesh, materials: &'a MaterialRegistry) -> Self {
+        Self {
+            chunk,
+            materials,
+            outside: vec![],
+        }
+    }
+
+    pub fn set(&mut self, position: vec3i, material: &str) {
+        let ChunkCubePt { chunk, local } = CubePt(position).into();
+        if chunk != self.chunk.position {
+            self.outside.push((CubePt(position), material.to_string()));
+            return;
+        }
+
+        if self.chunk.get(local).is_some() {
+            return;
+        }
+
+        let Some(material) = self.materials.get(material) else {
+            return;
+        };
+
+        let id = self.chunk.palette.insert(material.clone());
+        self.chunk.set(local, Some(id));
+    }
+
+    pub fn into_outside(self) -> Vec<(CubePt, String)> {
+        self.outside
+    }
+}
+
+impl GenerationParams {
+    /// Places the features anchored in a chunk and returns the cubes that belong to other chunks.
+    pub fn place_features(&self, chunk: &mut CubeMesh) -> Vec<(CubePt, String)> {
+        let base = chunk.position.0 * CHUNK_LENGTH as i32;
+        let half = CHUNK_LENGTH as i32 / 2;
+        let (_, biome) = self.biome_at(base.x + half, base.z + half);
+        let Some(surface) = chunk
+            .palette
+            .get_id_by_key(biome.surface.as_str())
+        else {
+            return vec![];
+        };
+
+        let mut rng = Rng::with_seed(chunk_seed(self.seed, chunk.position, FEATURE_SALT));
+        let features: [(&dyn Feature, f32); 2] = [(&Tree::default(), biome.tree_density), (&Boulder::default(), biome.boulder_density)];
+
+        let mut writer = FeatureWriter::new(chunk, &self.materials);
+        for (feature, density) in features {
+            let count = density as u32 + (rng.f32() < density.fract()) as u32;
+            for _ in 0..count {
+                let x = rng.u8(0..CHUNK_LENGTH as u8);
+                let z = rng.u8(0..CHUNK_LENGTH as u8);
+
+                let Some(y) = find_surface(writer.chunk, x, z) else {
+                    continue;
+                };
+                if writer.chunk.get(vec3u5::new(x, y, z)) != Some(surface) {
+                    continue;
+                }
+
+                let origin = base + Vec3::new(x as i32, y as i32 + 1, z as i32);
+                feature.place(&mut writer, origin, &mut rng);
+            }
+        }
+
+        let outside = writer.into_outside();
+        if !outside.is_empty() {
+            // The cubes written to other chunks are only queued once, so this chunk must be saved rather than
+            // regenerated, or reloading it would queue them again.
+            chunk.is_dirty = true;
+        }
+
+        outside
+    }
+}
+
+/// Finds the highest solid cube in a column that has an empty cube above it within the same chunk.
+fn find_surface(chunk: &CubeMesh, x: u8, z: u8) -> Option<u8> {
+    (0..CHUNK_LENGTH as u8 - 1)
+        .rev()
+        .find(|&y| chunk.get(vec3u5::new(x, y, z)).is_some() && chunk.get(vec3u5::new(x, y + 1, z)).is_none())
+}
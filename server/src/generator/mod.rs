@@ -1,112 +1,385 @@
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crossbeam_channel::{unbounded, Receiver, Sender, TryIter};
 use lib::point::ChunkPt;
+use lib::save::{GeneratorConfig, TerrainMode};
 use lib::task::THREAD_POOL;
 use lib::vector::{vec2f, vec3u5};
 use lib::world::{CHUNK_AREA, CHUNK_LENGTH};
 use simd_noise::noise::{FbmNoise, Noise, NoiseDim, NoiseTransform, OctaveNoise};
 
-use crate::chunk::material::Palette;
+use crate::chunk::material::{Material, PaletteMaterialId};
 use crate::chunk::mesh::CubeMesh;
+use crate::chunk::pending::PendingWrites;
+use crate::chunk::registry::MaterialRegistry;
+use crate::generator::biome::{Biome, BiomeId, BiomeTable};
+use crate::generator::column::{ColumnCache, DEFAULT_COLUMN_CACHE_CAPACITY};
+use crate::generator::ore::Ore;
+use crate::generator::stage::{GenerationPipeline, StageContext};
+
+pub mod biome;
+pub mod column;
+mod density;
+pub mod feature;
+pub mod ore;
+pub mod spawn;
+pub mod stage;
+pub mod world_type;
+
+const TEMPERATURE_SEED: i64 = 0x5445_4d50;
+const HUMIDITY_SEED: i64 = 0x4855_4d49;
+const CLIMATE_FREQ: f32 = 0.0004;
+const CLIMATE_OCTAVES: u8 = 3;
 
 #[derive(Debug)]
 pub struct ChunkGenerator {
     sender: Sender<CubeMesh>,
     receiver: Receiver<CubeMesh>,
     params: Arc<GenerationParams>,
+    pipeline: Arc<GenerationPipeline>,
+    column_cache: Arc<ColumnCache>,
+    pending: Arc<PendingWrites>,
+    /// Set once the generator is dropped with its world, so that the chunks still queued are skipped.
+    cancelled: Arc<AtomicBool>,
 }
 
 impl ChunkGenerator {
-    pub fn new(generator: Arc<GenerationParams>) -> Self {
+    pub fn new(generator: Arc<GenerationParams>, pipeline: GenerationPipeline, pending: Arc<PendingWrites>) -> Self {
         let (sender, receiver) = unbounded();
 
         Self {
             sender,
             receiver,
             params: generator,
+            pipeline: Arc::new(pipeline),
+            column_cache: Arc::new(ColumnCache::new(DEFAULT_COLUMN_CACHE_CAPACITY)),
+            pending,
+            cancelled: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub fn request(&self, position: ChunkPt) {
         let sender = self.sender.clone();
         let params = self.params.clone();
+        let pipeline = self.pipeline.clone();
+        let column_cache = self.column_cache.clone();
+        let pending = self.pending.clone();
+        let cancelled = self.cancelled.clone();
 
         THREAD_POOL.spawn(move || {
             #[cfg(feature = "tracing")]
             tracing_tracy::client::set_thread_name!("chunk_generator");
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
             let mut mesh = CubeMesh::new(position);
 
-            params.generate(&mut mesh);
-            sender.send(mesh).unwrap();
+            pending.extend(pipeline.run_cached(&params, &column_cache, &mut mesh));
+            // The world may have been unloaded while the chunk was generated.
+            let _ = sender.send(mesh);
         });
     }
 
+    pub fn params(&self) -> &GenerationParams {
+        &self.params
+    }
+
+    pub fn pipeline(&self) -> &GenerationPipeline {
+        &self.pipeline
+    }
+
+    pub fn column_cache(&self) -> &ColumnCache {
+        &self.column_cache
+    }
+
     pub fn dequeue(&self) -> TryIter<'_, CubeMesh> {
         self.receiver.try_iter()
     }
 }
 
+impl Drop for ChunkGenerator {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
 #[derive(Debug)]
 pub struct GenerationParams {
     seed: i64,
-    global_palette: Arc<Palette>,
+    terrain: TerrainMode,
+    config: GeneratorConfig,
+    materials: Arc<MaterialRegistry>,
+    biomes: Arc<BiomeTable>,
+    ores: Vec<Ore>,
+}
+
+#[derive(Debug)]
+struct Columns {
+    heights: [f32; CHUNK_AREA],
+    biomes: [BiomeId; CHUNK_AREA],
 }
 
 impl GenerationParams {
-    pub fn new(seed: i64, global_palette: Arc<Palette>) -> Self {
-        Self { seed, global_palette }
+    pub fn new(seed: i64, terrain: TerrainMode, config: GeneratorConfig, materials: Arc<MaterialRegistry>, biomes: Arc<BiomeTable>) -> Self {
+        Self {
+            seed,
+            terrain,
+            config,
+            materials,
+            biomes,
+            ores: Ore::defaults(),
+        }
+    }
+
+    /// Replaces the ores that are placed, which default to [`Ore::defaults`]. Their materials must be in the registry.
+    pub fn with_ores(mut self, ores: Vec<Ore>) -> Self {
+        self.ores = ores;
+        self
+    }
+
+    pub fn config(&self) -> &GeneratorConfig {
+        &self.config
+    }
+
+    pub fn materials(&self) -> &MaterialRegistry {
+        &self.materials
+    }
+
+    /// Looks up a material that the world was validated against when it was loaded.
+    fn material(&self, key: &str) -> Arc<Material> {
+        self.materials
+            .get(key)
+            .unwrap_or_else(|| panic!("Unknown material '{key}'"))
+            .clone()
+    }
+
+    pub fn biomes(&self) -> &BiomeTable {
+        &self.biomes
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> (BiomeId, &Biome) {
+        let position = vec2f::new(x as f32, z as f32);
+        let temperature = self.get_climate_point(position, TEMPERATURE_SEED);
+        let humidity = self.get_climate_point(position, HUMIDITY_SEED);
+
+        let id = self.biomes.select(temperature, humidity);
+        (id, self.biomes.get(id))
     }
 
-    #[tracing::instrument(name = "chunk_generate", skip_all)]
+    /// Shapes the terrain of a chunk without placing ores or features in it.
     pub fn generate(&self, chunk: &mut CubeMesh) {
-        let stone = chunk
-            .palette
-            .insert(self.global_palette.get("herbolution:stone"));
-        let dirt = chunk
-            .palette
-            .insert(self.global_palette.get("herbolution:dirt"));
-        let grass = chunk
-            .palette
-            .insert(self.global_palette.get("herbolution:grass"));
+        GenerationPipeline::terrain(self.terrain).run(self, chunk);
+    }
 
-        let chunk_position = chunk.position.0.xz().cast() * CHUNK_LENGTH as f32;
+    fn shape_heightmap(&self, context: &mut StageContext) {
+        let (chunk, columns) = context.chunk_and_columns(self);
+        let base = BaseMaterials::new(self, chunk);
+        let base_y = chunk.position.0.y * CHUNK_LENGTH as i32;
 
-        let noise = self.get_noise(chunk_position);
+        let mut depth_above = [0; CHUNK_AREA];
         for x in 0..CHUNK_LENGTH {
             for z in 0..CHUNK_LENGTH {
-                let h = (noise[x + z * CHUNK_LENGTH] * CHUNK_LENGTH as f32) as i32;
+                let i = x + z * CHUNK_LENGTH;
+                let h = columns.heights[i] as i32;
+                depth_above[i] = (h - base_y - CHUNK_LENGTH as i32).max(0) as u32;
 
                 for chunk_y in 0..CHUNK_LENGTH {
-                    let y = chunk.position.0.y * CHUNK_LENGTH as i32 + chunk_y as i32;
+                    let y = base_y + chunk_y as i32;
+                    if y < h {
+                        chunk.set(vec3u5::new(x as u8, chunk_y as u8, z as u8), Some(base.get(y)));
+                    }
+                }
+            }
+        }
+
+        context.depth_above = depth_above;
+    }
+
+    fn paint_surface(&self, context: &mut StageContext) {
+        let depth_above = context.depth_above;
+        let (chunk, columns) = context.chunk_and_columns(self);
+        let surface_depth = self.config.surface_depth;
+
+        let mut surfaces = SurfaceMaterials::new(&self.biomes);
+        for x in 0..CHUNK_LENGTH {
+            for z in 0..CHUNK_LENGTH {
+                let i = x + z * CHUNK_LENGTH;
+                let subsurface_depth = self.biomes.get(columns.biomes[i]).subsurface_depth;
+
+                let mut depth = depth_above[i];
+                for chunk_y in (0..CHUNK_LENGTH).rev() {
                     let position = vec3u5::new(x as u8, chunk_y as u8, z as u8);
+                    if chunk.get(position).is_none() {
+                        depth = 0;
+                        continue;
+                    }
 
-                    if y < h - 6 {
-                        chunk.set(position, Some(stone));
-                    } else if y < h - 1 {
-                        chunk.set(position, Some(dirt));
-                    } else if y < h {
-                        chunk.set(position, Some(grass));
+                    depth += 1;
+                    if depth > surface_depth + subsurface_depth {
+                        continue;
                     }
+
+                    let (surface, subsurface) = surfaces.get(self, chunk, columns.biomes[i]);
+                    chunk.set(position, Some(if depth <= surface_depth { surface } else { subsurface }));
                 }
             }
         }
     }
 
+    fn get_columns(&self, position: vec2f) -> Columns {
+        let noise = self.get_noise(position);
+        let temperature = self.get_climate_noise(position, TEMPERATURE_SEED);
+        let humidity = self.get_climate_noise(position, HUMIDITY_SEED);
+
+        let mut columns = Columns {
+            heights: [0.0; CHUNK_AREA],
+            biomes: [BiomeId::default(); CHUNK_AREA],
+        };
+        for i in 0..CHUNK_AREA {
+            let blend = self.biomes.blend(temperature[i], humidity[i]);
+            columns.heights[i] = self.config.sea_level + blend.height_offset + noise[i] * blend.height_scale * self.config.height_scale;
+            columns.biomes[i] = blend.dominant;
+        }
+
+        columns
+    }
+
     #[inline]
     fn get_noise(&self, position: vec2f) -> [f32; CHUNK_AREA] {
-        let transform: NoiseTransform<{ NoiseDim::new_2d(CHUNK_LENGTH, CHUNK_LENGTH) }> = NoiseTransform::from_seed(self.seed)
+        let mut noise = [0.0; CHUNK_AREA];
+        for (i, layer) in self.config.noise_layers.iter().enumerate() {
+            let seed = self.seed.wrapping_add(i as i64);
+            let transform: NoiseTransform<{ NoiseDim::new_2d(CHUNK_LENGTH, CHUNK_LENGTH) }> = NoiseTransform::from_seed(seed)
+                .with_x(position.x)
+                .with_y(position.y);
+
+            let (values, _, _) = FbmNoise::from(transform)
+                .with_seed(seed)
+                .with_freq([layer.frequency; 2])
+                .with_octaves(layer.octaves)
+                .with_lacunarity(layer.lacunarity)
+                .with_gain(layer.gain)
+                .generate();
+
+            for (sum, value) in noise.iter_mut().zip(values) {
+                *sum += value * layer.amplitude;
+            }
+        }
+
+        noise
+    }
+
+    #[inline]
+    fn get_climate_noise(&self, position: vec2f, salt: i64) -> [f32; CHUNK_AREA] {
+        let seed = self.seed ^ salt;
+        let transform: NoiseTransform<{ NoiseDim::new_2d(CHUNK_LENGTH, CHUNK_LENGTH) }> = NoiseTransform::from_seed(seed)
+            .with_x(position.x)
+            .with_y(position.y);
+
+        let (mut noise, _, _) = FbmNoise::from(transform)
+            .with_seed(seed)
+            .with_freq([CLIMATE_FREQ; 2])
+            .with_octaves(CLIMATE_OCTAVES)
+            .with_lacunarity(2.0)
+            .with_gain(0.5)
+            .generate();
+        noise.iter_mut().for_each(|x| *x = x.clamp(-1.0, 1.0));
+
+        noise
+    }
+
+    #[inline]
+    fn get_climate_point(&self, position: vec2f, salt: i64) -> f32 {
+        let seed = self.seed ^ salt;
+        let transform: NoiseTransform<{ NoiseDim::new_2d(1, 1) }> = NoiseTransform::from_seed(seed)
             .with_x(position.x)
             .with_y(position.y);
 
-        FbmNoise::from(transform)
-            .with_seed(self.seed)
-            .with_freq([0.001; 2])
-            .with_octaves(6)
+        let ([value], _, _) = FbmNoise::from(transform)
+            .with_seed(seed)
+            .with_freq([CLIMATE_FREQ; 2])
+            .with_octaves(CLIMATE_OCTAVES)
             .with_lacunarity(2.0)
-            .with_gain(0.6)
-            .generate()
-            .0
+            .with_gain(0.5)
+            .generate();
+
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+/// Derives a seed for random decisions that must be reproducible for a given chunk.
+pub(crate) fn chunk_seed(seed: i64, position: ChunkPt, salt: u64) -> u64 {
+    let mut hash = seed as u64 ^ salt.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for value in [position.0.x, position.0.y, position.0.z] {
+        hash = (hash ^ value as u32 as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash ^= hash >> 31;
+    }
+
+    hash
+}
+
+/// Resolves the material below the biome subsurface, which material bands can replace at some heights.
+struct BaseMaterials {
+    base: PaletteMaterialId,
+    bands: Vec<(RangeInclusive<i32>, PaletteMaterialId)>,
+}
+
+impl BaseMaterials {
+    fn new(params: &GenerationParams, chunk: &mut CubeMesh) -> Self {
+        let min_y = chunk.position.0.y * CHUNK_LENGTH as i32;
+        let max_y = min_y + CHUNK_LENGTH as i32 - 1;
+
+        let base = chunk
+            .palette
+            .insert(params.material(params.config.base_material.as_str()));
+        let bands = params
+            .config
+            .bands
+            .iter()
+            .filter(|band| band.min_y <= max_y && band.max_y >= min_y)
+            .map(|band| {
+                let id = chunk
+                    .palette
+                    .insert(params.material(band.material.as_str()));
+                (band.min_y..=band.max_y, id)
+            })
+            .collect();
+
+        Self { base, bands }
+    }
+
+    fn get(&self, y: i32) -> PaletteMaterialId {
+        self.bands
+            .iter()
+            .find(|(range, _)| range.contains(&y))
+            .map_or(self.base, |&(_, id)| id)
+    }
+}
+
+struct SurfaceMaterials {
+    cache: Vec<Option<(PaletteMaterialId, PaletteMaterialId)>>,
+}
+
+impl SurfaceMaterials {
+    fn new(biomes: &BiomeTable) -> Self {
+        Self { cache: vec![None; biomes.len()] }
+    }
+
+    fn get(&mut self, params: &GenerationParams, chunk: &mut CubeMesh, id: BiomeId) -> (PaletteMaterialId, PaletteMaterialId) {
+        *self.cache[id.to_u8() as usize].get_or_insert_with(|| {
+            let biome = params.biomes.get(id);
+            (
+                chunk
+                    .palette
+                    .insert(params.material(biome.surface.as_str())),
+                chunk
+                    .palette
+                    .insert(params.material(biome.subsurface.as_str())),
+            )
+        })
     }
 }
@@ -0,0 +1,118 @@
+use fastrand::Rng;
+use lib::vector::{vec3i, vec3u5, Vec3};
+use lib::world::CHUNK_LENGTH;
+use serde::Deserialize;
+
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::{chunk_seed, GenerationParams};
+
+const ORE_SALT: u64 = 0x4f52_4553;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Ore {
+    pub material: String,
+    pub min_y: i32,
+    pub max_y: i32,
+    /// The average number of veins in a chunk that lies entirely within the depth range.
+    pub veins_per_chunk: f32,
+    pub vein_size: u32,
+}
+
+impl Ore {
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self {
+                material: "herbolution:coal_ore".to_string(),
+                min_y: -128,
+                max_y: 64,
+                veins_per_chunk: 6.0,
+                vein_size: 12,
+            },
+            Self {
+                material: "herbolution:iron_ore".to_string(),
+                min_y: -192,
+                max_y: 0,
+                veins_per_chunk: 3.0,
+                vein_size: 8,
+            },
+            Self {
+                material: "herbolution:gold_ore".to_string(),
+                min_y: -256,
+                max_y: -64,
+                veins_per_chunk: 1.0,
+                vein_size: 6,
+            },
+            Self {
+                material: "herbolution:diamond_ore".to_string(),
+                min_y: i32::MIN,
+                max_y: -160,
+                veins_per_chunk: 0.4,
+                vein_size: 4,
+            },
+        ]
+    }
+}
+
+impl GenerationParams {
+    /// Replaces the generated base material with ore veins. Placement depends only on the seed and the chunk position, so a chunk
+    /// that is generated again receives the same veins.
+    pub fn place_ores(&self, chunk: &mut CubeMesh) {
+        let Some(base) = chunk
+            .palette
+            .get_id_by_key(self.config.base_material.as_str())
+        else {
+            return;
+        };
+
+        let base_y = chunk.position.0.y * CHUNK_LENGTH as i32;
+        let top_y = base_y + CHUNK_LENGTH as i32 - 1;
+
+        for (i, ore) in self.ores.iter().enumerate() {
+            let min_y = ore.min_y.max(base_y);
+            let max_y = ore.max_y.min(top_y);
+            if min_y > max_y {
+                continue;
+            }
+
+            let mut rng = Rng::with_seed(chunk_seed(self.seed, chunk.position, ORE_SALT + i as u64));
+
+            let coverage = (max_y - min_y + 1) as f32 / CHUNK_LENGTH as f32;
+            let expected = ore.veins_per_chunk * coverage;
+            let veins = expected as u32 + (rng.f32() < expected.fract()) as u32;
+            if veins == 0 {
+                continue;
+            }
+
+            let material = chunk
+                .palette
+                .insert(self.material(ore.material.as_str()));
+
+            for _ in 0..veins {
+                let mut position = vec3i::new(
+                    rng.i32(0..CHUNK_LENGTH as i32),
+                    rng.i32(min_y..=max_y) - base_y,
+                    rng.i32(0..CHUNK_LENGTH as i32),
+                );
+
+                for _ in 0..ore.vein_size {
+                    let local = vec3u5::new(position.x as u8, position.y as u8, position.z as u8);
+                    if chunk.get(local) == Some(base) {
+                        chunk.set(local, Some(material));
+                    }
+
+                    let mut step = Vec3::ZERO;
+                    step[rng.usize(0..3)] = if rng.bool() { 1 } else { -1 };
+
+                    let next = position + step;
+                    let next_y = next.y + base_y;
+                    if (0..CHUNK_LENGTH as i32).contains(&next.x)
+                        && (0..CHUNK_LENGTH as i32).contains(&next.z)
+                        && (min_y..=max_y).contains(&next_y)
+                    {
+                        position = next;
+                    }
+                }
+            }
+        }
+    }
+}
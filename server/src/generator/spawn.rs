@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use crossbeam_channel::{bounded, Receiver};
+use lib::point::ChunkPt;
+use lib::task::THREAD_POOL;
+use lib::vector::{vec3d, vec3u5, Vec3};
+use lib::world::{CHUNK_AREA, CHUNK_LENGTH};
+
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::column::ColumnCache;
+use crate::generator::stage::GenerationPipeline;
+use crate::generator::{ChunkGenerator, GenerationParams};
+
+/// How many chunks away from the origin, horizontally, a spawn point is searched for.
+const SPAWN_SEARCH_RADIUS: i32 = 4;
+/// How many chunks the search may generate before giving up, which is far fewer than the whole search area.
+const SPAWN_SEARCH_BUDGET: usize = 96;
+const SPAWN_MIN_CHUNK_Y: i32 = -4;
+const SPAWN_MAX_CHUNK_Y: i32 = 4;
+const SPAWN_HEADROOM: u32 = 2;
+// Entity positions are the minimum corner of their bounds, so this centers a player that is narrower than a cube.
+pub(super) const SPAWN_INSET: f64 = 0.05;
+
+impl ChunkGenerator {
+    /// Finds a spawn point near the origin on top of the generated surface, with a solid floor and enough headroom for a
+    /// player. Returns `None` if no column within the search radius has one, or if the search runs out of chunks to
+    /// generate.
+    pub fn find_spawn(&self) -> Option<vec3d> {
+        self.spawn_search().run()
+    }
+
+    /// Finds a spawn point like [`Self::find_spawn`] on the thread pool, and sends it once it is found.
+    pub fn request_spawn(&self) -> Receiver<Option<vec3d>> {
+        let (sender, receiver) = bounded(1);
+        let search = self.spawn_search();
+
+        THREAD_POOL.spawn(move || {
+            let _ = sender.send(search.run());
+        });
+
+        receiver
+    }
+
+    fn spawn_search(&self) -> SpawnSearch {
+        SpawnSearch {
+            params: self.params.clone(),
+            pipeline: self.pipeline.clone(),
+            column_cache: self.column_cache.clone(),
+        }
+    }
+}
+
+struct SpawnSearch {
+    params: Arc<GenerationParams>,
+    pipeline: Arc<GenerationPipeline>,
+    column_cache: Arc<ColumnCache>,
+}
+
+impl SpawnSearch {
+    fn run(&self) -> Option<vec3d> {
+        let mut budget = SPAWN_SEARCH_BUDGET;
+        let columns = (0..=SPAWN_SEARCH_RADIUS).flat_map(|radius| {
+            (-radius..=radius).flat_map(move |x| {
+                (-radius..=radius)
+                    .filter(move |&z| x.abs() == radius || z.abs() == radius)
+                    .map(move |z| (x, z))
+            })
+        });
+
+        for (x, z) in columns {
+            if budget == 0 {
+                return None;
+            }
+            if let Some(spawn) = self.find_in_column(x, z, &mut budget) {
+                return Some(spawn);
+            }
+        }
+
+        None
+    }
+
+    fn find_in_column(&self, chunk_x: i32, chunk_z: i32, budget: &mut usize) -> Option<vec3d> {
+        // Chunks are generated from the top down, only until every column has reached a cube.
+        let mut meshes = vec![];
+        let mut is_covered = [false; CHUNK_AREA];
+        for chunk_y in (SPAWN_MIN_CHUNK_Y..=SPAWN_MAX_CHUNK_Y).rev() {
+            if *budget == 0 || is_covered.iter().all(|&x| x) {
+                break;
+            }
+            *budget -= 1;
+
+            let mut mesh = CubeMesh::new(ChunkPt(Vec3::new(chunk_x, chunk_y, chunk_z)));
+            // Cubes written into neighbouring chunks are dropped, as those chunks generate them again when loaded.
+            self.pipeline
+                .run_cached(&self.params, &self.column_cache, &mut mesh);
+
+            for (i, is_covered) in is_covered.iter_mut().enumerate() {
+                let (x, z) = ((i % CHUNK_LENGTH) as u8, (i / CHUNK_LENGTH) as u8);
+                *is_covered |= (0..CHUNK_LENGTH as u8).any(|y| mesh.get(vec3u5::new(x, y, z)).is_some());
+            }
+            meshes.push(mesh);
+        }
+
+        let base_x = chunk_x * CHUNK_LENGTH as i32;
+        let base_z = chunk_z * CHUNK_LENGTH as i32;
+
+        let mut columns = (0..CHUNK_LENGTH as u8)
+            .flat_map(|x| (0..CHUNK_LENGTH as u8).map(move |z| (x, z)))
+            .collect::<Vec<_>>();
+        columns.sort_by_key(|&(x, z)| (base_x + x as i32).pow(2) + (base_z + z as i32).pow(2));
+
+        columns
+            .into_iter()
+            .find_map(|(x, z)| find_floor(&meshes, x, z).map(|y| Vec3::new(base_x + x as i32, y + 1, base_z + z as i32)))
+            .map(|position| position.cast::<f64>() + Vec3::new(SPAWN_INSET, 0.0, SPAWN_INSET))
+    }
+}
+
+/// Finds the height of the topmost cube in a column of stacked chunks, if it can be stood on with enough headroom.
+fn find_floor(meshes: &[CubeMesh], x: u8, z: u8) -> Option<i32> {
+    let mut headroom = 0;
+    for mesh in meshes {
+        for y in (0..CHUNK_LENGTH as u8).rev() {
+            let Some(id) = mesh.get(vec3u5::new(x, y, z)) else {
+                headroom += 1;
+                continue;
+            };
+
+            // A floor without a collider, such as a fluid, cannot be stood on, and the column is rejected rather than
+            // searched further down, since anything below it is not on the surface.
+            let is_solid = mesh
+                .palette
+                .get_by_id(id)
+                .is_some_and(|material| material.has_collider);
+            if headroom < SPAWN_HEADROOM || !is_solid {
+                return None;
+            }
+
+            return Some(mesh.position.0.y * CHUNK_LENGTH as i32 + y as i32);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,254 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use lib::point::CubePt;
+use lib::save::TerrainMode;
+use lib::world::{CHUNK_AREA, CHUNK_LENGTH};
+
+use crate::chunk::light;
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::column::ColumnCache;
+use crate::generator::{Columns, GenerationParams};
+
+/// A step of chunk generation. A chunk is generated by running it through an ordered list of stages.
+pub trait GenerationStage: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The context outside of the chunk that the stage depends on.
+    fn neighbours(&self) -> NeighbourContext {
+        NeighbourContext::None
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext);
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NeighbourContext {
+    /// The stage only reads and writes the chunk itself.
+    None,
+    /// The stage reads how deep the terrain above the chunk reaches, which the terrain shape stage records.
+    ColumnsAbove,
+    /// The stage writes cubes into neighbouring chunks, which are queued until those chunks are loaded.
+    WritesNeighbours,
+}
+
+/// The chunk being generated, along with the state that stages share.
+pub struct StageContext<'a> {
+    pub chunk: &'a mut CubeMesh,
+    column_cache: Option<&'a ColumnCache>,
+    pub(super) columns: Option<Arc<Columns>>,
+    pub(super) depth_above: [u32; CHUNK_AREA],
+    outside: Vec<(CubePt, String)>,
+}
+
+impl<'a> StageContext<'a> {
+    pub fn new(chunk: &'a mut CubeMesh) -> Self {
+        Self {
+            chunk,
+            column_cache: None,
+            columns: None,
+            depth_above: [0; CHUNK_AREA],
+            outside: vec![],
+        }
+    }
+
+    pub fn with_column_cache(mut self, column_cache: &'a ColumnCache) -> Self {
+        self.column_cache = Some(column_cache);
+        self
+    }
+
+    /// Returns the chunk along with its columns, which are computed by the first stage that needs them.
+    pub(super) fn chunk_and_columns(&mut self, params: &GenerationParams) -> (&mut CubeMesh, &Columns) {
+        let position = self.chunk.position.0.xz();
+        let columns = self.columns.get_or_insert_with(|| {
+            let compute = || params.get_columns(position.cast() * CHUNK_LENGTH as f32);
+            match self.column_cache {
+                Some(cache) => cache.get_or_insert_with(position, compute),
+                None => Arc::new(compute()),
+            }
+        });
+
+        (&mut *self.chunk, columns)
+    }
+
+    /// The number of solid cubes directly above the top of each column of the chunk.
+    pub fn depth_above(&self) -> &[u32; CHUNK_AREA] {
+        &self.depth_above
+    }
+
+    pub fn write_outside(&mut self, writes: impl IntoIterator<Item = (CubePt, String)>) {
+        self.outside.extend(writes);
+    }
+
+    pub fn into_outside(self) -> Vec<(CubePt, String)> {
+        self.outside
+    }
+}
+
+#[derive(Debug)]
+pub struct GenerationPipeline {
+    stages: Vec<Box<dyn GenerationStage>>,
+}
+
+impl GenerationPipeline {
+    pub fn new(stages: Vec<Box<dyn GenerationStage>>) -> Self {
+        Self { stages }
+    }
+
+    /// The stages that shape the terrain, without anything placed in it.
+    pub fn terrain(mode: TerrainMode) -> Self {
+        let mut stages: Vec<Box<dyn GenerationStage>> = vec![Box::new(TerrainShape), Box::new(SurfacePaint)];
+        if mode == TerrainMode::Density {
+            stages.push(Box::new(Carving));
+        }
+
+        Self::new(stages)
+    }
+
+    pub fn standard(mode: TerrainMode) -> Self {
+        Self::terrain(mode)
+            .with(OrePlacement)
+            .with(FeaturePlacement)
+            .with(Lighting)
+    }
+
+    pub fn with(mut self, stage: impl GenerationStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    pub fn without(mut self, name: &str) -> Self {
+        self.stages.retain(|stage| stage.name() != name);
+        self
+    }
+
+    pub fn stages(&self) -> impl Iterator<Item = &dyn GenerationStage> {
+        self.stages.iter().map(|x| x.as_ref())
+    }
+
+    /// Runs every stage on a chunk, and returns the cubes that stages wrote into neighbouring chunks.
+    pub fn run(&self, params: &GenerationParams, chunk: &mut CubeMesh) -> Vec<(CubePt, String)> {
+        self.run_with(params, StageContext::new(chunk))
+    }
+
+    /// Runs every stage on a chunk like [`Self::run`], sharing column noise with other chunks through the cache.
+    pub fn run_cached(&self, params: &GenerationParams, column_cache: &ColumnCache, chunk: &mut CubeMesh) -> Vec<(CubePt, String)> {
+        self.run_with(params, StageContext::new(chunk).with_column_cache(column_cache))
+    }
+
+    #[tracing::instrument(name = "chunk_generate", skip_all)]
+    fn run_with(&self, params: &GenerationParams, mut context: StageContext) -> Vec<(CubePt, String)> {
+        for stage in &self.stages {
+            let outside = context.outside.len();
+            stage.apply(params, &mut context);
+
+            debug_assert!(
+                context.outside.len() == outside || stage.neighbours() == NeighbourContext::WritesNeighbours,
+                "Stage '{}' wrote into neighbouring chunks without declaring it",
+                stage.name()
+            );
+        }
+
+        context.into_outside()
+    }
+}
+
+/// Fills the solid part of the chunk with the base material.
+#[derive(Debug)]
+pub struct TerrainShape;
+
+impl GenerationStage for TerrainShape {
+    fn name(&self) -> &'static str {
+        "terrain_shape"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        match params.terrain {
+            TerrainMode::Heightmap => params.shape_heightmap(context),
+            TerrainMode::Density => params.shape_density(context),
+        }
+    }
+}
+
+/// Replaces the top of each column with the surface and subsurface materials of its biome.
+#[derive(Debug)]
+pub struct SurfacePaint;
+
+impl GenerationStage for SurfacePaint {
+    fn name(&self) -> &'static str {
+        "surface_paint"
+    }
+
+    fn neighbours(&self) -> NeighbourContext {
+        NeighbourContext::ColumnsAbove
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        params.paint_surface(context);
+    }
+}
+
+/// Hollows out caves.
+#[derive(Debug)]
+pub struct Carving;
+
+impl GenerationStage for Carving {
+    fn name(&self) -> &'static str {
+        "carving"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        params.carve_caves(context.chunk);
+    }
+}
+
+#[derive(Debug)]
+pub struct OrePlacement;
+
+impl GenerationStage for OrePlacement {
+    fn name(&self) -> &'static str {
+        "ores"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        params.place_ores(context.chunk);
+    }
+}
+
+#[derive(Debug)]
+pub struct FeaturePlacement;
+
+impl GenerationStage for FeaturePlacement {
+    fn name(&self) -> &'static str {
+        "features"
+    }
+
+    fn neighbours(&self) -> NeighbourContext {
+        NeighbourContext::WritesNeighbours
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        let outside = params.place_features(context.chunk);
+        context.write_outside(outside);
+    }
+}
+
+/// Lights the chunk by itself, so that only the light across its sides is left to spread once it is loaded. Columns
+/// with nothing solid above the chunk are open to the sky.
+#[derive(Debug)]
+pub struct Lighting;
+
+impl GenerationStage for Lighting {
+    fn name(&self) -> &'static str {
+        "lighting"
+    }
+
+    fn neighbours(&self) -> NeighbourContext {
+        NeighbourContext::ColumnsAbove
+    }
+
+    fn apply(&self, _params: &GenerationParams, context: &mut StageContext) {
+        let depth_above = context.depth_above;
+        light::light_chunk(context.chunk, |x, z| depth_above[x as usize + z as usize * CHUNK_LENGTH] == 0);
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use lib::save::{MaterialBand, TerrainMode, WorldType};
+use lib::vector::{vec3d, vec3i, vec3u5, Vec3};
+use lib::world::CHUNK_LENGTH;
+
+use crate::chunk::material::Material;
+use crate::chunk::mesh::CubeMesh;
+use crate::generator::spawn::SPAWN_INSET;
+use crate::generator::stage::{GenerationPipeline, GenerationStage, Lighting, StageContext};
+use crate::generator::GenerationParams;
+
+const PLATFORM_RADIUS: i32 = 3;
+const PLATFORM_MATERIAL: &str = "herbolution:stone";
+const DEBUG_GRID_ROW: usize = 8;
+const DEBUG_GRID_SPACING: i32 = 2;
+
+impl GenerationPipeline {
+    pub fn for_world(world_type: &WorldType, terrain: TerrainMode) -> Self {
+        match world_type {
+            WorldType::Standard => Self::standard(terrain),
+            WorldType::Flat { layers } => Self::new(vec![Box::new(FlatLayers { layers: layers.clone() }), Box::new(Lighting)]),
+            WorldType::Void => Self::new(vec![Box::new(SpawnPlatform), Box::new(Lighting)]),
+            WorldType::DebugGrid => Self::new(vec![Box::new(DebugGrid), Box::new(Lighting)]),
+        }
+    }
+}
+
+/// The spawn point of the world types whose terrain is known without generating it, on top of the flat layers or on the
+/// spawn platform. The spawn point of the standard world type has to be searched for instead.
+pub fn fixed_spawn(world_type: &WorldType) -> Option<vec3d> {
+    let floor = match world_type {
+        WorldType::Standard => return None,
+        WorldType::Flat { layers } => Vec3::new(0, layers.iter().map(|x| x.max_y).max().unwrap_or(-1), 0),
+        WorldType::Void => Vec3::new(0, -1, 0),
+        WorldType::DebugGrid => Vec3::new(-DEBUG_GRID_SPACING, -1, -DEBUG_GRID_SPACING),
+    };
+
+    Some(floor.cast::<f64>() + Vec3::new(SPAWN_INSET, 1.0, SPAWN_INSET))
+}
+
+#[derive(Debug)]
+pub struct FlatLayers {
+    pub layers: Vec<MaterialBand>,
+}
+
+impl GenerationStage for FlatLayers {
+    fn name(&self) -> &'static str {
+        "flat_layers"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        for layer in &self.layers {
+            let material = params.material(layer.material.as_str());
+            fill(context.chunk, Vec3::new(i32::MIN, layer.min_y, i32::MIN), Vec3::new(i32::MAX, layer.max_y, i32::MAX), &material);
+        }
+    }
+}
+
+/// Places a small platform below the spawn point.
+#[derive(Debug)]
+pub struct SpawnPlatform;
+
+impl GenerationStage for SpawnPlatform {
+    fn name(&self) -> &'static str {
+        "spawn_platform"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        fill(
+            context.chunk,
+            Vec3::new(-PLATFORM_RADIUS, -1, -PLATFORM_RADIUS),
+            Vec3::new(PLATFORM_RADIUS, -1, PLATFORM_RADIUS),
+            &params.material(PLATFORM_MATERIAL),
+        );
+    }
+}
+
+/// Lays out every material in rows along the x axis, on a platform that starts at the spawn point.
+#[derive(Debug)]
+pub struct DebugGrid;
+
+impl GenerationStage for DebugGrid {
+    fn name(&self) -> &'static str {
+        "debug_grid"
+    }
+
+    fn apply(&self, params: &GenerationParams, context: &mut StageContext) {
+        let materials = params.materials.iter().collect::<Vec<_>>();
+        let rows = materials.len().div_ceil(DEBUG_GRID_ROW) as i32;
+
+        fill(
+            context.chunk,
+            Vec3::new(-DEBUG_GRID_SPACING, -1, -DEBUG_GRID_SPACING),
+            Vec3::new(DEBUG_GRID_ROW as i32 * DEBUG_GRID_SPACING, -1, rows * DEBUG_GRID_SPACING),
+            &params.material(PLATFORM_MATERIAL),
+        );
+
+        for (i, material) in materials.into_iter().enumerate() {
+            let position = Vec3::new(
+                (i % DEBUG_GRID_ROW) as i32 * DEBUG_GRID_SPACING,
+                0,
+                (i / DEBUG_GRID_ROW) as i32 * DEBUG_GRID_SPACING,
+            );
+            fill(context.chunk, position, position, material);
+        }
+    }
+}
+
+/// Sets every cube of the chunk that lies within the inclusive bounds, given in world coordinates.
+fn fill(chunk: &mut CubeMesh, min: vec3i, max: vec3i, material: &Arc<Material>) {
+    let base = chunk.position.0 * CHUNK_LENGTH as i32;
+    let range = |min: i32, max: i32, base: i32| min.saturating_sub(base).max(0)..=max.saturating_sub(base).min(CHUNK_LENGTH as i32 - 1);
+
+    let (xs, ys, zs) = (range(min.x, max.x, base.x), range(min.y, max.y, base.y), range(min.z, max.z, base.z));
+    if xs.is_empty() || ys.is_empty() || zs.is_empty() {
+        return;
+    }
+
+    let material = chunk.palette.insert(material.clone());
+    for x in xs {
+        for y in ys.clone() {
+            for z in zs.clone() {
+                chunk.set(vec3u5::new(x as u8, y as u8, z as u8), Some(material));
+            }
+        }
+    }
+}
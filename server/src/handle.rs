@@ -12,13 +12,17 @@ use tracing::error;
 
 use crate::chunk::handle::ChunkLoad;
 use crate::player::ServerPlayerHandle;
+use crate::GameError;
 
 #[derive(Debug)]
 pub struct GameHandle {
     pub chunks: GameChunksHandle,
     player_handle_rx: Receiver<ServerPlayerHandle>,
     pub particle_rx: Receiver<Particle>,
+    world_tx: Sender<String>,
     exit_signal: Arc<AtomicBool>,
+    exited_rx: Receiver<()>,
+    failed_rx: Receiver<GameError>,
 }
 
 #[derive(Debug)]
@@ -35,24 +39,40 @@ impl GameHandle {
         self.player_handle_rx.try_recv().ok()
     }
 
+    pub fn request_world(&self, name: impl Into<String>) {
+        if let Err(e) = self.world_tx.try_send(name.into()) {
+            error!("Failed to request world: {}", e);
+        }
+    }
+
     pub fn request_exit(&self) {
         self.exit_signal.store(true, Ordering::Relaxed);
     }
+
+    pub fn wait_for_exit(&self) {
+        let _ = self.exited_rx.recv();
+    }
+
+    /// Why the game failed to start, once it has.
+    pub fn failure(&self) -> Option<GameError> {
+        self.failed_rx.try_recv().ok()
+    }
+}
+
+#[derive(Debug)]
+pub enum ChunkEvent {
+    Load(ChunkLoad),
+    Unload(ChunkPt),
 }
 
 #[derive(Debug)]
 pub struct GameChunksHandle {
-    load_rx: Receiver<ChunkLoad>,
-    unload_rx: Receiver<ChunkPt>,
+    event_rx: Receiver<ChunkEvent>,
 }
 
 impl GameChunksHandle {
-    pub fn next_load(&self) -> Option<ChunkLoad> {
-        self.load_rx.try_recv().ok()
-    }
-
-    pub fn next_unload(&self) -> Option<ChunkPt> {
-        self.unload_rx.try_recv().ok()
+    pub fn next_event(&self) -> Option<ChunkEvent> {
+        self.event_rx.try_recv().ok()
     }
 }
 
@@ -61,7 +81,10 @@ pub struct ClientHandle {
     pub chunks: ClientChunksHandle,
     player_handle_tx: Sender<ServerPlayerHandle>,
     pub(crate) particle_tx: Sender<Particle>,
+    world_rx: Receiver<String>,
     exit_signal: Arc<AtomicBool>,
+    exited_tx: Sender<()>,
+    failed_tx: Sender<GameError>,
 }
 
 impl ClientHandle {
@@ -71,50 +94,71 @@ impl ClientHandle {
         }
     }
 
+    pub fn next_world_request(&self) -> Option<String> {
+        self.world_rx.try_recv().ok()
+    }
+
     pub fn is_exit_requested(&self) -> bool {
         self.exit_signal.load(Ordering::Relaxed)
     }
+
+    pub fn signal_exited(&self) {
+        let _ = self.exited_tx.try_send(());
+    }
+
+    /// Tells the client that the game failed to start, after which it has exited.
+    pub fn signal_failed(&self, error: GameError) {
+        let _ = self.failed_tx.try_send(error);
+        self.signal_exited();
+    }
 }
 
 #[derive(Debug)]
 pub struct ClientChunksHandle {
-    pub load_tx: Sender<ChunkLoad>,
-    pub unload_tx: Sender<ChunkPt>,
+    pub event_tx: Sender<ChunkEvent>,
 }
 
 impl ClientChunksHandle {
     pub fn load(&self, value: ChunkLoad) {
-        if let Err(e) = self.load_tx.try_send(value) {
+        if let Err(e) = self.event_tx.try_send(ChunkEvent::Load(value)) {
             error!("Failed to send chunk load: {}", e);
         }
     }
 
     pub fn unload(&self, value: ChunkPt) {
-        if let Err(e) = self.unload_tx.try_send(value) {
+        if let Err(e) = self.event_tx.try_send(ChunkEvent::Unload(value)) {
             error!("Failed to send chunk unload: {}", e);
         }
     }
 }
 
 pub fn create() -> (ClientHandle, GameHandle) {
-    let (load_tx, load_rx) = unbounded();
-    let (unload_tx, unload_rx) = unbounded();
+    let (event_tx, event_rx) = unbounded();
     let (player_handle_tx, player_handle_rx) = bounded(1);
     let (particle_tx, particle_rx) = unbounded();
+    let (world_tx, world_rx) = unbounded();
     let exit_signal = Arc::new(AtomicBool::new(false));
+    let (exited_tx, exited_rx) = bounded(1);
+    let (failed_tx, failed_rx) = bounded(1);
 
     (
         ClientHandle {
-            chunks: ClientChunksHandle { load_tx, unload_tx },
+            chunks: ClientChunksHandle { event_tx },
             player_handle_tx,
             particle_tx,
+            world_rx,
             exit_signal: Arc::clone(&exit_signal),
+            exited_tx,
+            failed_tx,
         },
         GameHandle {
-            chunks: GameChunksHandle { load_rx, unload_rx },
+            chunks: GameChunksHandle { event_rx },
             player_handle_rx,
             particle_rx,
+            world_tx,
             exit_signal,
+            exited_rx,
+            failed_rx,
         },
     )
 }
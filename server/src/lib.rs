@@ -1,25 +1,37 @@
 #![allow(incomplete_features)]
-#![feature(array_chunks)]
 #![feature(generic_const_exprs)]
 #![feature(iter_next_chunk)]
 
 extern crate herbolution_lib as lib;
 
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
 use hashbrown::HashMap;
-use lib::save::Save;
+use lib::save::{Save, SaveError};
 use lib::size::Size3;
 use lib::task::THREAD_POOL;
 use lib::util::DeltaTime;
-use lib::vector::Vec3;
+use lib::vector::{vec3d, Vec3};
+use lib::world::Health;
+use thiserror::Error;
+use time::Duration;
+use tracing::{debug, error, info, warn};
 
+use crate::autosave::AutosaveScheduler;
+use crate::chunk::registry::{MaterialError, MaterialRegistry};
 use crate::entity::behavior::EntityBehaviors;
 use crate::entity::body::{Bounds, EntityAttrs, EntityBody};
 use crate::entity::components::ChunkLoader;
+use crate::entity::set::EntityId;
 use crate::entity::{Entity, EntityData};
+use crate::generator::biome::BiomeTable;
 use crate::handle::{ClientHandle, GameHandle};
 use crate::player::Player;
 use crate::world::World;
 
+pub mod autosave;
 pub mod chunk;
 pub mod entity;
 pub mod generator;
@@ -27,15 +39,26 @@ pub mod handle;
 pub mod player;
 pub mod world;
 
+const LOCAL_PLAYER: &str = "local";
+
 pub struct Game {
     world_map: HashMap<String, World>,
     delta_time: DeltaTime,
     handle: ClientHandle,
     save: Save,
+    player: Option<EntityId>,
+    player_world: String,
+    autosave: AutosaveScheduler,
+    materials: Arc<MaterialRegistry>,
+    biomes: Arc<BiomeTable>,
 }
 
 pub struct Options {
     pub save: Save,
+    pub autosave_interval: Duration,
+    pub assets_path: PathBuf,
+    /// The world the player starts in, or the default world of the save if `None`.
+    pub world: Option<String>,
 }
 
 impl Game {
@@ -43,7 +66,9 @@ impl Game {
         let (client_handle, handle) = handle::create();
 
         THREAD_POOL.spawn(move || {
-            let mut game = Game::new(options, client_handle);
+            let Some(mut game) = Game::new(options, client_handle) else {
+                return;
+            };
             game.add_client();
 
             loop {
@@ -59,55 +84,229 @@ impl Game {
         handle
     }
 
-    fn exit(&self) {}
+    fn exit(&mut self) {
+        self.save();
+        self.handle.signal_exited();
+    }
+
+    fn save(&mut self) {
+        self.autosave.reset();
+
+        if let Some(id) = self.player {
+            self.world_map
+                .get_mut(&self.player_world)
+                .unwrap()
+                .save_player(LOCAL_PLAYER, id);
+        }
+
+        for world in self.world_map.values() {
+            world.save();
+        }
+    }
 
     fn add_client(&mut self) {
-        let (player, handle) = Player::new();
         let world = self
             .world_map
-            .get_mut(&self.save.descriptor.default_world)
+            .get_mut(&self.player_world)
             .unwrap();
-        world.entity_set.add(Entity {
-            data: EntityData {
-                body: EntityBody::new(
-                    Vec3::new(0.0, 96.0, 0.0),
-                    Bounds {
-                        size: Size3::new(0.9, 1.9, 0.9),
-                        eye_offset: Vec3::new(0.0, 1.0, 0.0),
-                    },
-                    EntityAttrs {
-                        has_gravity: true,
-                        acceleration_rate: 20.0,
-                        terminal_velocity: 100.0,
-                    },
-                ),
+        let data = world.load_player(LOCAL_PLAYER);
+
+        let (player, handle) = Player::new(data.as_ref().map_or(Health::new(100.0), |x| x.health));
+
+        let mut body = EntityBody::new(
+            data.as_ref()
+                .map_or(world.spawn_position(), |x| x.position),
+            Bounds {
+                size: Size3::new(0.9, 1.9, 0.9),
+                eye_offset: Vec3::new(0.0, 1.0, 0.0),
+            },
+            EntityAttrs {
+                has_gravity: data.as_ref().is_none_or(|x| x.has_gravity),
+                acceleration_rate: data.as_ref().map_or(20.0, |x| x.acceleration_rate),
+                terminal_velocity: 100.0,
             },
+        );
+        if let Some(data) = &data {
+            body.rotation = data.rotation;
+        }
+
+        self.player = Some(world.add_entity(Entity {
+            data: EntityData { body },
             behaviors: EntityBehaviors::new()
                 .with(player)
                 .with(ChunkLoader::new()),
-        });
+        }));
 
         self.handle.send_player_handle(handle);
     }
 
-    fn new(Options { save }: Options, handle: ClientHandle) -> Self {
+    fn load_world(&mut self, name: &str) -> Result<&mut World, SaveError> {
+        if !self.world_map.contains_key(name) {
+            let world = World::from_save(self.save.world(name)?, self.materials.clone(), self.biomes.clone());
+            self.world_map.insert(name.to_string(), world);
+        }
+
+        Ok(self.world_map.get_mut(name).unwrap())
+    }
+
+    pub fn move_entity(&mut self, id: EntityId, from: &str, to: &str, position: vec3d) -> Result<Option<EntityId>, SaveError> {
+        self.load_world(to)?;
+
+        let Some(source) = self.world_map.get_mut(from) else {
+            return Ok(None);
+        };
+        let Some(mut entity) = source.remove_entity(id, &self.handle) else {
+            return Ok(None);
+        };
+
+        entity.data.body.teleport(position);
+
+        Ok(Some(self.world_map.get_mut(to).unwrap().add_entity(entity)))
+    }
+
+    pub fn switch_world(&mut self, name: &str) -> Result<(), SaveError> {
+        let Some(id) = self.player else {
+            return Ok(());
+        };
+        if name == self.player_world {
+            return Ok(());
+        }
+
+        let world = self.load_world(name)?;
+        let data = world.load_player(LOCAL_PLAYER);
+        let spawn_position = world.spawn_position();
+
+        let from = self.player_world.clone();
+        self.world_map
+            .get_mut(&from)
+            .unwrap()
+            .save_player(LOCAL_PLAYER, id);
+
+        let position = data.as_ref().map_or(spawn_position, |x| x.position);
+        let Some(id) = self.move_entity(id, &from, name, position)? else {
+            return Ok(());
+        };
+
+        // The player is restored as it was in this world, like when it is added to the game.
+        if let Some(data) = &data {
+            let entity = self.world_map.get_mut(name).unwrap().entity_set.get_mut(id).unwrap();
+            let body = &mut entity.data.body;
+            body.rotation = data.rotation;
+            body.attrs.has_gravity = data.has_gravity;
+            body.attrs.acceleration_rate = data.acceleration_rate;
+            entity
+                .behaviors
+                .get_mut::<Player>()
+                .set_health(data.health);
+        }
+
+        self.player = Some(id);
+        self.player_world = name.to_string();
+        info!("Switched player from world '{from}' to '{name}'");
+
+        // Worlds are only loaded while players are in them.
+        if !self.world_map[&from].has_players() {
+            let world = self.world_map.remove(&from).unwrap();
+            world.save();
+            info!("Unloaded world '{from}'");
+        }
+
+        Ok(())
+    }
+
+    /// Loads the game, or tells the client why it could not be loaded.
+    fn new(Options { save, autosave_interval, assets_path, world }: Options, handle: ClientHandle) -> Option<Self> {
+        for migration in &save.migrations.applied {
+            match &migration.world {
+                Some(world) => info!("Migrated world '{world}' from format version {}: {}", migration.from_version, migration.description),
+                None => info!("Migrated save from format version {}: {}", migration.from_version, migration.description),
+            }
+        }
+        if let Some(backup_path) = &save.migrations.backup_path {
+            info!("The save was backed up to {} before migrating", backup_path.display());
+        }
+
+        let materials = match load_materials(&assets_path, &save.path) {
+            Ok(x) => Arc::new(x),
+            Err(e) => {
+                error!("Failed to load materials: {e}");
+                handle.signal_failed(e.into());
+                return None;
+            }
+        };
+        let biomes = match BiomeTable::load(&assets_path.join("biome")).and_then(|x| x.validate(&materials).map(|_| x)) {
+            Ok(x) => Arc::new(x),
+            Err(e) => {
+                warn!("Failed to load biomes, falling back to the default biome: {e}");
+                Arc::new(BiomeTable::default())
+            }
+        };
+
+        let player_world = world.unwrap_or_else(|| save.descriptor.default_world.clone());
+        let save_world = match save.world(&player_world) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Failed to open world '{player_world}': {e}");
+                handle.signal_failed(e.into());
+                return None;
+            }
+        };
         let mut world_map = HashMap::new();
-        let save_world = save.default_world().unwrap();
-        world_map.insert(save.descriptor.default_world.clone(), World::from_save(save_world));
+        world_map.insert(player_world.clone(), World::from_save(save_world, materials.clone(), biomes.clone()));
 
-        Self {
+        Some(Self {
             world_map,
             delta_time: DeltaTime::new(),
             handle,
+            player_world,
             save,
-        }
+            player: None,
+            autosave: AutosaveScheduler::new(autosave_interval),
+            materials,
+            biomes,
+        })
     }
 
     fn update(&mut self) {
         let dt = self.delta_time.next();
 
+        while let Some(name) = self.handle.next_world_request() {
+            if let Err(e) = self.switch_world(&name) {
+                error!("Failed to switch to world '{name}': {e}");
+            }
+        }
+
         for world in self.world_map.values_mut() {
             world.update(&self.handle, dt);
         }
+
+        if self.autosave.tick(dt) {
+            let start = Instant::now();
+            self.save();
+            debug!("Autosaved in {:?}", start.elapsed());
+        }
     }
 }
+
+/// Loads the bundled materials, overridden by the materials of the save.
+fn load_materials(assets_path: &Path, save_path: &Path) -> Result<MaterialRegistry, MaterialError> {
+    let materials = MaterialRegistry::load(&assets_path.join("material"))?;
+
+    let mut overridden = materials.clone();
+    match overridden.load_overrides(&save_path.join("materials")) {
+        Ok(()) => Ok(overridden),
+        Err(e) => {
+            error!("Failed to load the materials of the save, using the bundled materials: {e}");
+            Ok(materials)
+        }
+    }
+}
+
+/// Why a game could not be started.
+#[derive(Debug, Error)]
+pub enum GameError {
+    #[error(transparent)]
+    Materials(#[from] MaterialError),
+    #[error(transparent)]
+    Save(#[from] SaveError),
+}
@@ -7,10 +7,11 @@ use crossbeam_channel::{Receiver, Sender};
 use lib::aabb::Aabb3;
 use lib::motile::Motile;
 use lib::rotation::Euler;
-use lib::util::default;
+use lib::util::{default, GroupKeyBuf};
 use lib::vector::{vec2d, vec3d, vec3f, Vec2, Vec3};
 use lib::world::Health;
 use time::Duration;
+use tracing::warn;
 
 use crate::chunk::map::CubeHit;
 use crate::chunk::material::Material;
@@ -27,6 +28,8 @@ pub struct Player {
     regeneration: f32,
     dig_speed: f32,
     dig_state: Option<DigState>,
+    /// The material placed with the right hand, which is the default material of the registry until one is chosen.
+    held_material: Option<GroupKeyBuf>,
 }
 
 #[derive(Debug)]
@@ -36,22 +39,31 @@ struct DigState {
 }
 
 impl Player {
-    pub fn new() -> (Self, ServerPlayerHandle) {
+    pub fn new(health: Health) -> (Self, ServerPlayerHandle) {
         let (handle, server_handle) = create_handles();
         (
             Self {
                 action_state: ActionState::default(),
                 handle,
                 target: None,
-                health: Health::new(100.0),
+                health,
                 regeneration: 3.0,
                 dig_speed: 1.0,
                 dig_state: None,
+                held_material: None,
             },
             server_handle,
         )
     }
 
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    pub fn set_health(&mut self, health: Health) {
+        self.health = health;
+    }
+
     fn process_input(&mut self, ctx: &mut EntityContext) {
         for msg in self.handle.input_delta.try_iter() {
             match msg {
@@ -190,8 +202,21 @@ impl Player {
             .unwrap()
             .intersects(&ctx.entity.body().bounds())
         {
+            let materials = ctx.chunk_map.materials();
+            let held_material = match &self.held_material {
+                Some(x) if materials.contains(x) => x.clone(),
+                _ => match materials.default_material() {
+                    Some(x) => x.group_key.clone(),
+                    None => {
+                        warn!("There is no material to place");
+                        return;
+                    }
+                },
+            };
+            self.held_material = Some(held_material.clone());
+
             ctx.chunk_map
-                .set_cube(position, "herbolution:stone");
+                .set_cube(position, held_material);
         }
     }
 
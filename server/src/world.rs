@@ -1,27 +1,164 @@
-use lib::save::SaveWorld;
+use std::sync::Arc;
+
+use crossbeam_channel::{Receiver, TryRecvError};
+use lib::point::CubePt;
+use lib::save::{PlayerData, SaveWorld};
+use lib::util::DisplayJoined;
+use lib::vector::{vec3d, Vec3};
 use time::Duration;
+use tracing::{error, info, warn};
 
 use crate::chunk::map::ChunkMap;
-use crate::entity::set::EntitySet;
+use crate::chunk::registry::MaterialRegistry;
+use crate::entity::components::ChunkLoader;
+use crate::entity::set::{EntityId, EntitySet};
+use crate::entity::Entity;
+use crate::generator::biome::{Biome, BiomeId, BiomeTable};
+use crate::generator::world_type::fixed_spawn;
 use crate::handle::ClientHandle;
+use crate::player::Player;
+
+const DEFAULT_SPAWN_POSITION: vec3d = Vec3::new(0.0, 96.0, 0.0);
 
 #[derive(Debug)]
 pub struct World {
     chunk_map: ChunkMap,
     pub(crate) entity_set: EntitySet,
+    save: SaveWorld,
+    spawn_search: Option<Receiver<Option<vec3d>>>,
 }
 
 impl World {
-    pub fn from_save(save: SaveWorld) -> Self {
+    pub fn from_save(mut save: SaveWorld, materials: Arc<MaterialRegistry>, biomes: Arc<BiomeTable>) -> Self {
+        let chunk_map = ChunkMap::new(save.path.clone(), &save.descriptor, materials, biomes);
+
+        let mut spawn_search = None;
+        if save.descriptor.spawn.is_none() {
+            match fixed_spawn(&save.descriptor.world_type) {
+                Some(spawn) => set_spawn(&mut save, Some(spawn)),
+                None => spawn_search = Some(chunk_map.request_spawn()),
+            }
+        }
+
         Self {
-            chunk_map: ChunkMap::new(save.descriptor.seed, save.path),
+            chunk_map,
             entity_set: EntitySet::new(),
+            save,
+            spawn_search,
+        }
+    }
+
+    /// Where players spawn when they first enter the world, or respawn. If the spawn point is still being searched
+    /// for, this waits for the search to finish.
+    pub fn spawn_position(&mut self) -> vec3d {
+        if let Some(receiver) = self.spawn_search.take() {
+            set_spawn(&mut self.save, receiver.recv().ok().flatten());
+        }
+
+        self.save
+            .descriptor
+            .spawn
+            .unwrap_or(DEFAULT_SPAWN_POSITION)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.save.name
+    }
+
+    pub fn biome_at(&self, position: impl Into<CubePt>) -> (BiomeId, &Biome) {
+        self.chunk_map.biome_at(position)
+    }
+
+    pub fn add_entity(&mut self, entity: Entity) -> EntityId {
+        self.entity_set.add(entity)
+    }
+
+    pub fn remove_entity(&mut self, id: EntityId, handle: &ClientHandle) -> Option<Entity> {
+        let mut entity = self.entity_set.remove(id)?;
+
+        if let Some(loader) = entity.behaviors.try_get_mut::<ChunkLoader>() {
+            loader.release(&self.chunk_map);
+            self.chunk_map.unload_requested(handle);
+        }
+
+        Some(entity)
+    }
+
+    pub fn save(&self) {
+        self.chunk_map.save();
+    }
+
+    pub fn has_players(&self) -> bool {
+        self.entity_set
+            .iter()
+            .any(|(_, x)| x.behaviors.contains::<Player>())
+    }
+
+    pub fn load_player(&self, name: &str) -> Option<PlayerData> {
+        match self.save.read_player(name) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to read player data for '{name}': {e}");
+                None
+            }
+        }
+    }
+
+    pub fn save_player(&mut self, name: &str, id: EntityId) {
+        let Some(entity) = self.entity_set.get_mut(id) else {
+            return;
+        };
+
+        let body = &entity.data.body;
+        let data = PlayerData {
+            position: body.position,
+            rotation: body.rotation,
+            health: entity.behaviors.get_mut::<Player>().health(),
+            has_gravity: body.attrs.has_gravity,
+            acceleration_rate: body.attrs.acceleration_rate,
+        };
+
+        if let Err(e) = self.save.write_player(name, &data) {
+            error!("Failed to write player data for '{name}': {e}");
         }
     }
 
     pub fn update(&mut self, handle: &ClientHandle, dt: Duration) {
+        let searched = self
+            .spawn_search
+            .as_ref()
+            .and_then(|receiver| match receiver.try_recv() {
+                Ok(spawn) => Some(spawn),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(None),
+            });
+        if let Some(spawn) = searched {
+            self.spawn_search = None;
+            set_spawn(&mut self.save, spawn);
+        }
+
         self.chunk_map.update(handle);
         self.entity_set
             .update(handle, &mut self.chunk_map, dt);
     }
 }
+
+/// Stores the spawn point of a world, so that it is not searched for again when the world is next loaded. A world
+/// without one found spawns players at the default position from then on.
+fn set_spawn(save: &mut SaveWorld, spawn: Option<vec3d>) {
+    let spawn = match spawn {
+        Some(spawn) => {
+            info!("Found spawn point of world '{}' at {}", save.name, spawn.display_joined(", "));
+            spawn
+        }
+        None => {
+            warn!("Failed to find a spawn point for world '{}', using the default", save.name);
+            DEFAULT_SPAWN_POSITION
+        }
+    };
+
+    save.descriptor.spawn = Some(spawn);
+    if let Err(e) = save.write_descriptor() {
+        error!("Failed to save spawn point of world '{}': {}", save.name, e);
+    }
+}
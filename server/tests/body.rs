@@ -0,0 +1,221 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::time::Instant;
+
+use lib::point::ChunkPt;
+use lib::size::Size3;
+use lib::vector::{vec3d, Vec3};
+use server::chunk::map::ChunkMap;
+use server::entity::body::{Bounds, EntityAttrs, EntityBody};
+use server::handle::{self, ClientHandle};
+use time::Duration;
+
+use crate::common::{void_map, TempDir};
+
+mod common;
+
+const STEP: Duration = Duration::milliseconds(10);
+/// The height of the floor that the tests build on, which is the first layer of the loaded chunk.
+const FLOOR_Y: i32 = 0;
+
+/// An empty void world with a single chunk loaded around the origin.
+struct Scene {
+    map: ChunkMap,
+    handle: ClientHandle,
+    // Dropped last, once the map is done with it.
+    _dir: TempDir,
+}
+
+impl Scene {
+    fn new(name: &str) -> Self {
+        let dir = TempDir::new(name);
+        let mut map = void_map(&dir);
+        let (handle, _game_handle) = handle::create();
+
+        let position = ChunkPt(Vec3::ZERO);
+        map.queue_load(position);
+        let start = Instant::now();
+        while map.get_chunk(position).is_none() {
+            assert!(start.elapsed().as_secs() < 30, "the chunk was not loaded in time");
+            map.update(&handle);
+        }
+
+        Self { map, handle, _dir: dir }
+    }
+
+    /// Fills the floor from `min` to `max` on the x and z axes with a material.
+    fn floor(&mut self, material: &str, min: (i32, i32), max: (i32, i32)) {
+        for x in min.0..=max.0 {
+            for z in min.1..=max.1 {
+                self.map
+                    .set_cube(Vec3::new(x, FLOOR_Y, z), format!("herbolution:{material}").as_str());
+            }
+        }
+    }
+
+    fn set_cube(&mut self, x: i32, y: i32, z: i32, material: &str) {
+        self.map
+            .set_cube(Vec3::new(x, y, z), format!("herbolution:{material}").as_str());
+    }
+
+    /// Steps a body for a number of seconds.
+    fn run(&mut self, body: &mut EntityBody, seconds: f64) {
+        for _ in 0..(seconds / STEP.as_seconds_f64()).round() as usize {
+            body.update(&mut self.map, STEP);
+        }
+        self.map.update(&self.handle);
+    }
+}
+
+fn player(position: vec3d) -> EntityBody {
+    EntityBody::new(
+        position,
+        Bounds {
+            size: Size3::new(0.9, 1.9, 0.9),
+            eye_offset: Vec3::new(0.0, 1.0, 0.0),
+        },
+        EntityAttrs {
+            has_gravity: true,
+            acceleration_rate: 20.0,
+            terminal_velocity: 100.0,
+        },
+    )
+}
+
+fn horizontal_distance(a: vec3d, b: vec3d) -> f64 {
+    ((a.x - b.x).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+/// How far a body slides on the floor after walking for a second and letting go.
+fn slide_distance(scene: &mut Scene, start: vec3d) -> f64 {
+    let mut body = player(start);
+    scene.run(&mut body, 0.5);
+
+    body.set_motion(Vec3::new(1.0, 0.0, 0.0));
+    scene.run(&mut body, 1.0);
+    body.set_motion(Vec3::ZERO);
+
+    let released = body.position();
+    scene.run(&mut body, 3.0);
+    horizontal_distance(released, body.position())
+}
+
+#[test]
+fn slippery_ground_slides_further() {
+    let mut scene = Scene::new("friction");
+    scene.floor("stone", (0, 0), (31, 15));
+    scene.floor("ice", (0, 16), (31, 31));
+
+    let on_stone = slide_distance(&mut scene, Vec3::new(2.05, 1.0, 4.05));
+    let on_ice = slide_distance(&mut scene, Vec3::new(2.05, 1.0, 24.05));
+
+    assert!(on_ice > on_stone * 4.0, "slid {on_ice} on ice and {on_stone} on stone");
+}
+
+#[test]
+fn ground_is_felt_under_the_whole_footprint() {
+    let mut scene = Scene::new("footprint");
+    // A single row of ice, which the body stands on with its center over the gap next to it.
+    scene.floor("ice", (0, 16), (31, 16));
+    scene.floor("stone", (0, 4), (31, 4));
+
+    let on_ice_edge = slide_distance(&mut scene, Vec3::new(2.05, 1.0, 16.6));
+    let on_stone_edge = slide_distance(&mut scene, Vec3::new(2.05, 1.0, 4.6));
+
+    assert!(
+        on_ice_edge > on_stone_edge * 4.0,
+        "slid {on_ice_edge} on the ice edge and {on_stone_edge} on the stone edge"
+    );
+}
+
+#[test]
+fn bouncy_ground_bounces_bodies_back_up() {
+    let mut scene = Scene::new("bounce");
+    scene.floor("stone", (0, 0), (15, 31));
+    scene.floor("slime", (16, 0), (31, 31));
+
+    let mut highest_bounce = |x: f64| {
+        let mut body = player(Vec3::new(x, 8.0, 8.05));
+        let mut has_landed = false;
+        let mut highest = f64::MIN;
+        for _ in 0..300 {
+            body.update(&mut scene.map, STEP);
+            has_landed |= body.position().y < 1.0 + f64::EPSILON;
+            if has_landed {
+                highest = highest.max(body.position().y);
+            }
+        }
+        highest
+    };
+
+    let on_stone = highest_bounce(4.05);
+    let on_slime = highest_bounce(24.05);
+
+    assert!(on_stone < 1.0 + f64::EPSILON, "bounced up to {on_stone} from stone");
+    assert!(on_slime > 2.0, "bounced up to {on_slime} from slime");
+}
+
+#[test]
+fn slow_ground_and_fluids_slow_bodies_down() {
+    let mut scene = Scene::new("speed");
+    scene.floor("stone", (0, 0), (31, 9));
+    scene.floor("slime", (0, 10), (31, 19));
+    scene.floor("stone", (0, 20), (31, 31));
+    for x in 0..32 {
+        for z in 20..32 {
+            scene.set_cube(x, FLOOR_Y + 1, z, "water");
+            scene.set_cube(x, FLOOR_Y + 2, z, "water");
+        }
+    }
+
+    let mut walk_distance = |z: f64| {
+        let mut body = player(Vec3::new(2.05, 1.0, z));
+        scene.run(&mut body, 0.5);
+        let start = body.position();
+
+        body.set_motion(Vec3::new(1.0, 0.0, 0.0));
+        scene.run(&mut body, 0.5);
+        horizontal_distance(start, body.position())
+    };
+
+    let on_stone = walk_distance(4.05);
+    let on_slime = walk_distance(14.05);
+    let in_water = walk_distance(25.05);
+
+    assert!(on_slime < on_stone * 0.7, "walked {on_slime} on slime and {on_stone} on stone");
+    assert!(in_water < on_stone * 0.6, "walked {in_water} in water and {on_stone} on stone");
+}
+
+#[test]
+fn ladders_are_climbed_while_jumping() {
+    let mut scene = Scene::new("climb");
+    scene.floor("stone", (0, 0), (31, 31));
+    for y in FLOOR_Y + 1..FLOOR_Y + 12 {
+        scene.set_cube(8, y, 8, "ladder");
+    }
+
+    let mut body = player(Vec3::new(8.05, 1.0, 8.05));
+    scene.run(&mut body, 0.5);
+
+    body.set_motion(Vec3::new(0.0, 1.0, 0.0));
+    scene.run(&mut body, 1.0);
+    let climbed = body.position().y;
+    assert!(climbed > 4.0, "climbed to {climbed}");
+
+    // Letting go slides slowly back down rather than falling.
+    body.set_motion(Vec3::ZERO);
+    scene.run(&mut body, 0.25);
+    assert!(body.velocity().y >= -4.0 - f64::EPSILON);
+    assert!(body.position().y < climbed);
+}
+
+#[test]
+fn bodies_wait_for_their_chunk_to_be_loaded() {
+    let mut scene = Scene::new("unloaded");
+
+    let start = Vec3::new(40.05, 5.0, 4.05);
+    let mut body = player(start);
+    scene.run(&mut body, 0.5);
+    assert_eq!(body.position(), start);
+}
@@ -0,0 +1,261 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use fastrand::Rng;
+use lib::save::ChunkCompression;
+use lib::util::crc32;
+use lib::vector::vec3u5;
+use lib::world::CHUNK_VOLUME;
+use server::chunk::codec::{CubeGrid, DecodeError, FORMAT_VERSION, MAGIC};
+use server::chunk::compression;
+use server::chunk::material::{Material, MaterialPhysics, Palette, PaletteMaterialId, Texture};
+
+use crate::common::materials;
+
+mod common;
+
+fn random_grid(rng: &mut Rng) -> CubeGrid {
+    let mut palette = Palette::new();
+    let mut ids = vec![None];
+    for material in materials().iter() {
+        if rng.bool() {
+            ids.push(Some(palette.insert(material.clone())));
+        }
+    }
+
+    let mut grid = CubeGrid::new(palette);
+
+    let mut index = 0;
+    while index < CHUNK_VOLUME {
+        let material = ids[rng.usize(..ids.len())];
+        let run = match rng.u8(..3) {
+            0 => 1,
+            1 => rng.usize(1..64),
+            _ => rng.usize(1..2048),
+        };
+
+        for i in index..(index + run).min(CHUNK_VOLUME) {
+            grid.set(vec3u5::delinearize(i), material);
+        }
+        index += run;
+    }
+
+    grid
+}
+
+const COMPRESSIONS: [ChunkCompression; 3] = [ChunkCompression::None, ChunkCompression::Rle, ChunkCompression::Lz];
+
+fn encode(grid: &CubeGrid) -> Vec<u8> {
+    encode_with(grid, ChunkCompression::default())
+}
+
+fn encode_with(grid: &CubeGrid, compression: ChunkCompression) -> Vec<u8> {
+    let mut buf = vec![];
+    grid.encode(compression, &mut buf);
+    buf
+}
+
+fn assert_grids_eq(a: &CubeGrid, b: &CubeGrid) {
+    assert!(
+        a.palette()
+            .materials()
+            .eq(b.palette().materials())
+    );
+
+    for i in 0..CHUNK_VOLUME {
+        let position = vec3u5::delinearize(i);
+        assert_eq!(a.get(position), b.get(position), "cube {i} differs");
+    }
+}
+
+fn with_checksum(mut bytes: Vec<u8>) -> Vec<u8> {
+    let checksum = crc32(&bytes);
+    bytes.extend(checksum.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn round_trip_random_grids() {
+    let mut rng = Rng::with_seed(0x4865_7262);
+
+    for _ in 0..64 {
+        let grid = random_grid(&mut rng);
+
+        for compression in COMPRESSIONS {
+            let decoded = CubeGrid::decode(&encode_with(&grid, compression)).unwrap();
+            assert_grids_eq(&grid, &decoded);
+        }
+    }
+}
+
+#[test]
+fn round_trip_uniform_grids() {
+    let empty = CubeGrid::new(Palette::new());
+
+    let mut palette = Palette::new();
+    let stone = palette.insert(materials().get("herbolution:stone").unwrap().clone());
+    let mut full = CubeGrid::new(palette);
+    for i in 0..CHUNK_VOLUME {
+        full.set(vec3u5::delinearize(i), Some(stone));
+    }
+
+    for compression in COMPRESSIONS {
+        assert_grids_eq(&empty, &CubeGrid::decode(&encode_with(&empty, compression)).unwrap());
+        assert_grids_eq(&full, &CubeGrid::decode(&encode_with(&full, compression)).unwrap());
+    }
+}
+
+#[test]
+fn round_trip_noisy_grid() {
+    let mut rng = Rng::with_seed(0x4c5a);
+    let mut palette = Palette::new();
+    let ids: Vec<_> = materials()
+        .iter()
+        .map(|material| Some(palette.insert(material.clone())))
+        .chain([None])
+        .collect();
+
+    let mut grid = CubeGrid::new(palette);
+    for i in 0..CHUNK_VOLUME {
+        grid.set(vec3u5::delinearize(i), ids[rng.usize(..ids.len())]);
+    }
+
+    for compression in COMPRESSIONS {
+        assert_grids_eq(&grid, &CubeGrid::decode(&encode_with(&grid, compression)).unwrap());
+    }
+}
+
+#[test]
+fn round_trip_image_textures() {
+    let grass = materials().get("herbolution:grass").unwrap().clone();
+    assert!(matches!(&grass.texture, Texture::Image { top, bottom, .. } if top == "grass_top" && bottom == "dirt"));
+
+    let mut buf = vec![];
+    grass.encode(&mut buf);
+    assert_eq!(Material::decode(&mut buf.into_iter()).as_ref(), Some(grass.as_ref()));
+}
+
+#[test]
+fn round_trip_material_physics() {
+    let materials = materials();
+    for key in ["herbolution:ice", "herbolution:slime", "herbolution:ladder", "herbolution:stone"] {
+        let material = materials.get(key).unwrap();
+
+        let mut buf = vec![];
+        material.encode(&mut buf);
+        assert_eq!(Material::decode(&mut buf.into_iter()).as_ref(), Some(material.as_ref()));
+    }
+
+    // Materials with the default physics are encoded as they were before physics existed.
+    let stone = materials.get("herbolution:stone").unwrap();
+    assert_eq!(stone.physics, MaterialPhysics::default());
+    let mut buf = vec![];
+    stone.encode(&mut buf);
+    assert_eq!(buf[2 + "herbolution".len() + "stone".len()] >> 7, 0);
+}
+
+#[test]
+fn version_1_files_are_read_as_rle() {
+    let grid = random_grid(&mut Rng::with_seed(5));
+
+    let mut bytes = encode_with(&grid, ChunkCompression::Rle);
+    bytes.truncate(bytes.len() - 4);
+    bytes.remove(6);
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+
+    assert_grids_eq(&grid, &CubeGrid::decode(&with_checksum(bytes)).unwrap());
+}
+
+#[test]
+fn unknown_codec_is_rejected() {
+    let mut bytes = encode(&random_grid(&mut Rng::with_seed(6)));
+    bytes[6] = 0xEE;
+
+    assert!(matches!(CubeGrid::decode(&bytes), Err(DecodeError::UnknownCodec(0xEE))));
+}
+
+#[test]
+fn invalid_lz_body_is_rejected() {
+    let mut body = vec![];
+    body.push(0x10);
+    body.push(0);
+    body.extend(2u16.to_le_bytes());
+
+    let mut cubes = vec![0; CHUNK_VOLUME];
+    assert!(matches!(
+        compression::decode(ChunkCompression::Lz, &body, &mut cubes),
+        Err(DecodeError::InvalidBody(ChunkCompression::Lz))
+    ));
+}
+
+#[test]
+fn round_trip_through_mesh() {
+    let mut rng = Rng::with_seed(7);
+    let grid = random_grid(&mut rng);
+    let mesh = grid.to_mesh(lib::point::ChunkPt::ZERO);
+
+    assert_grids_eq(&grid, &CubeGrid::from_mesh(&mesh));
+}
+
+#[test]
+fn truncated_data_is_rejected() {
+    let bytes = encode(&random_grid(&mut Rng::with_seed(1)));
+
+    for len in (0..bytes.len()).step_by(7).chain([bytes.len() - 1]) {
+        assert!(matches!(CubeGrid::decode(&bytes[..len]), Err(DecodeError::Truncated)), "length {len} was accepted");
+    }
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut bytes = encode(&random_grid(&mut Rng::with_seed(2)));
+    bytes[0] = b'X';
+
+    assert!(matches!(CubeGrid::decode(&bytes), Err(DecodeError::BadMagic)));
+}
+
+#[test]
+fn bad_version_is_rejected() {
+    let mut bytes = encode(&random_grid(&mut Rng::with_seed(3)));
+    bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+    assert!(matches!(CubeGrid::decode(&bytes), Err(DecodeError::BadVersion(v)) if v == FORMAT_VERSION + 1));
+}
+
+#[test]
+fn corrupted_data_fails_checksum() {
+    let mut bytes = encode(&random_grid(&mut Rng::with_seed(4)));
+    let index = bytes.len() - 8;
+    bytes[index] ^= 0x5A;
+
+    assert!(matches!(CubeGrid::decode(&bytes), Err(DecodeError::ChecksumMismatch { .. })));
+}
+
+#[test]
+fn unknown_material_is_rejected() {
+    let materials = materials();
+    let mut small = Palette::new();
+    small.insert(materials.get("herbolution:stone").unwrap().clone());
+
+    let mut large = small.clone();
+    let dirt: PaletteMaterialId = large.insert(materials.get("herbolution:dirt").unwrap().clone());
+
+    let mut grid = CubeGrid::new(small);
+    grid.set(vec3u5::ZERO, Some(dirt));
+
+    assert!(matches!(CubeGrid::decode(&encode(&grid)), Err(DecodeError::UnknownMaterial(2))));
+}
+
+#[test]
+fn short_body_is_rejected() {
+    let mut bytes = vec![];
+    bytes.extend(MAGIC);
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+    bytes.push(compression::tag(ChunkCompression::Rle));
+    bytes.extend(2u32.to_le_bytes());
+    bytes.extend(0u16.to_le_bytes());
+    bytes.extend(3u32.to_le_bytes());
+    bytes.extend([255, 0, 0]);
+
+    assert!(matches!(CubeGrid::decode(&with_checksum(bytes)), Err(DecodeError::CubeCount(255))));
+}
@@ -0,0 +1,63 @@
+// Each test file only uses some of these.
+#![allow(dead_code)]
+
+use std::fs::{create_dir_all, remove_dir_all};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use lib::save::WorldDescriptor;
+use server::chunk::map::ChunkMap;
+use server::chunk::registry::MaterialRegistry;
+use server::generator::biome::BiomeTable;
+
+/// An empty directory that is unique to the test, and is removed with everything in it once dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("herbolution-{}-{name}-{}", env!("CARGO_CRATE_NAME"), std::process::id()));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+pub fn assets_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets")
+}
+
+/// The bundled materials.
+pub fn materials() -> MaterialRegistry {
+    MaterialRegistry::load(&assets_path().join("material")).unwrap()
+}
+
+/// The descriptor of an empty void world.
+pub fn void_descriptor(title: &str) -> WorldDescriptor {
+    toml::from_str(&format!("title = \"{title}\"\nseed = 1\n\n[world_type]\nkind = \"void\"\n")).unwrap()
+}
+
+/// The chunk map of an empty void world that is saved in `dir`.
+pub fn void_map(dir: &Path) -> ChunkMap {
+    ChunkMap::new(dir.to_path_buf(), &void_descriptor("Test"), Arc::new(materials()), Arc::new(BiomeTable::default()))
+}
@@ -0,0 +1,168 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::fs::{create_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use lib::rotation::Euler;
+use lib::save::{PlayerData, Save, SaveAttributes, SaveWorld, WorldAttributes};
+use lib::vector::{vec3d, Vec3};
+use lib::world::Health;
+use server::handle::GameHandle;
+use server::player::ServerPlayerHandle;
+use server::{Game, GameError, Options};
+use time::Duration;
+
+use crate::common::{assets_path, void_descriptor, TempDir};
+
+mod common;
+
+fn void_world(name: &str) -> WorldAttributes {
+    WorldAttributes {
+        name: name.to_string(),
+        descriptor: void_descriptor(name),
+    }
+}
+
+fn create_save(path: PathBuf) -> Save {
+    Save::create(
+        &path,
+        SaveAttributes {
+            title: "Test".to_string(),
+            default_world: void_world("overworld"),
+        },
+    )
+    .unwrap()
+}
+
+/// Waits for the game to send the player handle, and fails if it is never sent.
+fn wait_for_player(handle: &GameHandle) -> ServerPlayerHandle {
+    let start = Instant::now();
+    loop {
+        if let Some(player) = handle.next_player_handle() {
+            return player;
+        }
+        assert!(start.elapsed().as_secs() < 30, "the game did not start in time");
+        std::thread::yield_now();
+    }
+}
+
+/// Waits until the player is at a position, and fails if it never gets there.
+fn wait_for_position(handle: &ServerPlayerHandle, position: vec3d) {
+    let start = Instant::now();
+    loop {
+        let current = handle
+            .state
+            .load()
+            .as_ref()
+            .map(|x| x.position);
+        if current.is_some_and(|x| (x - position).length() < 0.01) {
+            return;
+        }
+
+        assert!(start.elapsed().as_secs() < 30, "the player is at {current:?} rather than {position:?}");
+        std::thread::yield_now();
+    }
+}
+
+fn read_position(world_path: &Path) -> vec3d {
+    let data: PlayerData = toml::from_str(&std::fs::read_to_string(world_path.join("players/local.toml")).unwrap()).unwrap();
+    data.position
+}
+
+#[test]
+fn malformed_materials_stop_the_game_from_starting() {
+    let root = TempDir::new("materials");
+    create_dir_all(root.join("assets/material")).unwrap();
+    write(root.join("assets/material/broken.toml"), "light = \"bright\"").unwrap();
+
+    let handle = Game::spawn(Options {
+        save: create_save(root.join("save")),
+        autosave_interval: Duration::minutes(5),
+        assets_path: root.join("assets"),
+        world: None,
+    });
+    handle.wait_for_exit();
+
+    assert!(matches!(handle.failure(), Some(GameError::Materials(_))));
+    assert!(handle.next_player_handle().is_none());
+}
+
+fn write_floating_player(world: &SaveWorld, position: vec3d) {
+    world
+        .write_player(
+            "local",
+            &PlayerData {
+                position,
+                rotation: Euler::IDENTITY,
+                health: Health::new(100.0),
+                has_gravity: false,
+                acceleration_rate: 20.0,
+            },
+        )
+        .unwrap();
+}
+
+#[test]
+fn players_start_in_the_chosen_world() {
+    let root = TempDir::new("start-world");
+    let save = create_save(root.join("save"));
+    let nether = save.create_world(void_world("nether")).unwrap();
+    let overworld_path = save.default_world().unwrap().path;
+    let nether_position = Vec3::new(3.5, 10.0, 2.5);
+    write_floating_player(&nether, nether_position);
+
+    let handle = Game::spawn(Options {
+        save,
+        autosave_interval: Duration::minutes(5),
+        assets_path: assets_path(),
+        world: Some("nether".to_string()),
+    });
+    let player = wait_for_player(&handle);
+    wait_for_position(&player, nether_position);
+
+    handle.request_exit();
+    handle.wait_for_exit();
+    assert!(handle.failure().is_none());
+    // The player never entered the default world.
+    assert!(!overworld_path.join("players/local.toml").exists());
+    assert!((read_position(&nether.path) - nether_position).length() < 0.01);
+}
+
+#[test]
+fn switching_worlds_moves_the_player_and_keeps_its_data_per_world() {
+    let root = TempDir::new("switch");
+    let save = create_save(root.join("save"));
+    let nether = save.create_world(void_world("nether")).unwrap();
+    let overworld_path = save.default_world().unwrap().path;
+
+    // The player floats where it was in the nether, and falls onto the platform of the overworld.
+    let nether_position = Vec3::new(3.5, 10.0, 2.5);
+    write_floating_player(&nether, nether_position);
+    let spawn = Vec3::new(0.05, 0.0, 0.05);
+
+    let handle = Game::spawn(Options {
+        save,
+        autosave_interval: Duration::minutes(5),
+        assets_path: assets_path(),
+        world: None,
+    });
+    let player = wait_for_player(&handle);
+    wait_for_position(&player, spawn);
+
+    handle.request_world("nether");
+    wait_for_position(&player, nether_position);
+    assert!((read_position(&overworld_path) - spawn).length() < 0.01);
+
+    // Worlds that do not exist leave the player where it is.
+    handle.request_world("missing");
+    handle.request_world("overworld");
+    wait_for_position(&player, spawn);
+    assert!((read_position(&nether.path) - nether_position).length() < 0.01);
+
+    handle.request_exit();
+    handle.wait_for_exit();
+    assert!(handle.failure().is_none());
+    assert!((read_position(&overworld_path) - spawn).length() < 0.01);
+}
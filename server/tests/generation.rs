@@ -0,0 +1,554 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use fastrand::Rng;
+use lib::point::{ChunkCubePt, ChunkPt, CubePt};
+use lib::save::{GeneratorConfig, TerrainMode, WorldDescriptor, WorldType};
+use lib::spatial::CubeFace;
+use lib::vector::{vec3u5, Vec2, Vec3};
+use lib::world::{CHUNK_LENGTH, CHUNK_VOLUME};
+use server::chunk::codec::CubeGrid;
+use server::chunk::light::{LightChannel, MAX_LIGHT};
+use server::chunk::material::Material;
+use server::chunk::mesh::CubeMesh;
+use server::chunk::pending::{self, PendingWrite, PendingWrites};
+use server::chunk::provider::ChunkProvider;
+use server::chunk::registry::MaterialRegistry;
+use server::generator::biome::BiomeTable;
+use server::generator::column::ColumnCache;
+use server::generator::feature::{Feature, FeatureWriter, Tree};
+use server::generator::stage::{GenerationPipeline, NeighbourContext, SurfacePaint, TerrainShape};
+use server::generator::world_type::fixed_spawn;
+use server::generator::{ChunkGenerator, GenerationParams};
+
+use crate::common::{assets_path, materials, TempDir};
+
+mod common;
+
+const SEED: i64 = 0x4865_7262;
+
+fn biomes() -> BiomeTable {
+    BiomeTable::load(&assets_path().join("biome")).unwrap()
+}
+
+fn params(terrain: TerrainMode, biomes: BiomeTable) -> GenerationParams {
+    params_with_config(terrain, GeneratorConfig::default(), biomes)
+}
+
+fn params_with_config(terrain: TerrainMode, config: GeneratorConfig, biomes: BiomeTable) -> GenerationParams {
+    GenerationParams::new(SEED, terrain, config, Arc::new(materials()), Arc::new(biomes))
+}
+
+fn generate(params: &GenerationParams, position: ChunkPt) -> CubeGrid {
+    let mut mesh = CubeMesh::new(position);
+    params.generate(&mut mesh);
+    CubeGrid::from_mesh(&mesh)
+}
+
+#[test]
+fn bundled_biomes_load() {
+    let biomes = biomes();
+
+    assert!(biomes.get_by_name("plains").is_some());
+    assert!(biomes.get_by_name("desert").is_some());
+}
+
+#[test]
+fn bundled_materials_load() {
+    let materials = materials();
+
+    assert!(materials.get("herbolution:stone").is_some());
+    assert!(materials.get("herbolution:diamond_ore").is_some());
+    assert!(biomes().validate(&materials).is_ok());
+}
+
+#[test]
+fn the_default_material_falls_back_to_a_solid_one() {
+    let materials = materials();
+    assert_eq!(materials.default_material().unwrap().group_key.to_string(), "herbolution:stone");
+
+    let mut without_stone = MaterialRegistry::default();
+    for material in materials.iter().filter(|x| x.group_key.to_string() != "herbolution:stone") {
+        without_stone.insert(Material::clone(material));
+    }
+    assert!(without_stone.default_material().unwrap().has_collider);
+    assert!(MaterialRegistry::default().default_material().is_none());
+}
+
+#[test]
+fn material_overrides_replace_by_key() {
+    let dir = TempDir::new("materials");
+    std::fs::write(dir.join("stone.toml"), "toughness = 9.0\ncolors = [[1.0, 0.0, 0.0]]\n").unwrap();
+    std::fs::write(dir.join("glow.toml"), "has_collider = false\ntoughness = 0.0\ncolors = [[1.0, 1.0, 0.5]]\n").unwrap();
+
+    let mut materials = materials();
+    let len = materials.len();
+    materials.load_overrides(&dir).unwrap();
+
+    assert_eq!(materials.len(), len + 1);
+    assert_eq!(materials.get("herbolution:stone").unwrap().toughness, 9.0);
+    assert!(!materials.get("herbolution:glow").unwrap().has_collider);
+
+    std::fs::write(dir.join("broken.toml"), "toughness = -1.0\ncolors = [[1.0, 1.0, 1.0]]\n").unwrap();
+    let error = materials.load_overrides(&dir).unwrap_err().to_string();
+    assert!(error.contains("broken.toml"));
+    assert!(error.contains("toughness"));
+}
+
+#[test]
+fn blending_is_continuous_across_borders() {
+    let biomes = biomes();
+
+    let steps = 2000;
+    let mut prev = biomes.blend(-1.0, -1.0);
+    for i in 1..=steps {
+        let t = -1.0 + 2.0 * i as f32 / steps as f32;
+        let blend = biomes.blend(t, t);
+
+        assert!((blend.height_offset - prev.height_offset).abs() < 1.0);
+        assert!((blend.height_scale - prev.height_scale).abs() < 4.0);
+        prev = blend;
+    }
+}
+
+#[test]
+fn surface_matches_queried_biome() {
+    let params = params(TerrainMode::Heightmap, biomes());
+    let mut seen = vec![];
+
+    for cx in -4..4 {
+        for cz in -4..4 {
+            let position = Vec3::new(cx * 64, 0, cz * 64);
+            let (id, biome) = params.biome_at(position.x * CHUNK_LENGTH as i32, position.z * CHUNK_LENGTH as i32);
+            if !seen.contains(&id) {
+                seen.push(id);
+            }
+
+            let surface = (-8..12).rev().find_map(|cy| {
+                let grid = generate(&params, ChunkPt(Vec3::new(position.x, cy, position.z)));
+                (0..CHUNK_LENGTH as u8)
+                    .rev()
+                    .find_map(|y| grid.get(vec3u5::new(0, y, 0)))
+                    .map(|id| grid.palette().get_by_id(id).unwrap().clone())
+            });
+
+            assert_eq!(surface.unwrap().group_key.as_str(), biome.surface);
+        }
+    }
+
+    assert!(seen.len() > 1);
+}
+
+#[test]
+fn generation_is_deterministic() {
+    for terrain in [TerrainMode::Heightmap, TerrainMode::Density] {
+        let params = params(terrain, biomes());
+
+        for position in [ChunkPt(Vec3::new(0, 0, 0)), ChunkPt(Vec3::new(-3, 1, 7))] {
+            let a = generate(&params, position);
+            let b = generate(&params, position);
+
+            for i in 0..CHUNK_VOLUME {
+                assert_eq!(a.get(vec3u5::delinearize(i)), b.get(vec3u5::delinearize(i)));
+            }
+        }
+    }
+}
+
+/// Counts the air under the topmost cube of each column around the origin, returning the number
+/// of buried air cubes and the number of gaps that are at least three cubes tall.
+fn count_buried_air(params: &GenerationParams) -> (usize, usize) {
+    let mut buried_air = 0;
+    let mut tall_gaps = 0;
+    for cx in -2..2 {
+        for cz in -2..2 {
+            let grids: Vec<_> = (-3..3)
+                .map(|cy| generate(params, ChunkPt(Vec3::new(cx, cy, cz))))
+                .collect();
+            let is_solid = |y: usize, x: u8, z: u8| grids[y / CHUNK_LENGTH].get(vec3u5::new(x, (y % CHUNK_LENGTH) as u8, z)).is_some();
+
+            for x in 0..CHUNK_LENGTH as u8 {
+                for z in 0..CHUNK_LENGTH as u8 {
+                    let column: Vec<_> = (0..grids.len() * CHUNK_LENGTH)
+                        .map(|y| is_solid(y, x, z))
+                        .collect();
+                    let Some(top) = column.iter().rposition(|&x| x) else {
+                        continue;
+                    };
+
+                    let mut gap = 0;
+                    for &is_solid in &column[..=top] {
+                        if !is_solid {
+                            buried_air += 1;
+                            gap += 1;
+                        } else {
+                            if gap >= 3 {
+                                tall_gaps += 1;
+                            }
+                            gap = 0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    (buried_air, tall_gaps)
+}
+
+#[test]
+fn density_terrain_has_caves_and_overhangs() {
+    let (buried_air, tall_gaps) = count_buried_air(&params(TerrainMode::Density, biomes()));
+    assert!(buried_air >= 4096, "only {buried_air} cubes of air were buried");
+    assert!(tall_gaps >= 64, "only {tall_gaps} gaps were tall enough to stand in");
+
+    let (buried_air, tall_gaps) = count_buried_air(&params(TerrainMode::Heightmap, biomes()));
+    assert_eq!((buried_air, tall_gaps), (0, 0), "heightmap terrain buried air");
+}
+
+#[test]
+fn ore_placement_is_deterministic_and_replaces_stone() {
+    let params = params(TerrainMode::Heightmap, biomes());
+    let position = ChunkPt(Vec3::new(2, -6, -5));
+
+    let place = || {
+        let mut mesh = CubeMesh::new(position);
+        params.generate(&mut mesh);
+        let before = CubeGrid::from_mesh(&mesh);
+        params.place_ores(&mut mesh);
+        (before, CubeGrid::from_mesh(&mesh))
+    };
+
+    let (before, a) = place();
+    let (_, b) = place();
+
+    let mut ores = 0;
+    for i in 0..CHUNK_VOLUME {
+        let position = vec3u5::delinearize(i);
+        assert_eq!(a.get(position), b.get(position));
+
+        if a.get(position) != before.get(position) {
+            let replaced = before.palette().get_by_id(before.get(position).unwrap()).unwrap();
+            assert_eq!(replaced.group_key.as_str(), "herbolution:stone");
+            ores += 1;
+        }
+    }
+
+    assert!(ores > 0);
+}
+
+#[test]
+fn ores_with_unknown_materials_are_left_out() {
+    let dir = TempDir::new("ores");
+    std::fs::create_dir_all(dir.join("material")).unwrap();
+    let materials_path = assets_path().join("material");
+    for entry in std::fs::read_dir(&materials_path).unwrap() {
+        let name = entry.unwrap().file_name();
+        if name != "gold_ore.toml" && name != "diamond_ore.toml" {
+            std::fs::copy(materials_path.join(&name), dir.join("material").join(&name)).unwrap();
+        }
+    }
+    let materials = MaterialRegistry::load(&dir.join("material")).unwrap();
+
+    let descriptor: WorldDescriptor = toml::from_str("title = \"Ores\"\nseed = 1\n").unwrap();
+    let provider = ChunkProvider::new(dir.join("world"), &descriptor, Arc::new(materials), Arc::new(biomes()));
+    let position = ChunkPt(Vec3::new(2, -6, -5));
+    provider.request(position);
+
+    let start = Instant::now();
+    let mesh = loop {
+        if let Some(mesh) = provider.dequeue().next() {
+            break mesh;
+        }
+        assert!(start.elapsed() < Duration::from_secs(30), "the chunk was never generated");
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    let grid = CubeGrid::from_mesh(&mesh);
+    let keys = (0..CHUNK_VOLUME)
+        .filter_map(|i| grid.get(vec3u5::delinearize(i)))
+        .map(|id| grid.palette().get_by_id(id).unwrap().group_key.as_str().to_string())
+        .collect::<HashSet<_>>();
+    assert!(keys.iter().any(|x| x.ends_with("_ore")));
+    assert!(!keys.contains("herbolution:gold_ore"));
+    assert!(!keys.contains("herbolution:diamond_ore"));
+}
+
+#[test]
+fn features_queue_cubes_for_neighbouring_chunks() {
+    let materials = materials();
+    let mut mesh = CubeMesh::new(ChunkPt(Vec3::ZERO));
+    let mut writer = FeatureWriter::new(&mut mesh, &materials);
+    Tree::default().place(&mut writer, Vec3::new(31, 4, 31), &mut Rng::with_seed(7));
+    let outside = writer.into_outside();
+
+    assert!(mesh.get(vec3u5::new(31, 4, 31)).is_some());
+    assert!(!outside.is_empty());
+    assert!(outside.iter().all(|(position, _)| {
+        let ChunkCubePt { chunk, .. } = (*position).into();
+        chunk != ChunkPt(Vec3::ZERO)
+    }));
+
+    let dir = TempDir::new("pending");
+    let pending = PendingWrites::new(dir.join("pending"));
+    pending.extend(outside);
+    pending.save().unwrap();
+
+    let reopened = PendingWrites::open(dir.join("pending"));
+    let neighbour = ChunkPt(Vec3::new(1, 0, 1));
+    let writes = reopened.take(neighbour);
+    assert_eq!(writes, pending.take(neighbour));
+
+    let mut neighbour_mesh = CubeMesh::new(neighbour);
+    assert!(pending::apply(&mut neighbour_mesh, writes, &materials));
+    let leaves = (0..CHUNK_VOLUME)
+        .filter(|&i| neighbour_mesh.get(vec3u5::delinearize(i)).is_some())
+        .count();
+    assert!(leaves > 0);
+}
+
+#[test]
+fn pending_writes_are_saved_per_chunk() {
+    let dir = TempDir::new("pending-chunks");
+    let chunk = ChunkPt(Vec3::ZERO);
+    let other = ChunkPt(Vec3::new(-1, 2, 0));
+
+    let pending = PendingWrites::new(dir.to_path_buf());
+    pending.push(CubePt(Vec3::new(1, 2, 3)), "herbolution:leaves".to_string());
+    pending.push(CubePt(Vec3::new(1, 2, 3)), "herbolution:log".to_string());
+    pending.push(CubePt(Vec3::new(4, 5, 6)), "herbolution:log".to_string());
+    pending.push(CubePt(Vec3::new(-1, 64, 0)), "herbolution:leaves".to_string());
+    pending.save().unwrap();
+
+    // Writes to cubes that already have one are dropped, so saving them again leaves the file as it is.
+    let region_len = || std::fs::metadata(dir.join("0.0.0.region")).unwrap().len();
+    let len = region_len();
+    pending.push(CubePt(Vec3::new(4, 5, 6)), "herbolution:stone".to_string());
+    pending.save().unwrap();
+    assert_eq!(region_len(), len);
+
+    let reopened = PendingWrites::open(dir.to_path_buf());
+    assert_eq!(
+        reopened.take(chunk),
+        vec![
+            PendingWrite { local: vec3u5::new(1, 2, 3), material: "herbolution:leaves".to_string() },
+            PendingWrite { local: vec3u5::new(4, 5, 6), material: "herbolution:log".to_string() },
+        ]
+    );
+    reopened.save().unwrap();
+
+    // Taken writes are removed from disk, while those of other chunks are kept.
+    let reopened = PendingWrites::open(dir.to_path_buf());
+    assert!(reopened.take(chunk).is_empty());
+    assert_eq!(
+        reopened.take(other),
+        vec![PendingWrite { local: vec3u5::new(31, 0, 0), material: "herbolution:leaves".to_string() }]
+    );
+}
+
+#[test]
+fn generator_config_is_read_from_world_descriptor() {
+    let descriptor: WorldDescriptor = toml::from_str(
+        r#"
+        title = "Islands"
+        seed = 1
+
+        [generator]
+        sea_level = -200.0
+
+        [[generator.bands]]
+        material = "herbolution:sand"
+        min_y = -260
+        max_y = -250
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(descriptor.generator.noise_layers, GeneratorConfig::default().noise_layers);
+    assert_eq!(descriptor.generator.base_material, "herbolution:stone");
+
+    let params = params_with_config(TerrainMode::Heightmap, descriptor.generator, BiomeTable::default());
+
+    // The surface now lies around y = -200, so a chunk that would be solid by default is empty.
+    let above = generate(&params, ChunkPt(Vec3::new(0, -3, 0)));
+    assert!((0..CHUNK_VOLUME).all(|i| above.get(vec3u5::delinearize(i)).is_none()));
+
+    let banded = generate(&params, ChunkPt(Vec3::new(0, -8, 0)));
+    for i in 0..CHUNK_VOLUME {
+        let position = vec3u5::delinearize(i);
+        let y = -8 * CHUNK_LENGTH as i32 + position.y() as i32;
+        if (-260..=-250).contains(&y) {
+            let material = banded.palette().get_by_id(banded.get(position).unwrap()).unwrap();
+            assert_eq!(material.group_key.as_str(), "herbolution:sand");
+        }
+    }
+}
+
+#[test]
+fn stages_run_in_isolation() {
+    let params = params(TerrainMode::Heightmap, BiomeTable::default());
+    let position = ChunkPt(Vec3::new(0, -1, 0));
+
+    let run = |pipeline: GenerationPipeline| {
+        let mut mesh = CubeMesh::new(position);
+        let outside = pipeline.run(&params, &mut mesh);
+        (CubeGrid::from_mesh(&mesh), outside)
+    };
+    let keys = |grid: &CubeGrid| {
+        (0..CHUNK_VOLUME)
+            .filter_map(|i| grid.get(vec3u5::delinearize(i)))
+            .map(|id| grid.palette().get_by_id(id).unwrap().group_key.as_str().to_string())
+            .collect::<HashSet<_>>()
+    };
+
+    let (shaped, _) = run(GenerationPipeline::new(vec![Box::new(TerrainShape)]));
+    assert_eq!(keys(&shaped), HashSet::from(["herbolution:stone".to_string()]));
+
+    let (painted, _) = run(GenerationPipeline::new(vec![Box::new(TerrainShape), Box::new(SurfacePaint)]));
+    assert!(keys(&painted).contains("herbolution:grass"));
+    assert!(keys(&painted).contains("herbolution:dirt"));
+
+    let pipeline = GenerationPipeline::standard(TerrainMode::Heightmap).without("features");
+    assert!(pipeline.stages().all(|stage| stage.neighbours() != NeighbourContext::WritesNeighbours));
+    let (_, outside) = run(pipeline);
+    assert!(outside.is_empty());
+}
+
+#[test]
+fn special_world_types_are_predictable() {
+    let params = params(TerrainMode::Heightmap, BiomeTable::default());
+    let run = |world_type: WorldType, position: ChunkPt| {
+        let mut mesh = CubeMesh::new(position);
+        GenerationPipeline::for_world(&world_type, TerrainMode::Heightmap).run(&params, &mut mesh);
+        CubeGrid::from_mesh(&mesh)
+    };
+    let key = |grid: &CubeGrid, position: vec3u5| {
+        grid.get(position)
+            .map(|id| grid.palette().get_by_id(id).unwrap().group_key.as_str().to_string())
+    };
+
+    let flat = run(WorldType::flat(), ChunkPt(Vec3::new(3, -1, -2)));
+    for (y, expected) in [(31, "herbolution:grass"), (28, "herbolution:dirt"), (0, "herbolution:stone")] {
+        assert_eq!(key(&flat, vec3u5::new(5, y, 7)).as_deref(), Some(expected));
+    }
+    let above = run(WorldType::flat(), ChunkPt(Vec3::ZERO));
+    assert!((0..CHUNK_VOLUME).all(|i| above.get(vec3u5::delinearize(i)).is_none()));
+
+    let void = run(WorldType::Void, ChunkPt(Vec3::new(0, -1, 0)));
+    assert_eq!(key(&void, vec3u5::new(0, 31, 0)).as_deref(), Some("herbolution:stone"));
+    assert!(void.get(vec3u5::new(10, 31, 10)).is_none());
+    let far = run(WorldType::Void, ChunkPt(Vec3::new(4, -1, 4)));
+    assert!((0..CHUNK_VOLUME).all(|i| far.get(vec3u5::delinearize(i)).is_none()));
+
+    let debug = run(WorldType::DebugGrid, ChunkPt(Vec3::ZERO));
+    let placed = (0..CHUNK_VOLUME)
+        .filter_map(|i| key(&debug, vec3u5::delinearize(i)))
+        .collect::<HashSet<_>>();
+    for material in materials().iter() {
+        assert!(placed.contains(material.group_key.as_str()));
+    }
+}
+
+#[test]
+fn lighting_stage_lights_chunks_by_themselves() {
+    let params = params(TerrainMode::Heightmap, BiomeTable::default());
+    let run = |world_type: WorldType, position: ChunkPt| {
+        let mut mesh = CubeMesh::new(position);
+        GenerationPipeline::for_world(&world_type, TerrainMode::Heightmap).run(&params, &mut mesh);
+        mesh
+    };
+
+    // The sky light comes in sideways under the platform, from the open columns around it.
+    let void = run(WorldType::Void, ChunkPt(Vec3::new(0, -1, 0)));
+    assert_eq!(void.light(vec3u5::new(10, 0, 10), LightChannel::Sky), MAX_LIGHT);
+    assert_eq!(void.light(vec3u5::new(4, 30, 0), LightChannel::Sky), MAX_LIGHT);
+    assert_eq!(void.light(vec3u5::new(0, 30, 0), LightChannel::Sky), MAX_LIGHT - 4);
+    assert_eq!(void.face_light(vec3u5::new(0, 31, 0), LightChannel::Sky).get(CubeFace::Down), MAX_LIGHT - 4);
+
+    let flat = run(WorldType::flat(), ChunkPt(Vec3::new(0, -1, 0)));
+    assert!((0..CHUNK_VOLUME).all(|i| flat.light(vec3u5::delinearize(i), LightChannel::Sky) == 0));
+
+    let debug = run(WorldType::DebugGrid, ChunkPt(Vec3::ZERO));
+    let grid = CubeGrid::from_mesh(&debug);
+    let lamp = (0..CHUNK_VOLUME)
+        .map(vec3u5::delinearize)
+        .find(|&x| grid.get(x).is_some_and(|id| grid.palette().get_by_id(id).unwrap().light > 0))
+        .unwrap();
+    assert!(debug.light(lamp, LightChannel::Block) > 0);
+    assert!(debug.light(vec3u5::new(0, 31, 31), LightChannel::Block) < debug.light(lamp, LightChannel::Block));
+    assert!(GenerationPipeline::standard(TerrainMode::Heightmap)
+        .stages()
+        .any(|stage| stage.name() == "lighting"));
+}
+
+#[test]
+fn stacked_chunks_share_cached_columns() {
+    let params = params(TerrainMode::Heightmap, biomes());
+    let pipeline = GenerationPipeline::terrain(TerrainMode::Heightmap);
+    let cache = ColumnCache::new(2);
+
+    for cy in -3..3 {
+        let position = ChunkPt(Vec3::new(4, cy, -7));
+        let mut cached = CubeMesh::new(position);
+        pipeline.run_cached(&params, &cache, &mut cached);
+
+        let uncached = generate(&params, position);
+        let cached = CubeGrid::from_mesh(&cached);
+        for i in 0..CHUNK_VOLUME {
+            let position = vec3u5::delinearize(i);
+            assert_eq!(cached.get(position), uncached.get(position));
+        }
+    }
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.len), (5, 1, 1));
+    assert!((stats.hit_rate() - 5.0 / 6.0).abs() < 1e-9);
+
+    for cx in 0..3 {
+        pipeline.run_cached(&params, &cache, &mut CubeMesh::new(ChunkPt(Vec3::new(cx, 0, 0))));
+    }
+    assert_eq!(cache.stats().len, 2);
+    assert!(!cache.contains(Vec2::new(0, 0)));
+
+    cache.evict(Vec2::new(2, 0));
+    assert!(!cache.contains(Vec2::new(2, 0)));
+    assert!(cache.contains(Vec2::new(1, 0)));
+}
+
+#[test]
+fn spawn_is_on_the_surface_with_headroom() {
+    let pending = Arc::new(PendingWrites::new(std::env::temp_dir().join("herbolution-unused-pending")));
+
+    for terrain in [TerrainMode::Heightmap, TerrainMode::Density] {
+        let params = Arc::new(params(terrain, biomes()));
+        let generator = ChunkGenerator::new(params.clone(), GenerationPipeline::terrain(terrain), pending.clone());
+        let spawn = generator.find_spawn().unwrap();
+
+        let cube = |offset: i32| {
+            let ChunkCubePt { chunk, local } = CubePt(spawn.floor().cast::<i32>() + Vec3::new(0, offset, 0)).into();
+            let grid = generate(&params, chunk);
+            grid.get(local)
+                .map(|id| grid.palette().get_by_id(id).unwrap().has_collider)
+        };
+
+        assert_eq!(cube(-1), Some(true));
+        assert_eq!(cube(0), None);
+        assert_eq!(cube(1), None);
+    }
+
+    let params = Arc::new(params(TerrainMode::Heightmap, BiomeTable::default()));
+    let generator = ChunkGenerator::new(params.clone(), GenerationPipeline::standard(TerrainMode::Heightmap), pending.clone());
+    assert_eq!(generator.request_spawn().recv().unwrap(), generator.find_spawn());
+
+    // Worlds whose terrain is known spawn players on top of it without searching.
+    assert_eq!(fixed_spawn(&WorldType::Standard), None);
+    for world_type in [WorldType::Void, WorldType::flat()] {
+        let generator = ChunkGenerator::new(params.clone(), GenerationPipeline::for_world(&world_type, TerrainMode::Heightmap), pending.clone());
+        assert_eq!(fixed_spawn(&world_type), generator.find_spawn());
+    }
+    assert_eq!(fixed_spawn(&WorldType::Void).unwrap().floor().cast::<i32>(), Vec3::new(0, 0, 0));
+}
@@ -0,0 +1,249 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use lib::point::{ChunkPt, CubePt};
+use lib::vector::Vec3;
+use server::chunk::light::{self, LightChannel, LightProperties, LightVolume, MAX_LIGHT};
+use server::chunk::map::ChunkMap;
+use server::handle::{self, ClientHandle};
+
+use crate::common::{void_map, TempDir};
+
+mod common;
+
+const EXTENT: i32 = 20;
+
+/// A loaded region of cubes from -20 to 20 on each axis, with the open sky above it.
+#[derive(Default)]
+struct Volume {
+    light: HashMap<(CubePt, LightChannel), u8>,
+    cubes: HashMap<CubePt, LightProperties>,
+}
+
+impl Volume {
+    fn with_sky() -> Self {
+        let mut volume = Self::default();
+        let mut queue = VecDeque::new();
+        for x in -EXTENT..=EXTENT {
+            for z in -EXTENT..=EXTENT {
+                volume.set_light(pt(x, EXTENT, z), LightChannel::Sky, MAX_LIGHT);
+                queue.push_back(pt(x, EXTENT, z));
+            }
+        }
+        light::propagate(&mut volume, LightChannel::Sky, queue);
+        volume
+    }
+
+    fn set_cube(&mut self, position: CubePt, properties: LightProperties) {
+        self.cubes.insert(position, properties);
+        light::update(self, position);
+    }
+
+    fn level(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.light(pt(x, y, z), LightChannel::Block)
+            .unwrap()
+    }
+
+    fn sky(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.light(pt(x, y, z), LightChannel::Sky)
+            .unwrap()
+    }
+}
+
+impl LightVolume for Volume {
+    fn light(&self, position: CubePt, channel: LightChannel) -> Option<u8> {
+        is_loaded(position).then(|| {
+            self.light
+                .get(&(position, channel))
+                .copied()
+                .unwrap_or(0)
+        })
+    }
+
+    fn set_light(&mut self, position: CubePt, channel: LightChannel, level: u8) {
+        self.light.insert((position, channel), level);
+    }
+
+    fn light_properties(&self, position: CubePt, channel: LightChannel) -> Option<LightProperties> {
+        if !is_loaded(position) {
+            return None;
+        }
+
+        let mut properties = self.cubes.get(&position).copied().unwrap_or_default();
+        if channel == LightChannel::Sky {
+            properties.emission = if !properties.is_opaque && position.0.y == EXTENT { MAX_LIGHT } else { 0 };
+        }
+        Some(properties)
+    }
+}
+
+fn is_loaded(position: CubePt) -> bool {
+    position.0.x.abs() <= EXTENT && position.0.y.abs() <= EXTENT && position.0.z.abs() <= EXTENT
+}
+
+fn pt(x: i32, y: i32, z: i32) -> CubePt {
+    CubePt(Vec3::new(x, y, z))
+}
+
+const LAMP: LightProperties = LightProperties {
+    is_opaque: true,
+    emission: MAX_LIGHT,
+};
+
+const STONE: LightProperties = LightProperties {
+    is_opaque: true,
+    emission: 0,
+};
+
+#[test]
+fn light_spreads_from_emitters() {
+    let mut volume = Volume::default();
+    volume.set_cube(pt(0, 0, 0), LAMP);
+
+    assert_eq!(volume.level(0, 0, 0), MAX_LIGHT);
+    assert_eq!(volume.level(1, 0, 0), MAX_LIGHT - 1);
+    assert_eq!(volume.level(3, 2, -1), MAX_LIGHT - 6);
+    assert_eq!(volume.level(15, 0, 0), 0);
+}
+
+#[test]
+fn removing_an_emitter_darkens_its_light() {
+    let mut volume = Volume::default();
+    volume.set_cube(pt(0, 0, 0), LAMP);
+    volume.set_cube(pt(10, 0, 0), LAMP);
+    volume.set_cube(pt(0, 0, 0), LightProperties::default());
+
+    assert_eq!(volume.level(0, 0, 0), MAX_LIGHT - 10);
+    assert_eq!(volume.level(-3, 0, 0), MAX_LIGHT - 13);
+    assert_eq!(volume.level(10, 0, 0), MAX_LIGHT);
+}
+
+#[test]
+fn opaque_cubes_block_light() {
+    let mut volume = Volume::default();
+    volume.set_cube(pt(0, 0, 0), LAMP);
+
+    // The wall spans the whole volume, so the light cannot go around it.
+    for y in -EXTENT..=EXTENT {
+        for z in -EXTENT..=EXTENT {
+            volume.set_cube(pt(1, y, z), STONE);
+        }
+    }
+
+    assert_eq!(volume.level(1, 0, 0), 0);
+    assert_eq!(volume.level(2, 0, 0), 0);
+    assert_eq!(volume.level(-1, 0, 0), MAX_LIGHT - 1);
+
+    // Opening a gap lets the light through it.
+    volume.set_cube(pt(1, 0, 0), LightProperties::default());
+    assert_eq!(volume.level(1, 0, 0), MAX_LIGHT - 1);
+    assert_eq!(volume.level(2, 0, 0), MAX_LIGHT - 2);
+}
+
+#[test]
+fn light_is_stopped_by_unloaded_cubes() {
+    let mut volume = Volume::default();
+    volume.set_light(pt(19, 0, 0), LightChannel::Block, MAX_LIGHT);
+    light::propagate(&mut volume, LightChannel::Block, VecDeque::from([pt(19, 0, 0)]));
+
+    assert_eq!(volume.level(20, 0, 0), MAX_LIGHT - 1);
+    assert_eq!(volume.light(pt(21, 0, 0), LightChannel::Block), None);
+}
+
+#[test]
+fn sky_light_falls_without_being_lost() {
+    let volume = Volume::with_sky();
+
+    assert_eq!(volume.sky(0, EXTENT, 0), MAX_LIGHT);
+    assert_eq!(volume.sky(5, -EXTENT, -5), MAX_LIGHT);
+    assert_eq!(volume.level(0, 0, 0), 0);
+}
+
+#[test]
+fn roofs_shade_the_sky_light_below_them() {
+    let mut volume = Volume::with_sky();
+    for x in -3..=3 {
+        for z in -3..=3 {
+            volume.set_cube(pt(x, 5, z), STONE);
+        }
+    }
+
+    // The light comes in sideways from the open columns around the roof.
+    assert_eq!(volume.sky(0, 6, 0), MAX_LIGHT);
+    assert_eq!(volume.sky(0, 5, 0), 0);
+    assert_eq!(volume.sky(0, 4, 0), MAX_LIGHT - 4);
+    assert_eq!(volume.sky(3, 0, 3), MAX_LIGHT - 1);
+    assert_eq!(volume.sky(4, 0, 0), MAX_LIGHT);
+
+    volume.set_cube(pt(0, 5, 0), LightProperties::default());
+    assert_eq!(volume.sky(0, 5, 0), MAX_LIGHT);
+    assert_eq!(volume.sky(0, -EXTENT, 0), MAX_LIGHT);
+    assert_eq!(volume.sky(1, 4, 0), MAX_LIGHT - 1);
+}
+
+fn load_chunk(map: &mut ChunkMap, handle: &ClientHandle, x: i32, y: i32, z: i32) {
+    let position = ChunkPt(Vec3::new(x, y, z));
+    map.queue_load(position);
+    let start = Instant::now();
+    while map.get_chunk(position).is_none() {
+        assert!(start.elapsed().as_secs() < 30, "the chunk was not loaded in time");
+        map.update(handle);
+    }
+}
+
+fn unload_chunk(map: &mut ChunkMap, handle: &ClientHandle, x: i32, y: i32, z: i32) {
+    map.queue_unload(ChunkPt(Vec3::new(x, y, z)));
+    map.update(handle);
+    assert!(map.get_chunk(ChunkPt(Vec3::new(x, y, z))).is_none());
+}
+
+#[test]
+fn unloading_a_chunk_opens_the_chunk_below_to_the_sky() {
+    let dir = TempDir::new("unload");
+    let mut map = void_map(&dir);
+    let (handle, _game_handle) = handle::create();
+    load_chunk(&mut map, &handle, 0, 0, 0);
+    load_chunk(&mut map, &handle, 0, 1, 0);
+
+    // A roof at the bottom of the chunk above, which the sky light only reaches under from its sides.
+    for x in 12..20 {
+        for z in 12..20 {
+            map.set_cube(Vec3::new(x, 32, z), "herbolution:stone");
+        }
+    }
+    assert_eq!(map.get_light(Vec3::new(15, 31, 15), LightChannel::Sky), Some(MAX_LIGHT - 4));
+
+    unload_chunk(&mut map, &handle, 0, 1, 0);
+    assert_eq!(map.get_light(Vec3::new(15, 31, 15), LightChannel::Sky), Some(MAX_LIGHT));
+    assert_eq!(map.get_light(Vec3::new(15, 0, 15), LightChannel::Sky), Some(MAX_LIGHT));
+
+    // Loading it again from disk shades the chunk below once more.
+    load_chunk(&mut map, &handle, 0, 1, 0);
+    assert_eq!(map.get_light(Vec3::new(15, 31, 15), LightChannel::Sky), Some(MAX_LIGHT - 4));
+    assert_eq!(map.get_light(Vec3::new(15, 33, 15), LightChannel::Sky), Some(MAX_LIGHT));
+}
+
+#[test]
+fn light_spreads_into_chunks_loaded_next_to_it() {
+    let dir = TempDir::new("border");
+    let mut map = void_map(&dir);
+    let (handle, _game_handle) = handle::create();
+    load_chunk(&mut map, &handle, 0, 0, 0);
+    map.set_cube(Vec3::new(31, 5, 5), "herbolution:lamp");
+
+    load_chunk(&mut map, &handle, 1, 0, 0);
+    assert_eq!(map.get_light(Vec3::new(32, 5, 5), LightChannel::Block), Some(MAX_LIGHT - 1));
+    assert_eq!(map.get_light(Vec3::new(34, 6, 5), LightChannel::Block), Some(MAX_LIGHT - 4));
+
+    // A lamp in the neighbouring chunk is saved with it, and lights this chunk again once it is read back.
+    map.set_cube(Vec3::new(32, 20, 20), "herbolution:lamp");
+    unload_chunk(&mut map, &handle, 1, 0, 0);
+
+    load_chunk(&mut map, &handle, 1, 0, 0);
+    assert_eq!(map.get_light(Vec3::new(40, 0, 9), LightChannel::Sky), Some(MAX_LIGHT));
+    assert_eq!(map.get_light(Vec3::new(32, 5, 5), LightChannel::Block), Some(MAX_LIGHT - 1));
+    assert_eq!(map.get_light(Vec3::new(30, 20, 20), LightChannel::Block), Some(MAX_LIGHT - 2));
+}
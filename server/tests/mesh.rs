@@ -0,0 +1,97 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use lib::point::ChunkPt;
+use lib::spatial::CubeFace;
+use lib::vector::{vec3u5, Vec3};
+use lib::world::CHUNK_LENGTH;
+use server::chunk::codec::CubeGrid;
+use server::chunk::material::Palette;
+use server::chunk::mesh::CubeMesh;
+use server::chunk::registry::MaterialRegistry;
+
+use crate::common::materials;
+
+mod common;
+
+const EDGE: u8 = CHUNK_LENGTH as u8 - 1;
+
+/// Builds a chunk with a pair of cubes side by side along the x axis.
+fn pair(materials: &MaterialRegistry, a: &str, b: &str) -> CubeMesh {
+    let mut palette = Palette::new();
+    let a = palette.insert(materials.get(a).unwrap().clone());
+    let b = palette.insert(materials.get(b).unwrap().clone());
+
+    let mut grid = CubeGrid::new(palette);
+    grid.set(vec3u5::new(4, 4, 4), Some(a));
+    grid.set(vec3u5::new(5, 4, 4), Some(b));
+    grid.to_mesh(ChunkPt(Vec3::ZERO))
+}
+
+fn shared_faces_shown(mesh: &CubeMesh) -> (bool, bool) {
+    (
+        mesh.faces(vec3u5::new(4, 4, 4))
+            .contains(CubeFace::East),
+        mesh.faces(vec3u5::new(5, 4, 4))
+            .contains(CubeFace::West),
+    )
+}
+
+#[test]
+fn faces_are_culled_by_transparency() {
+    let materials = materials();
+
+    assert_eq!(shared_faces_shown(&pair(&materials, "herbolution:stone", "herbolution:dirt")), (false, false));
+    assert_eq!(shared_faces_shown(&pair(&materials, "herbolution:stone", "herbolution:glass")), (true, false));
+    assert_eq!(shared_faces_shown(&pair(&materials, "herbolution:glass", "herbolution:glass")), (false, false));
+    assert_eq!(shared_faces_shown(&pair(&materials, "herbolution:glass", "herbolution:water")), (true, true));
+    assert_eq!(shared_faces_shown(&pair(&materials, "herbolution:leaves", "herbolution:leaves")), (true, true));
+
+    let mesh = pair(&materials, "herbolution:stone", "herbolution:glass");
+    assert!(
+        mesh.faces(vec3u5::new(4, 4, 4))
+            .contains(CubeFace::Up)
+    );
+}
+
+#[test]
+fn removing_a_cube_shows_the_faces_behind_it() {
+    let materials = materials();
+
+    let mut mesh = pair(&materials, "herbolution:stone", "herbolution:stone");
+    assert_eq!(shared_faces_shown(&mesh), (false, false));
+
+    mesh.set(vec3u5::new(5, 4, 4), None);
+    assert!(
+        mesh.faces(vec3u5::new(4, 4, 4))
+            .contains(CubeFace::East)
+    );
+    assert!(mesh.faces(vec3u5::new(5, 4, 4)).is_empty());
+}
+
+#[test]
+fn faces_between_chunks_are_culled_by_transparency() {
+    let materials = materials();
+    let chunk = |position, cube, key| {
+        let mut palette = Palette::new();
+        let id = palette.insert(materials.get(key).unwrap().clone());
+        let mut grid = CubeGrid::new(palette);
+        grid.set(cube, Some(id));
+        grid.to_mesh(position)
+    };
+
+    let mut stone = chunk(ChunkPt(Vec3::ZERO), vec3u5::new(EDGE, 4, 4), "herbolution:stone");
+    let mut glass = chunk(ChunkPt(Vec3::new(1, 0, 0)), vec3u5::new(0, 4, 4), "herbolution:glass");
+    stone.cull_shared_faces(&mut glass);
+
+    assert!(
+        stone
+            .faces(vec3u5::new(EDGE, 4, 4))
+            .contains(CubeFace::East)
+    );
+    assert!(
+        !glass
+            .faces(vec3u5::new(0, 4, 4))
+            .contains(CubeFace::West)
+    );
+}
@@ -0,0 +1,244 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::fs::{create_dir_all, metadata, read, read_dir, write, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use lib::point::ChunkPt;
+use lib::vector::Vec3;
+use server::chunk::region::RegionStore;
+
+use crate::common::TempDir;
+
+mod common;
+
+const SECTOR_SIZE: u64 = 512;
+const HEADER_SECTORS: u64 = 65;
+const TABLE_OFFSET: u64 = 8;
+
+fn pt(x: i32, y: i32, z: i32) -> ChunkPt {
+    ChunkPt(Vec3::new(x, y, z))
+}
+
+fn payload(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+/// The number of sectors in a region file, past its header.
+fn data_sectors(path: &Path) -> u64 {
+    metadata(path).unwrap().len().div_ceil(SECTOR_SIZE) - HEADER_SECTORS
+}
+
+/// Reads the table entry of the chunk at an index of the region, as its offset and length.
+fn read_entry(path: &Path, index: u64) -> (u32, u32) {
+    let bytes = read(path).unwrap();
+    let start = (TABLE_OFFSET + index * 8) as usize;
+    (
+        u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap()),
+        u32::from_le_bytes(bytes[start + 4..start + 8].try_into().unwrap()),
+    )
+}
+
+/// Overwrites the table entry of the chunk at an index of the region.
+fn write_entry(path: &Path, index: u64, offset: u32, len: u32) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(TABLE_OFFSET + index * 8)).unwrap();
+    file.write_all(&offset.to_le_bytes()).unwrap();
+    file.write_all(&len.to_le_bytes()).unwrap();
+}
+
+#[test]
+fn chunks_round_trip() {
+    let dir = TempDir::new("round-trip");
+    let store = RegionStore::new(dir.to_path_buf());
+
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), None);
+    assert!(!store.contains(pt(0, 0, 0)));
+    assert!(store.positions().unwrap().is_empty());
+
+    let chunks = [(pt(0, 0, 0), payload(100, 1)), (pt(15, -1, 3), payload(2000, 2)), (pt(-17, 40, 16), payload(1, 3))];
+    for (position, bytes) in &chunks {
+        store.write(*position, bytes).unwrap();
+    }
+
+    for (position, bytes) in &chunks {
+        assert!(store.contains(*position));
+        assert_eq!(store.read(*position).unwrap().as_ref(), Some(bytes));
+    }
+    assert_eq!(store.read(pt(1, 0, 0)).unwrap(), None);
+
+    let mut positions = store.positions().unwrap();
+    positions.sort_by_key(|x| (x.0.x, x.0.y, x.0.z));
+    assert_eq!(positions, vec![pt(-17, 40, 16), pt(0, 0, 0), pt(15, -1, 3)]);
+    assert_eq!(read_dir(&dir).unwrap().count(), 3);
+}
+
+#[test]
+fn rewritten_chunks_reuse_free_sectors() {
+    let dir = TempDir::new("rewrite");
+    let path = dir.join("0.0.0.region");
+    let store = RegionStore::new(dir.to_path_buf());
+
+    store.write(pt(0, 0, 0), &payload(100, 1)).unwrap();
+    store.write(pt(0, 1, 0), &payload(100, 2)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(data_sectors(&path), 2);
+
+    // A chunk that grows moves to the end of the file, since it does not fit where it was.
+    store.write(pt(0, 0, 0), &payload(1500, 3)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(data_sectors(&path), 5);
+
+    // Once it shrinks again, it fits back into the sector it left, and the file does not grow.
+    store.write(pt(0, 0, 0), &payload(200, 4)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(data_sectors(&path), 5);
+
+    // The sectors it grew into are free for other chunks.
+    store.write(pt(0, 2, 0), &payload(1000, 5)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(data_sectors(&path), 5);
+
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), Some(payload(200, 4)));
+    assert_eq!(store.read(pt(0, 1, 0)).unwrap(), Some(payload(100, 2)));
+    assert_eq!(store.read(pt(0, 2, 0)).unwrap(), Some(payload(1000, 5)));
+}
+
+#[test]
+fn writes_are_committed_when_synced() {
+    let dir = TempDir::new("sync");
+    let path = dir.join("0.0.0.region");
+    let store = RegionStore::new(dir.to_path_buf());
+
+    store.write(pt(0, 0, 0), &payload(100, 1)).unwrap();
+    store.sync().unwrap();
+
+    // Until the store is synced, the chunk is read back from where it was written, but the table on disk still
+    // refers to the previous payload.
+    store.write(pt(0, 0, 0), &payload(100, 2)).unwrap();
+    store.write(pt(0, 0, 0), &payload(100, 3)).unwrap();
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), Some(payload(100, 3)));
+    assert_eq!(read_entry(&path, 0), (HEADER_SECTORS as u32, 100));
+
+    // The payload written in between was never referenced on disk, so its sector is free right away, while the sector
+    // of the synced payload is only freed once the new one is synced.
+    store.write(pt(0, 1, 0), &payload(100, 4)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(read_entry(&path, 0), (HEADER_SECTORS as u32 + 2, 100));
+    assert_eq!(read_entry(&path, 1), (HEADER_SECTORS as u32 + 1, 100));
+    assert_eq!(data_sectors(&path), 3);
+
+    store.write(pt(0, 2, 0), &payload(100, 5)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(read_entry(&path, 2), (HEADER_SECTORS as u32, 100));
+}
+
+#[test]
+fn removed_chunks_free_their_sectors() {
+    let dir = TempDir::new("remove");
+    let path = dir.join("0.0.0.region");
+    let store = RegionStore::new(dir.to_path_buf());
+
+    store.write(pt(0, 0, 0), &payload(100, 1)).unwrap();
+    store.write(pt(0, 1, 0), &payload(100, 2)).unwrap();
+    store.sync().unwrap();
+
+    store.remove(pt(0, 0, 0)).unwrap();
+    store.remove(pt(5, 5, 5)).unwrap();
+    assert!(!store.contains(pt(0, 0, 0)));
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), None);
+    assert_eq!(read_entry(&path, 0), (HEADER_SECTORS as u32, 100));
+
+    store.sync().unwrap();
+    assert_eq!(read_entry(&path, 0), (0, 0));
+    assert_eq!(store.positions().unwrap(), vec![pt(0, 1, 0)]);
+
+    store.write(pt(0, 2, 0), &payload(100, 3)).unwrap();
+    store.sync().unwrap();
+    assert_eq!(data_sectors(&path), 2);
+}
+
+#[test]
+fn chunks_are_read_after_reopening() {
+    let dir = TempDir::new("reopen");
+    {
+        let store = RegionStore::new(dir.to_path_buf());
+        store.write(pt(0, 0, 0), &payload(700, 1)).unwrap();
+        store.write(pt(-1, 0, 0), &payload(300, 2)).unwrap();
+        store.write(pt(0, 0, 0), &payload(50, 3)).unwrap();
+    }
+
+    let store = RegionStore::new(dir.to_path_buf());
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), Some(payload(50, 3)));
+    assert_eq!(store.read(pt(-1, 0, 0)).unwrap(), Some(payload(300, 2)));
+    assert_eq!(store.positions().unwrap().len(), 2);
+
+    // The sectors freed before reopening are reused.
+    let sectors = data_sectors(&dir.join("0.0.0.region"));
+    store.write(pt(0, 1, 0), &payload(700, 4)).unwrap();
+    assert_eq!(data_sectors(&dir.join("0.0.0.region")), sectors);
+}
+
+#[test]
+fn corrupt_entries_are_discarded() {
+    let dir = TempDir::new("corrupt");
+    let path = dir.join("0.0.0.region");
+    {
+        let store = RegionStore::new(dir.to_path_buf());
+        for y in 0..4 {
+            store.write(pt(0, y, 0), &payload(100, y as u8)).unwrap();
+        }
+    }
+
+    // The chunks are indexed by their y first, and each takes a single sector after the header.
+    let first = HEADER_SECTORS as u32;
+    write_entry(&path, 1, first, 100);
+    write_entry(&path, 2, 3, 100);
+    write_entry(&path, 3, first + 100, 100);
+
+    let store = RegionStore::new(dir.to_path_buf());
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), Some(payload(100, 0)));
+    for y in 1..4 {
+        assert_eq!(store.read(pt(0, y, 0)).unwrap(), None);
+        assert!(!store.contains(pt(0, y, 0)));
+    }
+
+    // Discarded chunks can be written again without touching the valid one.
+    store.write(pt(0, 1, 0), &payload(100, 9)).unwrap();
+    assert_eq!(store.read(pt(0, 1, 0)).unwrap(), Some(payload(100, 9)));
+    assert_eq!(store.read(pt(0, 0, 0)).unwrap(), Some(payload(100, 0)));
+}
+
+#[test]
+fn files_that_are_not_regions_fail_to_open() {
+    let dir = TempDir::new("magic");
+    write(dir.join("0.0.0.region"), vec![0; (HEADER_SECTORS * SECTOR_SIZE) as usize]).unwrap();
+
+    let store = RegionStore::new(dir.to_path_buf());
+    assert!(store.read(pt(0, 0, 0)).is_err());
+    assert!(!store.contains(pt(0, 0, 0)));
+}
+
+#[test]
+fn chunk_files_are_migrated_into_regions() {
+    let dir = TempDir::new("migrate");
+    let chunks_dir = dir.join("chunks");
+    create_dir_all(chunks_dir.join("0.0.0")).unwrap();
+    write(chunks_dir.join("1.2.3"), payload(100, 1)).unwrap();
+    write(chunks_dir.join("-20.0.5"), payload(900, 2)).unwrap();
+    write(chunks_dir.join("notes.txt"), b"not a chunk").unwrap();
+
+    let store = RegionStore::new(dir.join("regions"));
+    assert_eq!(store.migrate_chunk_files(&chunks_dir).unwrap(), 2);
+
+    assert_eq!(store.read(pt(1, 2, 3)).unwrap(), Some(payload(100, 1)));
+    assert_eq!(store.read(pt(-20, 0, 5)).unwrap(), Some(payload(900, 2)));
+    assert!(!chunks_dir.join("1.2.3").exists());
+    assert!(!chunks_dir.join("-20.0.5").exists());
+    assert!(chunks_dir.join("notes.txt").exists());
+    assert!(chunks_dir.join("0.0.0").is_dir());
+
+    // Nothing is left to migrate the second time.
+    assert_eq!(store.migrate_chunk_files(&chunks_dir).unwrap(), 0);
+}
@@ -0,0 +1,441 @@
+extern crate herbolution_lib as lib;
+extern crate herbolution_server as server;
+
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::path::Path;
+
+use lib::fs::Fs;
+use lib::save::archive::{read_archive, ARCHIVE_MAGIC, ARCHIVE_VERSION};
+use lib::rotation::Euler;
+use lib::save::{
+    ArchiveError, ImportOptions, MigrationKind, Migrations, PlayerData, Save, SaveAttributes, SaveError, WorldAttributes, WORLD_FORMAT_VERSION,
+};
+use lib::point::ChunkPt;
+use lib::util::crc32;
+use lib::vector::{vec3u5, Vec3};
+use lib::world::Health;
+use server::chunk::migration::migrations;
+use server::chunk::pending::PendingWrites;
+
+use crate::common::{materials, TempDir};
+
+mod common;
+
+/// Writes a save from before format versions existed, with a single world.
+fn write_version_0_save(path: &Path) {
+    create_dir_all(path.join("worlds/overworld")).unwrap();
+    write(path.join("Save.toml"), "title = \"Old\"\ndefault_world = \"overworld\"\n").unwrap();
+    write(path.join("worlds/overworld/World.toml"), "title = \"Overworld\"\nseed = 7\n").unwrap();
+}
+
+fn format_version(path: &Path) -> Option<i64> {
+    let table: toml::Table = toml::from_str(&read_to_string(path).unwrap()).unwrap();
+    table
+        .get("format_version")
+        .and_then(|x| x.as_integer())
+}
+
+#[test]
+fn version_0_worlds_are_migrated_to_the_current_version() {
+    let dir = TempDir::new("migrate");
+    let path = dir.join("save");
+    write_version_0_save(&path);
+
+    let save = Save::open(path.clone(), &migrations()).unwrap();
+    let applied = save
+        .migrations
+        .applied
+        .iter()
+        .filter(|x| x.kind == MigrationKind::World)
+        .map(|x| x.from_version)
+        .collect::<Vec<_>>();
+
+    assert_eq!(applied, (0..WORLD_FORMAT_VERSION).collect::<Vec<_>>());
+    assert_eq!(format_version(&path.join("worlds/overworld/World.toml")), Some(WORLD_FORMAT_VERSION as i64));
+    assert_eq!(save.default_world().unwrap().descriptor.seed, 7);
+
+    // Opening the migrated save again has nothing left to do.
+    assert!(Save::open(path.clone(), &migrations()).unwrap().migrations.is_empty());
+}
+
+#[test]
+fn pending_writes_are_migrated_into_a_region_store() {
+    let dir = TempDir::new("pending");
+    let path = dir.join("save");
+    write_version_0_save(&path);
+    let world_path = path.join("worlds/overworld");
+    write(
+        world_path.join("pending.toml"),
+        "[[writes]]\nposition = [1, 2, 3]\nmaterial = \"herbolution:leaves\"\n\n\
+         [[writes]]\nposition = [1, 2, 3]\nmaterial = \"herbolution:log\"\n\n\
+         [[writes]]\nposition = [-1, 40, 0]\nmaterial = \"herbolution:log\"\n",
+    )
+    .unwrap();
+
+    Save::open(path.clone(), &migrations()).unwrap();
+    assert!(!world_path.join("pending.toml").exists());
+
+    let pending = PendingWrites::open(world_path.join("pending"));
+    let writes = pending.take(ChunkPt(Vec3::ZERO));
+    assert_eq!(writes.len(), 1);
+    assert_eq!(writes[0].local, vec3u5::new(1, 2, 3));
+    assert_eq!(writes[0].material, "herbolution:leaves");
+    assert_eq!(pending.take(ChunkPt(Vec3::new(-1, 1, 0))).len(), 1);
+}
+
+#[test]
+fn missing_migrations_leave_the_save_untouched() {
+    let dir = TempDir::new("missing");
+    let path = dir.join("save");
+    write_version_0_save(&path);
+
+    // The steps of this crate alone cannot re-encode chunks.
+    let error = Save::open(path.clone(), &Migrations::new()).unwrap_err();
+    assert!(matches!(error, SaveError::MissingMigration { kind: MigrationKind::World, version: 1 }));
+
+    assert_eq!(format_version(&path.join("Save.toml")), None);
+    assert_eq!(format_version(&path.join("worlds/overworld/World.toml")), None);
+    assert!(!path.join("backups").exists());
+}
+
+#[test]
+fn temporary_files_of_interrupted_writes_are_removed() {
+    let dir = TempDir::new("temp-files");
+    let path = dir.join("save");
+    write_version_0_save(&path);
+    write(path.join("Save.toml.herbolution-tmp"), "title = \"Partial").unwrap();
+    write(path.join("worlds/overworld/World.toml.herbolution-tmp"), "").unwrap();
+    write(path.join("worlds/overworld/notes.tmp"), "").unwrap();
+
+    Save::open(path.clone(), &migrations()).unwrap();
+    assert!(!path.join("Save.toml.herbolution-tmp").exists());
+    assert!(!path.join("worlds/overworld/World.toml.herbolution-tmp").exists());
+    assert!(path.join("worlds/overworld/notes.tmp").exists());
+}
+
+#[test]
+fn migrations_back_up_the_save_first() {
+    let dir = TempDir::new("backup");
+    let path = dir.join("save");
+    write_version_0_save(&path);
+
+    let save = Save::open(path.clone(), &migrations()).unwrap();
+    let backup_path = save.migrations.backup_path.unwrap();
+
+    assert!(backup_path.starts_with(path.join("backups")));
+    assert_eq!(format_version(&backup_path.join("Save.toml")), None);
+    assert_eq!(format_version(&backup_path.join("worlds/overworld/World.toml")), None);
+    assert_eq!(read_dir(path.join("backups")).unwrap().count(), 1);
+}
+
+fn world_attributes(name: &str) -> WorldAttributes {
+    WorldAttributes {
+        name: name.to_string(),
+        descriptor: toml::from_str(&format!("title = \"{name}\"\nseed = 3\n")).unwrap(),
+    }
+}
+
+#[test]
+fn worlds_are_created_and_deleted_by_name() {
+    let root = TempDir::new("worlds");
+    let invalid = |name: &str| {
+        Save::create(
+            &root.join(format!("invalid-{}", name.len())),
+            SaveAttributes {
+                title: "Invalid".to_string(),
+                default_world: world_attributes(name),
+            },
+        )
+    };
+    assert!(matches!(invalid("../escape"), Err(SaveError::InvalidWorldName(_))));
+
+    let save = Save::create(
+        &root.join("save"),
+        SaveAttributes {
+            title: "Worlds".to_string(),
+            default_world: world_attributes("overworld"),
+        },
+    )
+    .unwrap();
+
+    let nether = save.create_world(world_attributes("the_nether-2")).unwrap();
+    assert_eq!(nether.path, save.path.join("worlds/the_nether-2"));
+    assert_eq!(nether.descriptor.format_version, WORLD_FORMAT_VERSION);
+    assert_eq!(save.world("the_nether-2").unwrap().descriptor.title, "the_nether-2");
+    assert!(matches!(save.create_world(world_attributes("the_nether-2")), Err(SaveError::WorldAlreadyExists(_))));
+
+    let mut names = save
+        .worlds()
+        .unwrap()
+        .map(|x| x.unwrap().name)
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["overworld".to_string(), "the_nether-2".to_string()]);
+
+    // Names that are not plain file names never reach the file system.
+    for name in ["", ".", "..", "../overworld", "a/b", "a\\b", "a b", "nether.old", "über"] {
+        assert!(matches!(save.create_world(world_attributes(name)), Err(SaveError::InvalidWorldName(_))), "{name:?}");
+        assert!(matches!(save.world(name), Err(SaveError::InvalidWorldName(_))), "{name:?}");
+        assert!(matches!(save.delete_world(name), Err(SaveError::InvalidWorldName(_))), "{name:?}");
+    }
+
+    assert!(matches!(save.delete_world("overworld"), Err(SaveError::DefaultWorld(_))));
+    assert!(matches!(save.delete_world("missing"), Err(SaveError::WorldNotFound(_))));
+    assert!(matches!(save.world("missing"), Err(SaveError::WorldNotFound(_))));
+
+    save.delete_world("the_nether-2").unwrap();
+    assert!(!nether.path.exists());
+    assert!(matches!(save.world("the_nether-2"), Err(SaveError::WorldNotFound(_))));
+    assert!(save.default_world().is_ok());
+}
+
+#[test]
+fn player_data_is_kept_per_world() {
+    let root = TempDir::new("players");
+    let save = Save::create(
+        &root.join("save"),
+        SaveAttributes {
+            title: "Players".to_string(),
+            default_world: world_attributes("overworld"),
+        },
+    )
+    .unwrap();
+    let nether = save.create_world(world_attributes("nether")).unwrap();
+    let overworld = save.default_world().unwrap();
+
+    assert!(overworld.read_player("local").unwrap().is_none());
+
+    let data = PlayerData {
+        position: Vec3::new(1.5, 20.0, -3.25),
+        rotation: Euler::new(0.5, -0.25, 0.0),
+        health: Health::new(42.0),
+        has_gravity: false,
+        acceleration_rate: 30.0,
+    };
+    overworld.write_player("local", &data).unwrap();
+
+    assert!(nether.read_player("local").unwrap().is_none());
+    assert!(overworld.read_player("other").unwrap().is_none());
+
+    let read = save
+        .world("overworld")
+        .unwrap()
+        .read_player("local")
+        .unwrap()
+        .unwrap();
+    assert_eq!(read.position, data.position);
+    assert_eq!(read.rotation, data.rotation);
+    assert_eq!(read.health, data.health);
+    assert_eq!(read.has_gravity, data.has_gravity);
+    assert_eq!(read.acceleration_rate, data.acceleration_rate);
+}
+
+#[test]
+fn snapshots_are_created_pruned_and_restored() {
+    let root = TempDir::new("snapshots");
+    let fs = Fs::new(root.to_path_buf(), migrations());
+    fs.init().unwrap();
+    write_version_0_save(&root.join("saves/old"));
+    let notes_path = root.join("saves/old/worlds/overworld/notes.bin");
+
+    // Opening the save migrates it, which leaves a backup in the save.
+    let save = fs.open_save("old").unwrap();
+    assert_eq!(read_dir(save.path.join("backups")).unwrap().count(), 1);
+
+    write(&notes_path, [1]).unwrap();
+    let first = fs.snapshot_save("old", 3).unwrap();
+    write(&notes_path, [2]).unwrap();
+    let second = fs.snapshot_save("old", 3).unwrap();
+    assert_eq!(first.manifest.save_title, "Old");
+    assert!(!first.path.join("backups").exists());
+
+    // Directories without a manifest are snapshots that were interrupted, and are left out.
+    create_dir_all(fs.snapshots_path().join("old/0")).unwrap();
+    let paths = |save: &Save| {
+        save.snapshots(fs.snapshots_path())
+            .unwrap()
+            .into_iter()
+            .map(|x| x.path)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&save), vec![first.path.clone(), second.path.clone()]);
+
+    write(&notes_path, [3]).unwrap();
+    let third = fs.snapshot_save("old", 2).unwrap();
+    assert_eq!(paths(&save), vec![second.path.clone(), third.path.clone()]);
+    assert!(!first.path.exists());
+
+    write(&notes_path, [4]).unwrap();
+    write(save.path.join("stray"), "").unwrap();
+    let restored = save
+        .restore_snapshot(&second, fs.migrations())
+        .unwrap();
+    assert_eq!(std::fs::read(&notes_path).unwrap(), [2]);
+    assert!(!restored.path.join("stray").exists());
+    assert!(!restored.path.join("Snapshot.toml").exists());
+    assert_eq!(read_dir(restored.path.join("backups")).unwrap().count(), 1);
+    assert!(restored.migrations.is_empty());
+
+    // Restoring leaves the snapshot and nothing else behind.
+    assert!(second.path.join("Snapshot.toml").is_file());
+    let mut names = read_dir(root.join("saves"))
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["old".to_string()]);
+}
+
+#[test]
+fn material_overrides_are_kept_by_snapshots_and_archives() {
+    let root = TempDir::new("material-overrides");
+    let fs = Fs::new(root.to_path_buf(), migrations());
+    fs.init().unwrap();
+    write_version_0_save(&root.join("saves/old"));
+    let save = fs.open_save("old").unwrap();
+
+    let override_path = save.path.join("materials/glow.toml");
+    let glow = "has_collider = false\ntoughness = 0.0\ncolors = [[1.0, 1.0, 0.5]]\n";
+    create_dir_all(override_path.parent().unwrap()).unwrap();
+    write(&override_path, glow).unwrap();
+    let resolves_glow = |save_path: &Path| {
+        let mut materials = materials();
+        materials
+            .load_overrides(&save_path.join("materials"))
+            .unwrap();
+        materials.get("herbolution:glow").is_some()
+    };
+
+    let snapshot = fs.snapshot_save("old", 1).unwrap();
+    write(&override_path, glow.replace("0.5", "0.0")).unwrap();
+    let restored = save
+        .restore_snapshot(&snapshot, fs.migrations())
+        .unwrap();
+    assert_eq!(read_to_string(&override_path).unwrap(), glow);
+    assert!(resolves_glow(&restored.path));
+
+    let archive_path = root.join("Old.hbsave");
+    restored.export(&archive_path).unwrap();
+    let archive = read_archive(&archive_path).unwrap();
+    assert!(archive.entries.iter().any(|x| x.path == "materials/glow.toml"));
+    let imported = fs.import_save(&archive_path, ImportOptions::default()).unwrap();
+    assert_eq!(read_to_string(imported.path.join("materials/glow.toml")).unwrap(), glow);
+    assert!(resolves_glow(&imported.path));
+}
+
+#[test]
+fn interrupted_restores_are_finished_or_rolled_back() {
+    let root = TempDir::new("interrupted-restore");
+    let fs = Fs::new(root.to_path_buf(), migrations());
+    fs.init().unwrap();
+
+    // A save that was moved aside but not yet replaced is moved back, and the unfinished copy is removed.
+    write_version_0_save(&root.join("saves/moved.replaced"));
+    write_version_0_save(&root.join("saves/moved.restoring"));
+    // A save that was already replaced keeps the restored copy.
+    write_version_0_save(&root.join("saves/replaced"));
+    write_version_0_save(&root.join("saves/replaced.replaced"));
+
+    fs.init().unwrap();
+    let mut names = read_dir(root.join("saves"))
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["moved".to_string(), "replaced".to_string()]);
+}
+
+/// Writes an archive with the given manifest and entries, laid out like an exported save.
+fn write_archive(path: &Path, manifest: &str, entries: &[(&str, &[u8])]) {
+    let mut bytes = ARCHIVE_MAGIC.to_vec();
+    bytes.extend(ARCHIVE_VERSION.to_le_bytes());
+    bytes.extend((manifest.len() as u32).to_le_bytes());
+    bytes.extend(manifest.as_bytes());
+    bytes.extend((entries.len() as u32).to_le_bytes());
+    for (name, data) in entries {
+        bytes.extend((name.len() as u16).to_le_bytes());
+        bytes.extend(name.as_bytes());
+        bytes.extend((data.len() as u64).to_le_bytes());
+        bytes.extend(*data);
+    }
+    bytes.extend(crc32(&bytes).to_le_bytes());
+
+    write(path, bytes).unwrap();
+}
+
+const MANIFEST: &str = "title = \"Old\"\nformat_version = 0\ndefault_world = \"overworld\"\nworlds = [\"overworld\"]\n";
+
+fn archive_error(path: &Path) -> ArchiveError {
+    match read_archive(path).unwrap_err() {
+        SaveError::Archive(e) => e,
+        e => panic!("expected an archive error, found {e}"),
+    }
+}
+
+#[test]
+fn exported_saves_are_imported_under_unique_names() {
+    let root = TempDir::new("archive");
+    let fs = Fs::new(root.to_path_buf(), migrations());
+    create_dir_all(root.join("saves")).unwrap();
+    write_version_0_save(&root.join("saves/old"));
+    write(root.join("saves/old/worlds/overworld/notes.bin"), [1, 2, 3]).unwrap();
+
+    let save = fs.open_save("old").unwrap();
+    let archive_path = root.join("Old.hbsave");
+    save.export(&archive_path).unwrap();
+
+    let archive = read_archive(&archive_path).unwrap();
+    assert_eq!(archive.manifest.worlds, vec!["overworld".to_string()]);
+    assert!(archive.entries.iter().any(|x| x.path == "worlds/overworld/notes.bin" && x.data == [1, 2, 3]));
+
+    // Imported saves are named after the archive, and never replace an existing save unless asked to.
+    let imported = fs.import_save(&archive_path, ImportOptions::default()).unwrap();
+    assert_eq!(imported.path, root.join("saves/Old"));
+    let imported = fs.import_save(&archive_path, ImportOptions::default()).unwrap();
+    assert_eq!(imported.path, root.join("saves/Old-2"));
+    let world = |path: &Path| read_to_string(path.join("worlds/overworld/World.toml")).unwrap();
+    assert_eq!(world(&imported.path), world(&save.path));
+
+    let named = |overwrite| ImportOptions {
+        name: Some("old".to_string()),
+        overwrite,
+    };
+    assert!(matches!(fs.import_save(&archive_path, named(false)), Err(SaveError::AlreadyExists)));
+    write(root.join("saves/old/stray"), "").unwrap();
+    let replaced = fs.import_save(&archive_path, named(true)).unwrap();
+    assert_eq!(replaced.descriptor.title, "Old");
+    assert!(!root.join("saves/old/stray").exists());
+}
+
+#[test]
+fn archives_with_unsafe_paths_are_rejected() {
+    let root = TempDir::new("unsafe");
+    let path = root.join("unsafe.hbsave");
+    let save = b"title = \"Old\"\ndefault_world = \"overworld\"\n".as_slice();
+    let world = b"title = \"Overworld\"\nseed = 7\n".as_slice();
+
+    for name in ["worlds/overworld/../../../escaped", "/worlds/overworld/World.toml", "worlds//World.toml", "other/file", "worlds/./overworld"] {
+        write_archive(&path, MANIFEST, &[("Save.toml", save), ("worlds/overworld/World.toml", world), (name, b"")]);
+        assert!(matches!(archive_error(&path), ArchiveError::UnsafePath(x) if x == name), "{name}");
+    }
+
+    // Entries may only belong to the worlds that the manifest lists.
+    write_archive(&path, MANIFEST, &[("Save.toml", save), ("worlds/overworld/World.toml", world), ("worlds/nether/World.toml", world)]);
+    assert!(matches!(archive_error(&path), ArchiveError::UnlistedWorld(x) if x == "worlds/nether/World.toml"));
+
+    let manifest = MANIFEST.replace("worlds = [\"overworld\"]", "worlds = [\"overworld\", \"..\"]");
+    write_archive(&path, &manifest, &[("Save.toml", save), ("worlds/overworld/World.toml", world)]);
+    assert!(matches!(archive_error(&path), ArchiveError::InvalidManifest(_)));
+
+    write_archive(&path, MANIFEST, &[("Save.toml", save)]);
+    assert!(matches!(archive_error(&path), ArchiveError::MissingEntry(x) if x == "worlds/overworld/World.toml"));
+
+    write_archive(&path, MANIFEST, &[("Save.toml", save), ("worlds/overworld/World.toml", world)]);
+    assert!(read_archive(&path).is_ok());
+
+    // Any changed byte fails the checksum.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[20] ^= 1;
+    write(&path, bytes).unwrap();
+    assert!(matches!(archive_error(&path), ArchiveError::ChecksumMismatch { .. }));
+}